@@ -168,10 +168,19 @@ fn bench_batch_verification(c: &mut Criterion) {
             },
         );
 
+        let message_refs: Vec<&[u8]> = messages.iter().map(|v| v.as_slice()).collect();
+        group.bench_function(
+            format!("{num_validators} randomized batch verification"),
+            |b| {
+                b.iter(|| {
+                    SignatureProjective::verify_batch(&pubkeys, &signatures, &message_refs)
+                        .unwrap();
+                });
+            },
+        );
+
         #[cfg(feature = "parallel")]
         {
-            let message_refs: Vec<&[u8]> = messages.iter().map(|v| v.as_slice()).collect();
-
             group.bench_function(
                 format!("{num_validators} parallel batch verification"),
                 |b| {
@@ -0,0 +1,392 @@
+//! Verification of aggregate signatures against a signer bitmap.
+//!
+//! A certificate consists of an aggregate signature together with a bitmap,
+//! encoded with [`solana_signer_store`], that selects the signers out of an
+//! ordered registry of public keys. Verifying a certificate reconstructs the
+//! aggregate public key from the selected registry entries, checks that the
+//! signers meet a threshold, and verifies the aggregate signature.
+//!
+//! A Base2-encoded bitmap selects a single set of signers that all signed the
+//! same message. A Base3-encoded bitmap selects two disjoint sets of signers:
+//! the base signers signed the primary message and the fallback signers signed
+//! the fallback message, with both contributions aggregated into one
+//! signature.
+
+use {
+    crate::{
+        error::BlsError,
+        pubkey::{AddToPubkeyProjective, PubkeyProjective, VerifiablePubkey},
+        signature::{AsSignatureAffine, SignatureProjective},
+    },
+    alloc::vec::Vec,
+    solana_signer_store::{decode, DecodeError, Decoded},
+    thiserror::Error,
+};
+
+/// The minimum participation required for a certificate to be valid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignerThreshold<'a> {
+    /// At least this many registry entries must have signed.
+    Count(usize),
+    /// The signers must hold at least `min_stake`, where `stakes[i]` is the
+    /// stake of the `i`-th registry entry.
+    Stake { stakes: &'a [u64], min_stake: u64 },
+}
+
+/// An error that can occur while verifying a certificate.
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum CertificateError {
+    #[error("Failed to decode signer bitmap: {0}")]
+    Decode(DecodeError),
+    #[error("Stake list has {actual} entries but the registry has {expected}")]
+    StakesLengthMismatch { expected: usize, actual: usize },
+    #[error("Signer bitmap selects fallback signers but no fallback message was given")]
+    MissingFallbackMessage,
+    #[error("Signer bitmap selects no signers")]
+    NoSigners,
+    #[error("Signer threshold not met")]
+    ThresholdNotMet,
+    #[error("Total signer stake overflowed")]
+    StakeOverflow,
+    #[error(transparent)]
+    Bls(#[from] BlsError),
+}
+
+impl From<DecodeError> for CertificateError {
+    fn from(err: DecodeError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+/// The primary and, for Base3-encoded bitmaps, fallback messages of a
+/// certificate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CertificateMessages<'a> {
+    /// The message signed by the base signers.
+    pub message: &'a [u8],
+    /// The message signed by the fallback signers, if any.
+    pub fallback_message: Option<&'a [u8]>,
+}
+
+impl<'a> CertificateMessages<'a> {
+    /// Messages for a certificate whose signers all signed `message`.
+    pub fn new(message: &'a [u8]) -> Self {
+        Self {
+            message,
+            fallback_message: None,
+        }
+    }
+
+    /// Messages for a certificate with both base and fallback signers.
+    pub fn with_fallback(message: &'a [u8], fallback_message: &'a [u8]) -> Self {
+        Self {
+            message,
+            fallback_message: Some(fallback_message),
+        }
+    }
+}
+
+/// Verifies an aggregate signature against the signers selected by an encoded
+/// bitmap.
+///
+/// `registry` is the ordered list of public keys that the bitmap indexes into;
+/// the bitmap may be shorter than the registry, in which case the trailing
+/// entries did not sign. On success, returns the number of signers.
+///
+/// The aggregate public key is the plain sum of the selected keys, so every
+/// key in `registry` must have had its proof of possession verified before it
+/// was registered. Otherwise, a registered rogue key chosen to cancel out the
+/// others can forge a certificate on their behalf.
+pub fn verify_certificate<P, S>(
+    registry: &[P],
+    bitmap: &[u8],
+    signature: &S,
+    messages: CertificateMessages<'_>,
+    threshold: SignerThreshold<'_>,
+) -> Result<usize, CertificateError>
+where
+    P: AddToPubkeyProjective,
+    S: AsSignatureAffine,
+{
+    if let SignerThreshold::Stake { stakes, .. } = threshold {
+        if stakes.len() != registry.len() {
+            return Err(CertificateError::StakesLengthMismatch {
+                expected: registry.len(),
+                actual: stakes.len(),
+            });
+        }
+    }
+
+    let (base, fallback) = match decode(bitmap, registry.len())? {
+        Decoded::Base2(base) => (base, None),
+        Decoded::Base3(base, fallback) => (base, Some(fallback)),
+    };
+    let base_signers: Vec<usize> = base.iter_ones().collect();
+    let fallback_signers: Vec<usize> = fallback
+        .map(|fallback| fallback.iter_ones().collect())
+        .unwrap_or_default();
+
+    let num_signers = base_signers.len().saturating_add(fallback_signers.len());
+    if num_signers == 0 {
+        return Err(CertificateError::NoSigners);
+    }
+    let threshold_met = match threshold {
+        SignerThreshold::Count(min_signers) => num_signers >= min_signers,
+        SignerThreshold::Stake { stakes, min_stake } => {
+            let total_stake = base_signers
+                .iter()
+                .chain(&fallback_signers)
+                .try_fold(0u64, |total, &i| total.checked_add(stakes[i]))
+                .ok_or(CertificateError::StakeOverflow)?;
+            total_stake >= min_stake
+        }
+    };
+    if !threshold_met {
+        return Err(CertificateError::ThresholdNotMet);
+    }
+
+    let aggregate =
+        |signers: &[usize]| PubkeyProjective::aggregate(signers.iter().map(|&i| &registry[i]));
+    if fallback_signers.is_empty() {
+        aggregate(&base_signers)?.verify_signature(signature, messages.message)?;
+    } else {
+        let fallback_message = messages
+            .fallback_message
+            .ok_or(CertificateError::MissingFallbackMessage)?;
+        let fallback_pubkey = aggregate(&fallback_signers)?;
+        if base_signers.is_empty() {
+            fallback_pubkey.verify_signature(signature, fallback_message)?;
+        } else {
+            let base_pubkey = aggregate(&base_signers)?;
+            SignatureProjective::verify_distinct_aggregated(
+                [base_pubkey, fallback_pubkey].iter(),
+                signature,
+                [messages.message, fallback_message].into_iter(),
+            )?;
+        }
+    }
+    Ok(num_signers)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{keypair::Keypair, pubkey::PubkeyAffine},
+        bitvec::prelude::*,
+        solana_signer_store::{encode_base2, encode_base3},
+    };
+
+    fn registry(n: usize) -> (Vec<Keypair>, Vec<PubkeyAffine>) {
+        let keypairs: Vec<Keypair> = (0..n).map(|_| Keypair::new()).collect();
+        let pubkeys = keypairs.iter().map(|keypair| keypair.public).collect();
+        (keypairs, pubkeys)
+    }
+
+    fn bits(n: usize, signers: &[usize]) -> BitVec<u8, Lsb0> {
+        let mut bits = bitvec![u8, Lsb0; 0; n];
+        for &i in signers {
+            bits.set(i, true);
+        }
+        bits
+    }
+
+    fn sign(keypairs: &[Keypair], signers: &[usize], message: &[u8]) -> SignatureProjective {
+        let signatures: Vec<SignatureProjective> =
+            signers.iter().map(|&i| keypairs[i].sign(message)).collect();
+        SignatureProjective::aggregate(signatures.iter()).unwrap()
+    }
+
+    #[test]
+    fn test_verify_certificate_base2() {
+        let (keypairs, pubkeys) = registry(5);
+        let message = b"notarize";
+        let signers = [0, 2, 3];
+        let bitmap = encode_base2(&bits(5, &signers)).unwrap();
+        let signature = sign(&keypairs, &signers, message);
+
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::new(message),
+                SignerThreshold::Count(3),
+            ),
+            Ok(3)
+        );
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::new(message),
+                SignerThreshold::Count(4),
+            ),
+            Err(CertificateError::ThresholdNotMet)
+        );
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::new(b"skip"),
+                SignerThreshold::Count(3),
+            ),
+            Err(CertificateError::Bls(BlsError::VerificationFailed))
+        );
+
+        // the bitmap claims a signer that did not contribute to the signature
+        let bitmap = encode_base2(&bits(5, &[0, 1, 2, 3])).unwrap();
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::new(message),
+                SignerThreshold::Count(3),
+            ),
+            Err(CertificateError::Bls(BlsError::VerificationFailed))
+        );
+    }
+
+    #[test]
+    fn test_verify_certificate_stake_threshold() {
+        let (keypairs, pubkeys) = registry(4);
+        let stakes = [10, 20, 30, 40];
+        let message = b"finalize";
+        let signers = [1, 3];
+        let bitmap = encode_base2(&bits(4, &signers)).unwrap();
+        let signature = sign(&keypairs, &signers, message);
+        let verify = |threshold| {
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::new(message),
+                threshold,
+            )
+        };
+
+        assert_eq!(
+            verify(SignerThreshold::Stake {
+                stakes: &stakes,
+                min_stake: 60,
+            }),
+            Ok(2)
+        );
+        assert_eq!(
+            verify(SignerThreshold::Stake {
+                stakes: &stakes,
+                min_stake: 61,
+            }),
+            Err(CertificateError::ThresholdNotMet)
+        );
+        assert_eq!(
+            verify(SignerThreshold::Stake {
+                stakes: &stakes[..3],
+                min_stake: 0,
+            }),
+            Err(CertificateError::StakesLengthMismatch {
+                expected: 4,
+                actual: 3,
+            })
+        );
+        assert_eq!(
+            verify(SignerThreshold::Stake {
+                stakes: &[0, u64::MAX, 0, 1],
+                min_stake: 0,
+            }),
+            Err(CertificateError::StakeOverflow)
+        );
+    }
+
+    #[test]
+    fn test_verify_certificate_base3() {
+        let (keypairs, pubkeys) = registry(6);
+        let message = b"notarize";
+        let fallback_message = b"notarize-fallback";
+        let base_signers = [0, 4];
+        let fallback_signers = [1, 2, 5];
+        let bitmap = encode_base3(&bits(6, &base_signers), &bits(6, &fallback_signers)).unwrap();
+        let mut signature = sign(&keypairs, &base_signers, message);
+        signature
+            .aggregate_with([sign(&keypairs, &fallback_signers, fallback_message)].iter())
+            .unwrap();
+
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::with_fallback(message, fallback_message),
+                SignerThreshold::Count(5),
+            ),
+            Ok(5)
+        );
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::new(message),
+                SignerThreshold::Count(5),
+            ),
+            Err(CertificateError::MissingFallbackMessage)
+        );
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::with_fallback(fallback_message, message),
+                SignerThreshold::Count(5),
+            ),
+            Err(CertificateError::Bls(BlsError::VerificationFailed))
+        );
+
+        // only fallback signers
+        let bitmap = encode_base3(&bits(6, &[]), &bits(6, &fallback_signers)).unwrap();
+        let signature = sign(&keypairs, &fallback_signers, fallback_message);
+        assert_eq!(
+            verify_certificate(
+                &pubkeys,
+                &bitmap,
+                &signature,
+                CertificateMessages::with_fallback(message, fallback_message),
+                SignerThreshold::Count(3),
+            ),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn test_verify_certificate_invalid_bitmap() {
+        let (keypairs, pubkeys) = registry(3);
+        let signature = sign(&keypairs, &[0], b"message");
+        let verify = |bitmap: &[u8]| {
+            verify_certificate(
+                &pubkeys,
+                bitmap,
+                &signature,
+                CertificateMessages::new(b"message"),
+                SignerThreshold::Count(0),
+            )
+        };
+
+        assert_eq!(
+            verify(&encode_base2(&bits(4, &[0])).unwrap()),
+            Err(CertificateError::Decode(DecodeError::CorruptDataPayload))
+        );
+        assert_eq!(
+            verify(&[0, 1]),
+            Err(CertificateError::Decode(DecodeError::InputTooShort))
+        );
+        assert_eq!(
+            verify(&encode_base2(&bits(3, &[])).unwrap()),
+            Err(CertificateError::NoSigners)
+        );
+        // a bitmap shorter than the registry leaves the trailing entries unset
+        assert_eq!(verify(&encode_base2(&bits(1, &[0])).unwrap()), Ok(1));
+    }
+}
@@ -30,6 +30,8 @@ pub use crate::{
     },
 };
 
+#[cfg(all(not(target_os = "solana"), feature = "signer-store"))]
+pub mod certificate;
 pub mod error;
 #[cfg(not(target_os = "solana"))]
 pub mod keypair;
@@ -42,3 +44,5 @@ pub mod pubkey;
 #[cfg(not(target_os = "solana"))]
 pub mod secret_key;
 pub mod signature;
+#[cfg(not(target_os = "solana"))]
+pub mod threshold;
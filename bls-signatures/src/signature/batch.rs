@@ -0,0 +1,295 @@
+//! Randomized batch verification of independent signatures.
+//!
+//! Each `(pubkey, message, signature)` triple in a batch is weighted by a fresh
+//! random 128-bit scalar `r_i`, and the whole batch is checked with a single
+//! multi-pairing:
+//!
+//! `prod_i e(r_i * pubkey_i, H(message_i)) * e(-g1, sum_i r_i * signature_i) = 1`
+//!
+//! Unlike [`SignatureProjective::verify_distinct`], which aggregates the
+//! signatures as given, the random weights prevent invalid signatures from
+//! cancelling each other out. A batch containing an invalid signature passes
+//! with probability at most 2^-127.
+//!
+//! When a batch fails, [`SignatureProjective::find_invalid_in_batch`] bisects
+//! it to identify the offending entries.
+
+#[cfg(feature = "std")]
+use crate::pubkey::points::NEG_G1_GENERATOR_AFFINE;
+#[cfg(not(feature = "std"))]
+use blstrs::G1Projective;
+use {
+    crate::{
+        error::BlsError,
+        hash::hash_signature_message_to_point,
+        pubkey::AsPubkeyAffine,
+        signature::{AsSignatureAffine, SignatureProjective},
+    },
+    alloc::vec::Vec,
+    blstrs::{Bls12, G1Affine, G2Affine, G2Prepared, G2Projective, Gt, Scalar},
+    ff::PrimeField,
+    group::{Curve, Group},
+    pairing::{MillerLoopResult, MultiMillerLoop},
+    rand::{rngs::OsRng, Rng},
+};
+
+/// A batch entry with its points decoded and its message hashed.
+struct PreparedEntry {
+    pubkey: G1Affine,
+    signature: G2Affine,
+    hashed_message: G2Prepared,
+}
+
+impl PreparedEntry {
+    fn new<P, S>(pubkey: &P, signature: &S, message: &[u8]) -> Result<Self, BlsError>
+    where
+        P: AsPubkeyAffine + ?Sized,
+        S: AsSignatureAffine + ?Sized,
+    {
+        let hashed_message: G2Affine = hash_signature_message_to_point(message).into();
+        Ok(Self {
+            pubkey: pubkey.try_as_affine()?.0,
+            signature: signature.try_as_affine()?.0,
+            hashed_message: G2Prepared::from(hashed_message),
+        })
+    }
+}
+
+impl SignatureProjective {
+    /// Verifies a batch of independent signatures, each over its own message
+    /// and public key, with a single randomized multi-pairing.
+    pub fn verify_batch<P, S>(
+        public_keys: &[P],
+        signatures: &[S],
+        messages: &[&[u8]],
+    ) -> Result<(), BlsError>
+    where
+        P: AsPubkeyAffine,
+        S: AsSignatureAffine,
+    {
+        if public_keys.len() != messages.len() || public_keys.len() != signatures.len() {
+            return Err(BlsError::InputLengthMismatch);
+        }
+        if public_keys.is_empty() {
+            return Err(BlsError::EmptyAggregation);
+        }
+
+        let entries = public_keys
+            .iter()
+            .zip(signatures)
+            .zip(messages)
+            .map(|((pubkey, signature), message)| {
+                PreparedEntry::new(pubkey, signature, message).map(Some)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let indices: Vec<usize> = (0..entries.len()).collect();
+        verify_entries(&entries, &indices)
+            .then_some(())
+            .ok_or(BlsError::VerificationFailed)
+    }
+
+    /// Returns the indices, in ascending order, of the entries of a batch that
+    /// fail verification.
+    ///
+    /// The batch is first checked as a whole. If that fails, it is split in
+    /// half recursively until every invalid entry is isolated, so a batch with
+    /// `k` invalid entries out of `n` costs `O(k log n)` multi-pairings. Entries
+    /// whose public key or signature cannot be decoded are reported as invalid.
+    pub fn find_invalid_in_batch<P, S>(
+        public_keys: &[P],
+        signatures: &[S],
+        messages: &[&[u8]],
+    ) -> Result<Vec<usize>, BlsError>
+    where
+        P: AsPubkeyAffine,
+        S: AsSignatureAffine,
+    {
+        if public_keys.len() != messages.len() || public_keys.len() != signatures.len() {
+            return Err(BlsError::InputLengthMismatch);
+        }
+
+        let mut invalid = Vec::new();
+        let mut entries = Vec::with_capacity(public_keys.len());
+        let mut indices = Vec::with_capacity(public_keys.len());
+        for (i, ((pubkey, signature), message)) in
+            public_keys.iter().zip(signatures).zip(messages).enumerate()
+        {
+            match PreparedEntry::new(pubkey, signature, message) {
+                Ok(entry) => {
+                    entries.push(Some(entry));
+                    indices.push(i);
+                }
+                Err(_) => {
+                    entries.push(None);
+                    invalid.push(i);
+                }
+            }
+        }
+
+        bisect(&entries, &indices, false, &mut invalid);
+        invalid.sort_unstable();
+        Ok(invalid)
+    }
+}
+
+/// Collects the invalid entries among `indices` into `invalid`.
+///
+/// If `known_invalid` is set, the caller has already established that at least
+/// one of the entries is invalid, so the check of the whole range is skipped.
+fn bisect(
+    entries: &[Option<PreparedEntry>],
+    indices: &[usize],
+    known_invalid: bool,
+    invalid: &mut Vec<usize>,
+) {
+    if indices.is_empty() || (!known_invalid && verify_entries(entries, indices)) {
+        return;
+    }
+    if let [index] = indices {
+        invalid.push(*index);
+        return;
+    }
+    let (left, right) = indices.split_at(indices.len() / 2);
+    let left_valid = verify_entries(entries, left);
+    if !left_valid {
+        bisect(entries, left, true, invalid);
+    }
+    // if the left half is valid, the failure must come from the right half
+    bisect(entries, right, left_valid, invalid);
+}
+
+/// Checks the entries at `indices` with a single randomized multi-pairing.
+#[allow(clippy::arithmetic_side_effects)]
+fn verify_entries(entries: &[Option<PreparedEntry>], indices: &[usize]) -> bool {
+    let mut rng = OsRng;
+    let mut pubkeys = Vec::with_capacity(indices.len());
+    let mut hashed_messages = Vec::with_capacity(indices.len());
+    let mut aggregate_signature = G2Projective::identity();
+    for &i in indices {
+        let Some(entry) = entries[i].as_ref() else {
+            return false;
+        };
+        // an odd scalar is never zero, which would drop the entry from the check
+        let scalar = Scalar::from_u128(rng.gen::<u128>() | 1);
+        pubkeys.push((entry.pubkey * scalar).to_affine());
+        hashed_messages.push(&entry.hashed_message);
+        aggregate_signature += entry.signature * scalar;
+    }
+    let signature_prepared = G2Prepared::from(aggregate_signature.to_affine());
+
+    #[cfg(feature = "std")]
+    let neg_g1_generator = &*NEG_G1_GENERATOR_AFFINE;
+    #[cfg(not(feature = "std"))]
+    let neg_g1_generator_val: G1Affine = (-G1Projective::generator()).into();
+    #[cfg(not(feature = "std"))]
+    let neg_g1_generator = &neg_g1_generator_val;
+
+    let mut terms: Vec<_> = pubkeys.iter().zip(hashed_messages).collect();
+    terms.push((neg_g1_generator, &signature_prepared));
+    Bls12::multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{keypair::Keypair, pubkey::Pubkey, signature::Signature},
+        std::{format, vec},
+    };
+
+    fn batch(n: usize) -> (Vec<Pubkey>, Vec<Signature>, Vec<Vec<u8>>) {
+        let keypairs: Vec<Keypair> = (0..n).map(|_| Keypair::new()).collect();
+        let messages: Vec<Vec<u8>> = (0..n).map(|i| format!("vote {i}").into_bytes()).collect();
+        let signatures = keypairs
+            .iter()
+            .zip(&messages)
+            .map(|(keypair, message)| keypair.sign(message).into())
+            .collect();
+        let pubkeys = keypairs
+            .iter()
+            .map(|keypair| keypair.public.into())
+            .collect();
+        (pubkeys, signatures, messages)
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let (pubkeys, signatures, messages) = batch(8);
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+
+        assert_eq!(
+            SignatureProjective::verify_batch(&pubkeys, &signatures, &messages),
+            Ok(())
+        );
+        assert_eq!(
+            SignatureProjective::find_invalid_in_batch(&pubkeys, &signatures, &messages),
+            Ok(vec![])
+        );
+
+        let mut swapped = messages.clone();
+        swapped.swap(2, 5);
+        assert_eq!(
+            SignatureProjective::verify_batch(&pubkeys, &signatures, &swapped),
+            Err(BlsError::VerificationFailed)
+        );
+
+        assert_eq!(
+            SignatureProjective::verify_batch(&pubkeys, &signatures[..7], &messages),
+            Err(BlsError::InputLengthMismatch)
+        );
+        assert_eq!(
+            SignatureProjective::verify_batch::<Pubkey, Signature>(&[], &[], &[]),
+            Err(BlsError::EmptyAggregation)
+        );
+    }
+
+    #[test]
+    fn test_verify_batch_rejects_cancelling_signatures() {
+        // Two invalid signatures whose errors cancel out still aggregate to a
+        // valid aggregate signature, so only the randomized check catches them.
+        let (pubkeys, signatures, messages) = batch(2);
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+        let offset = Keypair::new().sign(b"offset");
+        let mut first: SignatureProjective = signatures[0].try_into().unwrap();
+        let mut second: SignatureProjective = signatures[1].try_into().unwrap();
+        first.aggregate_with([offset].iter()).unwrap();
+        second.0 -= offset.0;
+        let tampered = [first, second];
+
+        assert_eq!(
+            SignatureProjective::verify_distinct(
+                pubkeys.iter(),
+                tampered.iter(),
+                messages.iter().copied(),
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            SignatureProjective::verify_batch(&pubkeys, &tampered, &messages),
+            Err(BlsError::VerificationFailed)
+        );
+        assert_eq!(
+            SignatureProjective::find_invalid_in_batch(&pubkeys, &tampered, &messages),
+            Ok(vec![0, 1])
+        );
+    }
+
+    #[test]
+    fn test_find_invalid_in_batch() {
+        let (pubkeys, mut signatures, messages) = batch(13);
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+        signatures[3] = signatures[4];
+        signatures[12] = Keypair::new().sign(messages[12]).into();
+        // not a valid curve point
+        signatures[7] = Signature([0xff; 192]);
+
+        assert_eq!(
+            SignatureProjective::verify_batch(&pubkeys, &signatures, &messages),
+            Err(BlsError::PointConversion)
+        );
+        assert_eq!(
+            SignatureProjective::find_invalid_in_batch(&pubkeys, &signatures, &messages),
+            Ok(vec![3, 7, 12])
+        );
+    }
+}
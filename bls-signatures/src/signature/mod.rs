@@ -1,3 +1,5 @@
+#[cfg(not(target_os = "solana"))]
+pub mod batch;
 pub mod bytes;
 pub mod conversion;
 pub mod points;
@@ -0,0 +1,339 @@
+//! Threshold signatures through Shamir secret sharing of a BLS secret key.
+//!
+//! A dealer splits a [`SecretKey`] into `n` shares such that any `t` of them
+//! can produce a signature that is valid under the original public key, while
+//! fewer than `t` reveal nothing about the key. Each share holder signs
+//! independently, and the resulting [`SignatureShare`]s are combined with
+//! Lagrange interpolation at zero.
+//!
+//! The dealer also publishes a Feldman [`ShareCommitment`] to the sharing
+//! polynomial, which lets every share holder check that their share is
+//! consistent with the group public key, and lets anyone verify an individual
+//! signature share before combining it.
+
+use {
+    crate::{
+        error::BlsError,
+        pubkey::{PubkeyProjective, VerifiablePubkey},
+        secret_key::SecretKey,
+        signature::{AsSignatureAffine, SignatureProjective},
+    },
+    alloc::vec::Vec,
+    blstrs::{G1Projective, G2Projective, Scalar},
+    ff::Field,
+    group::Group,
+    rand::rngs::OsRng,
+    thiserror::Error,
+};
+
+/// An error that can occur while splitting a key or combining shares.
+#[derive(Error, Clone, Debug, Eq, PartialEq)]
+pub enum ThresholdError {
+    #[error("Threshold {threshold} is invalid for {num_shares} shares")]
+    InvalidThreshold { threshold: usize, num_shares: usize },
+    #[error("Share index must be nonzero")]
+    ZeroShareIndex,
+    #[error("Duplicate share index {0}")]
+    DuplicateShareIndex(u32),
+    #[error("No shares to combine")]
+    EmptyShares,
+    #[error("Commitment has no coefficients")]
+    EmptyCommitment,
+    #[error("Secret key share is inconsistent with the commitment")]
+    InvalidShare,
+    #[error(transparent)]
+    Bls(#[from] BlsError),
+}
+
+/// A share of a secret key, held by a single participant.
+///
+/// The share is the evaluation of the sharing polynomial at `index`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SecretKeyShare {
+    pub index: u32,
+    pub secret: SecretKey,
+}
+
+impl SecretKeyShare {
+    /// The public key corresponding to this share
+    pub fn public(&self) -> PubkeyProjective {
+        PubkeyProjective::from_secret(&self.secret)
+    }
+
+    /// Sign a message with this share
+    pub fn sign(&self, message: &[u8]) -> SignatureShare {
+        SignatureShare {
+            index: self.index,
+            signature: self.secret.sign(message),
+        }
+    }
+}
+
+/// A signature produced by a single [`SecretKeyShare`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SignatureShare {
+    pub index: u32,
+    pub signature: SignatureProjective,
+}
+
+/// A Feldman commitment to a sharing polynomial.
+///
+/// The commitment holds `g1 * a_j` for every coefficient `a_j`, starting with
+/// the constant term, which is the group public key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShareCommitment(Vec<PubkeyProjective>);
+
+impl ShareCommitment {
+    /// Constructs a commitment from the commitments to the polynomial
+    /// coefficients, starting with the constant term
+    pub fn new(coefficients: Vec<PubkeyProjective>) -> Result<Self, ThresholdError> {
+        if coefficients.is_empty() {
+            return Err(ThresholdError::EmptyCommitment);
+        }
+        Ok(Self(coefficients))
+    }
+
+    /// The number of shares needed to produce a signature
+    pub fn threshold(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The public key under which combined signatures verify
+    pub fn group_pubkey(&self) -> PubkeyProjective {
+        self.0[0]
+    }
+
+    /// The commitments to the polynomial coefficients, starting with the
+    /// constant term
+    pub fn coefficients(&self) -> &[PubkeyProjective] {
+        &self.0
+    }
+
+    /// Computes the public key of the share at `index` from the commitment.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn pubkey_share(&self, index: u32) -> Result<PubkeyProjective, ThresholdError> {
+        if index == 0 {
+            return Err(ThresholdError::ZeroShareIndex);
+        }
+        let x = Scalar::from(u64::from(index));
+        let point = self
+            .0
+            .iter()
+            .rev()
+            .fold(G1Projective::identity(), |acc, coefficient| {
+                acc * x + coefficient.0
+            });
+        Ok(PubkeyProjective(point))
+    }
+
+    /// Checks that a secret key share is consistent with the commitment.
+    pub fn verify_share(&self, share: &SecretKeyShare) -> Result<(), ThresholdError> {
+        (self.pubkey_share(share.index)? == share.public())
+            .then_some(())
+            .ok_or(ThresholdError::InvalidShare)
+    }
+
+    /// Verifies a signature share against the public key of its share.
+    pub fn verify_signature_share(
+        &self,
+        share: &SignatureShare,
+        message: &[u8],
+    ) -> Result<(), ThresholdError> {
+        self.pubkey_share(share.index)?
+            .verify_signature(&share.signature, message)?;
+        Ok(())
+    }
+
+    /// Verifies a combined signature against the group public key.
+    pub fn verify_signature<S: AsSignatureAffine>(
+        &self,
+        signature: &S,
+        message: &[u8],
+    ) -> Result<(), ThresholdError> {
+        self.group_pubkey().verify_signature(signature, message)?;
+        Ok(())
+    }
+}
+
+/// Splits a secret key into `num_shares` shares, any `threshold` of which can
+/// sign on its behalf.
+///
+/// Shares are assigned the indices `1..=num_shares`. Returns the shares along
+/// with the commitment to the sharing polynomial, which should be published to
+/// all share holders.
+#[allow(clippy::arithmetic_side_effects)]
+pub fn split_secret_key(
+    secret: &SecretKey,
+    threshold: usize,
+    num_shares: usize,
+) -> Result<(Vec<SecretKeyShare>, ShareCommitment), ThresholdError> {
+    if threshold == 0 || threshold > num_shares || u32::try_from(num_shares).is_err() {
+        return Err(ThresholdError::InvalidThreshold {
+            threshold,
+            num_shares,
+        });
+    }
+
+    let mut rng = OsRng;
+    let mut coefficients = Vec::with_capacity(threshold);
+    coefficients.push(secret.0);
+    coefficients.extend((1..threshold).map(|_| Scalar::random(&mut rng)));
+
+    let shares = (1..=num_shares as u32)
+        .map(|index| {
+            let x = Scalar::from(u64::from(index));
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(Scalar::ZERO, |acc, coefficient| acc * x + coefficient);
+            SecretKeyShare {
+                index,
+                secret: SecretKey(y),
+            }
+        })
+        .collect();
+    let commitment = ShareCommitment(
+        coefficients
+            .iter()
+            .map(|coefficient| PubkeyProjective(G1Projective::generator() * coefficient))
+            .collect(),
+    );
+    Ok((shares, commitment))
+}
+
+/// Combines signature shares into a signature under the group public key.
+///
+/// The result is only valid if at least `threshold` valid shares are given;
+/// shares can be checked individually with
+/// [`ShareCommitment::verify_signature_share`].
+#[allow(clippy::arithmetic_side_effects)]
+pub fn combine_signature_shares(
+    shares: &[SignatureShare],
+) -> Result<SignatureProjective, ThresholdError> {
+    if shares.is_empty() {
+        return Err(ThresholdError::EmptyShares);
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            return Err(ThresholdError::ZeroShareIndex);
+        }
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(ThresholdError::DuplicateShareIndex(share.index));
+        }
+    }
+
+    let xs: Vec<Scalar> = shares
+        .iter()
+        .map(|share| Scalar::from(u64::from(share.index)))
+        .collect();
+    let mut combined = G2Projective::identity();
+    for (i, share) in shares.iter().enumerate() {
+        // Lagrange basis polynomial for `x_i`, evaluated at zero
+        let (numerator, denominator) = xs.iter().enumerate().filter(|(j, _)| *j != i).fold(
+            (Scalar::ONE, Scalar::ONE),
+            |(numerator, denominator), (_, x_j)| (numerator * x_j, denominator * (x_j - xs[i])),
+        );
+        // the denominator is nonzero since the indices are distinct
+        let coefficient = numerator * denominator.invert().unwrap();
+        combined += share.signature.0 * coefficient;
+    }
+    Ok(SignatureProjective(combined))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::keypair::Keypair};
+
+    #[test]
+    fn test_threshold_signature() {
+        let keypair = Keypair::new();
+        let (shares, commitment) = split_secret_key(&keypair.secret, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(commitment.threshold(), 3);
+        assert_eq!(commitment.group_pubkey(), keypair.public.into());
+        for share in &shares {
+            assert_eq!(commitment.verify_share(share), Ok(()));
+        }
+
+        let message = b"vote";
+        let signature_shares: Vec<SignatureShare> =
+            shares.iter().map(|share| share.sign(message)).collect();
+        for share in &signature_shares {
+            assert_eq!(commitment.verify_signature_share(share, message), Ok(()));
+        }
+
+        // any three shares, in any order, combine to the same signature
+        let expected = keypair.sign(message);
+        for subset in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<SignatureShare> = subset.iter().map(|&i| signature_shares[i]).collect();
+            let signature = combine_signature_shares(&subset).unwrap();
+            assert_eq!(signature, expected);
+            assert_eq!(commitment.verify_signature(&signature, message), Ok(()));
+        }
+
+        // two shares are not enough
+        let signature = combine_signature_shares(&signature_shares[..2]).unwrap();
+        assert_eq!(
+            commitment.verify_signature(&signature, message),
+            Err(ThresholdError::Bls(BlsError::VerificationFailed))
+        );
+    }
+
+    #[test]
+    fn test_invalid_shares() {
+        let keypair = Keypair::new();
+        let (shares, commitment) = split_secret_key(&keypair.secret, 2, 3).unwrap();
+
+        let mut tampered = shares[1].clone();
+        tampered.secret = shares[2].secret.clone();
+        assert_eq!(
+            commitment.verify_share(&tampered),
+            Err(ThresholdError::InvalidShare)
+        );
+        let signature_share = tampered.sign(b"vote");
+        assert_eq!(
+            commitment.verify_signature_share(&signature_share, b"vote"),
+            Err(ThresholdError::Bls(BlsError::VerificationFailed))
+        );
+
+        let signature_shares = [shares[0].sign(b"vote"), shares[0].sign(b"vote")];
+        assert_eq!(
+            combine_signature_shares(&signature_shares),
+            Err(ThresholdError::DuplicateShareIndex(1))
+        );
+        assert_eq!(
+            combine_signature_shares(&[]),
+            Err(ThresholdError::EmptyShares)
+        );
+        assert_eq!(
+            commitment.pubkey_share(0),
+            Err(ThresholdError::ZeroShareIndex)
+        );
+        assert_eq!(
+            ShareCommitment::new(commitment.coefficients().to_vec()),
+            Ok(commitment)
+        );
+        assert_eq!(
+            ShareCommitment::new(Vec::new()),
+            Err(ThresholdError::EmptyCommitment)
+        );
+    }
+
+    #[test]
+    fn test_split_secret_key_invalid_threshold() {
+        let keypair = Keypair::new();
+        for (threshold, num_shares) in [(0, 3), (4, 3)] {
+            assert_eq!(
+                split_secret_key(&keypair.secret, threshold, num_shares),
+                Err(ThresholdError::InvalidThreshold {
+                    threshold,
+                    num_shares,
+                })
+            );
+        }
+
+        // a threshold of one gives every share the full key
+        let (shares, _) = split_secret_key(&keypair.secret, 1, 2).unwrap();
+        assert!(shares.iter().all(|share| share.secret == keypair.secret));
+    }
+}
@@ -0,0 +1,453 @@
+//! Composing compute budget instructions into messages.
+//!
+//! [`ComputeBudgetBuilder`] prepends compute budget instructions to the
+//! instructions of a transaction, merging them with any compute budget
+//! instructions those already contain, and computes the fee the resulting
+//! transaction pays.
+
+use {
+    crate::{
+        check_id,
+        requested::{
+            invalid_instruction_data_error, prioritization_fee, InstructionCounts,
+            RequestedComputeBudget,
+        },
+        ComputeBudgetInstruction, DefaultComputeUnitLimit,
+    },
+    solana_address::Address,
+    solana_fee_structure::{FeeDetails, FeeStructure},
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+    solana_message::Message,
+    solana_transaction_error::TransactionError,
+};
+
+/// Builds instruction lists and messages which request a compute budget.
+///
+/// Requests set on the builder are treated as compute budget instructions
+/// placed before the caller's instructions. Every kind of compute budget
+/// instruction may only appear once in a transaction, so an instruction which
+/// repeats an earlier request with the same value is dropped, while one which
+/// requests a different value is rejected with
+/// [`TransactionError::DuplicateInstruction`], as the runtime would.
+///
+/// Errors report instruction indexes as if the builder's requests were
+/// prepended to the caller's instructions, one instruction per request in
+/// the order: compute unit limit, compute unit price, heap frame, loaded
+/// accounts data size limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeBudgetBuilder {
+    requested: RequestedComputeBudget,
+    default_compute_unit_limit: DefaultComputeUnitLimit,
+}
+
+impl ComputeBudgetBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request a compute unit limit for the transaction.
+    pub fn with_compute_unit_limit(mut self, units: u32) -> Self {
+        self.requested.compute_unit_limit = Some(units);
+        self
+    }
+
+    /// Pay `micro_lamports` for each requested compute unit.
+    pub fn with_compute_unit_price(mut self, micro_lamports: u64) -> Self {
+        self.requested.compute_unit_price = Some(micro_lamports);
+        self
+    }
+
+    /// Request a heap frame of `bytes` for each program in the transaction.
+    pub fn with_heap_frame(mut self, bytes: u32) -> Self {
+        self.requested.heap_frame_bytes = Some(bytes);
+        self
+    }
+
+    /// Limit the total size of the accounts loaded by the transaction.
+    pub fn with_loaded_accounts_data_size_limit(mut self, bytes: u32) -> Self {
+        self.requested.loaded_accounts_data_size_limit = Some(bytes);
+        self
+    }
+
+    /// Allot compute units as `default_compute_unit_limit` describes when
+    /// computing fees without a requested compute unit limit, which defaults
+    /// to [`DefaultComputeUnitLimit::ReserveMinimalForBuiltins`].
+    pub fn with_default_compute_unit_limit(
+        mut self,
+        default_compute_unit_limit: DefaultComputeUnitLimit,
+    ) -> Self {
+        self.default_compute_unit_limit = default_compute_unit_limit;
+        self
+    }
+
+    fn requested_instructions(&self) -> impl Iterator<Item = ComputeBudgetInstruction> {
+        let RequestedComputeBudget {
+            compute_unit_limit,
+            compute_unit_price,
+            heap_frame_bytes,
+            loaded_accounts_data_size_limit,
+            ..
+        } = self.requested;
+        compute_unit_limit
+            .map(ComputeBudgetInstruction::SetComputeUnitLimit)
+            .into_iter()
+            .chain(compute_unit_price.map(ComputeBudgetInstruction::SetComputeUnitPrice))
+            .chain(heap_frame_bytes.map(ComputeBudgetInstruction::RequestHeapFrame))
+            .chain(
+                loaded_accounts_data_size_limit
+                    .map(ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit),
+            )
+    }
+
+    /// Merge the builder's requests with the compute budget instructions
+    /// found in `instructions`.
+    ///
+    /// Returns the merged requests along with the instructions which are not
+    /// compute budget instructions.
+    fn try_merge<'a>(
+        &self,
+        instructions: &'a [Instruction],
+    ) -> Result<(RequestedComputeBudget, Vec<&'a Instruction>), TransactionError> {
+        let mut requested = RequestedComputeBudget::default();
+        let mut index = 0usize;
+        for instruction in self.requested_instructions() {
+            requested.try_insert(index, &instruction)?;
+            index = index.saturating_add(1);
+        }
+
+        let mut other_instructions = Vec::with_capacity(instructions.len());
+        for instruction in instructions {
+            if check_id(&instruction.program_id) {
+                let compute_budget_instruction =
+                    ComputeBudgetInstruction::from_instruction_data(&instruction.data)
+                        .ok_or_else(|| invalid_instruction_data_error(index as u8))?;
+                if !requested.contains(&compute_budget_instruction) {
+                    requested.try_insert(index, &compute_budget_instruction)?;
+                }
+            } else {
+                other_instructions.push(instruction);
+            }
+            index = index.saturating_add(1);
+        }
+        requested.sanitize()?;
+
+        Ok((requested, other_instructions))
+    }
+
+    /// Returns `instructions` preceded by one compute budget instruction for
+    /// each request, with compute budget instructions already present in
+    /// `instructions` moved to the front and deduplicated.
+    pub fn try_build_instructions(
+        &self,
+        instructions: &[Instruction],
+    ) -> Result<Vec<Instruction>, TransactionError> {
+        let (requested, other_instructions) = self.try_merge(instructions)?;
+        Ok(Self { requested, ..*self }
+            .requested_instructions()
+            .map(|instruction| instruction.to_instruction())
+            .chain(other_instructions.into_iter().cloned())
+            .collect())
+    }
+
+    /// Create a message from the instructions returned by
+    /// [`try_build_instructions`](Self::try_build_instructions).
+    pub fn try_build_message(
+        &self,
+        instructions: &[Instruction],
+        payer: Option<&Address>,
+        recent_blockhash: &Hash,
+    ) -> Result<Message, TransactionError> {
+        let instructions = self.try_build_instructions(instructions)?;
+        Ok(Message::new_with_blockhash(
+            &instructions,
+            payer,
+            recent_blockhash,
+        ))
+    }
+
+    /// Fee paid by a transaction built from `instructions` which carries
+    /// `num_signatures` signatures.
+    ///
+    /// The prioritization fee is the compute unit price multiplied by the
+    /// compute unit limit, rounded up to the nearest lamport. Without a
+    /// requested compute unit limit, each instruction of the built
+    /// transaction, including its compute budget instructions, is allotted
+    /// compute units as described by [`DefaultComputeUnitLimit`].
+    pub fn try_fee_details(
+        &self,
+        instructions: &[Instruction],
+        num_signatures: u64,
+        fee_structure: &FeeStructure,
+    ) -> Result<FeeDetails, TransactionError> {
+        let (requested, other_instructions) = self.try_merge(instructions)?;
+        let mut instruction_counts = InstructionCounts::default();
+        instruction_counts.add_compute_budget_instructions(
+            Self { requested, ..*self }.requested_instructions().count(),
+        );
+        for instruction in other_instructions {
+            instruction_counts.add(&instruction.program_id);
+        }
+        let compute_unit_limit =
+            requested.compute_unit_limit(&instruction_counts, self.default_compute_unit_limit);
+        Ok(FeeDetails::new(
+            num_signatures.saturating_mul(fee_structure.lamports_per_signature),
+            prioritization_fee(
+                requested.compute_unit_price.unwrap_or_default(),
+                compute_unit_limit,
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {
+        super::*,
+        crate::{MAX_COMPUTE_UNIT_LIMIT, MIN_HEAP_FRAME_BYTES},
+        solana_instruction::AccountMeta,
+        solana_instruction_error::InstructionError,
+    };
+
+    fn program_instruction() -> Instruction {
+        Instruction::new_with_bytes(
+            Address::new_unique(),
+            &[1, 2, 3],
+            vec![AccountMeta::new(Address::new_unique(), false)],
+        )
+    }
+
+    #[test]
+    fn test_try_build_instructions_prepends_requests() {
+        let instructions = vec![program_instruction(), program_instruction()];
+        let built = ComputeBudgetBuilder::new()
+            .with_loaded_accounts_data_size_limit(1024)
+            .with_compute_unit_price(5)
+            .with_compute_unit_limit(1_000)
+            .try_build_instructions(&instructions)
+            .unwrap();
+        assert_eq!(
+            built,
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(1_000),
+                ComputeBudgetInstruction::set_compute_unit_price(5),
+                ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(1024),
+                instructions[0].clone(),
+                instructions[1].clone(),
+            ]
+        );
+
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .try_build_instructions(&instructions)
+                .unwrap(),
+            instructions
+        );
+    }
+
+    #[test]
+    fn test_try_build_instructions_deduplicates() {
+        let instructions = vec![
+            program_instruction(),
+            ComputeBudgetInstruction::set_compute_unit_price(5),
+            ComputeBudgetInstruction::request_heap_frame(MIN_HEAP_FRAME_BYTES * 2),
+            ComputeBudgetInstruction::set_compute_unit_price(5),
+        ];
+        let built = ComputeBudgetBuilder::new()
+            .with_compute_unit_price(5)
+            .try_build_instructions(&instructions)
+            .unwrap();
+        assert_eq!(
+            built,
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_price(5),
+                ComputeBudgetInstruction::request_heap_frame(MIN_HEAP_FRAME_BYTES * 2),
+                instructions[0].clone(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_build_instructions_rejects_conflicts() {
+        let instructions = vec![
+            program_instruction(),
+            ComputeBudgetInstruction::set_compute_unit_limit(10),
+        ];
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_price(1)
+                .with_compute_unit_limit(20)
+                .try_build_instructions(&instructions),
+            Err(TransactionError::DuplicateInstruction(3))
+        );
+
+        let instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(1),
+            ComputeBudgetInstruction::set_compute_unit_price(2),
+        ];
+        assert_eq!(
+            ComputeBudgetBuilder::new().try_build_instructions(&instructions),
+            Err(TransactionError::DuplicateInstruction(1))
+        );
+    }
+
+    #[test]
+    fn test_try_build_instructions_rejects_invalid_requests() {
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_heap_frame(MIN_HEAP_FRAME_BYTES + 1)
+                .try_build_instructions(&[]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_loaded_accounts_data_size_limit(0)
+                .try_build_instructions(&[]),
+            Err(TransactionError::InvalidLoadedAccountsDataSizeLimit)
+        );
+
+        let invalid_instruction = Instruction::new_with_bytes(crate::id(), &[9], vec![]);
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_limit(1)
+                .try_build_instructions(&[program_instruction(), invalid_instruction]),
+            Err(TransactionError::InstructionError(
+                2,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+    }
+
+    #[test]
+    fn test_try_build_message() {
+        let payer = Address::new_unique();
+        let instructions = vec![program_instruction()];
+        let recent_blockhash = Hash::new_unique();
+        let builder = ComputeBudgetBuilder::new().with_compute_unit_limit(300);
+        assert_eq!(
+            builder
+                .try_build_message(&instructions, Some(&payer), &recent_blockhash)
+                .unwrap(),
+            Message::new_with_blockhash(
+                &builder.try_build_instructions(&instructions).unwrap(),
+                Some(&payer),
+                &recent_blockhash,
+            )
+        );
+    }
+
+    #[test]
+    fn test_try_fee_details() {
+        let fee_structure = FeeStructure::default();
+        let instructions = vec![program_instruction(), program_instruction()];
+
+        // no price, no prioritization fee
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .try_fee_details(&instructions, 2, &fee_structure)
+                .unwrap(),
+            FeeDetails::new(10_000, 0)
+        );
+
+        // default limit of 200k compute units for each program instruction
+        // and 3k for the compute unit price instruction, rounded up
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_price(1)
+                .try_fee_details(&instructions, 1, &fee_structure)
+                .unwrap(),
+            FeeDetails::new(5_000, 1)
+        );
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_price(7_000_001)
+                .try_fee_details(&instructions, 1, &fee_structure)
+                .unwrap(),
+            FeeDetails::new(5_000, 2_821_001)
+        );
+
+        // requested limit
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_price(1_000_000)
+                .with_compute_unit_limit(1_000)
+                .try_fee_details(&instructions, 1, &fee_structure)
+                .unwrap(),
+            FeeDetails::new(5_000, 1_000)
+        );
+
+        // limit is capped
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_price(1_000_000)
+                .with_compute_unit_limit(u32::MAX)
+                .try_fee_details(&instructions, 1, &fee_structure)
+                .unwrap()
+                .prioritization_fee(),
+            u64::from(MAX_COMPUTE_UNIT_LIMIT)
+        );
+
+        // price from the instructions, saturating
+        assert_eq!(
+            ComputeBudgetBuilder::new()
+                .with_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT)
+                .try_fee_details(
+                    &[ComputeBudgetInstruction::set_compute_unit_price(u64::MAX)],
+                    1,
+                    &fee_structure
+                )
+                .unwrap()
+                .prioritization_fee(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_try_fee_details_without_compute_unit_limit() {
+        let fee_structure = FeeStructure::default();
+        let mut data = vec![2, 0, 0, 0];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        let transfer = Instruction::new_with_bytes(
+            solana_sdk_ids::system_program::id(),
+            &data,
+            vec![
+                AccountMeta::new(Address::new_unique(), true),
+                AccountMeta::new(Address::new_unique(), false),
+            ],
+        );
+        let builder = ComputeBudgetBuilder::new().with_compute_unit_price(1_000_000);
+
+        // the transfer and the compute unit price instruction are each
+        // allotted 3k compute units by the runtime
+        assert_eq!(
+            builder
+                .try_fee_details(std::slice::from_ref(&transfer), 1, &fee_structure)
+                .unwrap(),
+            FeeDetails::new(5_000, 6_000)
+        );
+        // another program's instruction is allotted 200k compute units
+        assert_eq!(
+            builder
+                .try_fee_details(
+                    &[transfer.clone(), program_instruction()],
+                    1,
+                    &fee_structure
+                )
+                .unwrap(),
+            FeeDetails::new(5_000, 206_000)
+        );
+        assert_eq!(
+            builder
+                .with_default_compute_unit_limit(
+                    DefaultComputeUnitLimit::PerNonComputeBudgetInstruction
+                )
+                .try_fee_details(&[transfer], 1, &fee_structure)
+                .unwrap(),
+            FeeDetails::new(5_000, 200_000)
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Calculating transaction fees offline.
+//!
+//! A transaction pays a signature fee of
+//! [`FeeStructure::lamports_per_signature`] for each of its signatures,
+//! including the signatures verified by the ed25519, secp256k1 and secp256r1
+//! precompiles, plus the prioritization fee it requests with compute budget
+//! instructions.
+
+use {
+    crate::limits::ComputeBudgetLimits,
+    solana_fee_structure::{FeeDetails, FeeStructure},
+    solana_message::{SanitizedMessage, TransactionSignatureDetails},
+    solana_transaction_error::TransactionError,
+};
+
+/// Fee paid by a transaction with `message`.
+///
+/// Without a requested compute unit limit, the prioritization fee is computed
+/// from the compute units the runtime allots to each instruction; see
+/// [`DefaultComputeUnitLimit::ReserveMinimalForBuiltins`](crate::DefaultComputeUnitLimit::ReserveMinimalForBuiltins).
+///
+/// Fails if the compute budget instructions of the message would be rejected
+/// by the runtime; see [`ComputeBudgetLimits::try_from_sanitized_message`].
+pub fn calculate_fee_details(
+    message: &SanitizedMessage,
+    fee_structure: &FeeStructure,
+) -> Result<FeeDetails, TransactionError> {
+    let compute_budget_limits = ComputeBudgetLimits::try_from_sanitized_message(message)?;
+    Ok(calculate_fee_details_with_limits(
+        message,
+        &compute_budget_limits,
+        fee_structure,
+    ))
+}
+
+/// Fee paid by a transaction with `message`, whose compute budget
+/// instructions were already processed into `compute_budget_limits`.
+///
+/// Clusters which don't charge for signatures don't charge a prioritization
+/// fee either.
+pub fn calculate_fee_details_with_limits(
+    message: &SanitizedMessage,
+    compute_budget_limits: &ComputeBudgetLimits,
+    fee_structure: &FeeStructure,
+) -> FeeDetails {
+    if fee_structure.lamports_per_signature == 0 {
+        return FeeDetails::default();
+    }
+    FeeDetails::new(
+        calculate_signature_fee(
+            &message.get_signature_details(),
+            fee_structure.lamports_per_signature,
+        ),
+        compute_budget_limits.prioritization_fee(),
+    )
+}
+
+/// Signature fee for the transaction and precompile signatures counted in
+/// `signature_details`.
+pub fn calculate_signature_fee(
+    signature_details: &TransactionSignatureDetails,
+    lamports_per_signature: u64,
+) -> u64 {
+    signature_details
+        .total_signatures()
+        .saturating_mul(lamports_per_signature)
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {
+        super::*,
+        crate::ComputeBudgetInstruction,
+        bytemuck::bytes_of,
+        solana_address::Address,
+        solana_ed25519_program::{offsets_to_ed25519_instruction, Ed25519SignatureOffsets},
+        solana_instruction::{AccountMeta, Instruction},
+        solana_instruction_error::InstructionError,
+        solana_message::Message,
+        solana_secp256k1_program::{SecpSignatureOffsets, SIGNATURE_OFFSETS_SERIALIZED_SIZE},
+        solana_secp256r1_program::Secp256r1SignatureOffsets,
+        std::collections::HashSet,
+    };
+
+    fn sanitized_message(instructions: &[Instruction], payer: &Address) -> SanitizedMessage {
+        SanitizedMessage::try_from_legacy_message(
+            Message::new(instructions, Some(payer)),
+            &HashSet::new(),
+        )
+        .unwrap()
+    }
+
+    fn secp256k1_instruction(num_signatures: u8) -> Instruction {
+        let mut data = vec![num_signatures];
+        for _ in 0..num_signatures {
+            let offsets = bincode::serialize(&SecpSignatureOffsets::default()).unwrap();
+            assert_eq!(offsets.len(), SIGNATURE_OFFSETS_SERIALIZED_SIZE);
+            data.extend_from_slice(&offsets);
+        }
+        Instruction::new_with_bytes(solana_sdk_ids::secp256k1_program::id(), &data, vec![])
+    }
+
+    fn secp256r1_instruction(num_signatures: u8) -> Instruction {
+        let mut data = vec![num_signatures, 0];
+        for _ in 0..num_signatures {
+            data.extend_from_slice(bytes_of(&Secp256r1SignatureOffsets::default()));
+        }
+        Instruction::new_with_bytes(solana_secp256r1_program::id(), &data, vec![])
+    }
+
+    #[test]
+    fn test_calculate_fee_details() {
+        let fee_structure = FeeStructure::default();
+        let payer = Address::new_unique();
+        let signer = Address::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            Address::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(signer, true)],
+        );
+
+        let message = sanitized_message(std::slice::from_ref(&instruction), &payer);
+        assert_eq!(
+            calculate_fee_details(&message, &fee_structure),
+            Ok(FeeDetails::new(10_000, 0))
+        );
+
+        // 200k default compute units for the instruction and 3k for the
+        // compute unit price instruction at 3 micro-lamports each, rounded up
+        let message = sanitized_message(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_price(3),
+                instruction.clone(),
+            ],
+            &payer,
+        );
+        assert_eq!(
+            calculate_fee_details(&message, &fee_structure),
+            Ok(FeeDetails::new(10_000, 1))
+        );
+
+        let message = sanitized_message(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(10_000),
+                ComputeBudgetInstruction::set_compute_unit_price(2_500_000),
+                instruction,
+            ],
+            &payer,
+        );
+        let fee_details = calculate_fee_details(&message, &fee_structure).unwrap();
+        assert_eq!(fee_details, FeeDetails::new(10_000, 25_000));
+        assert_eq!(fee_details.total_fee(), 35_000);
+    }
+
+    #[test]
+    fn test_calculate_fee_details_builtin_instructions() {
+        let fee_structure = FeeStructure::default();
+        let payer = Address::new_unique();
+        let mut data = vec![2, 0, 0, 0];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        let transfer = Instruction::new_with_bytes(
+            solana_sdk_ids::system_program::id(),
+            &data,
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(Address::new_unique(), false),
+            ],
+        );
+
+        // the transfer and the compute unit price instruction are each
+        // allotted 3k compute units at 1 lamport each
+        let message = sanitized_message(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_price(1_000_000),
+                transfer.clone(),
+                transfer,
+            ],
+            &payer,
+        );
+        let fee_details = calculate_fee_details(&message, &fee_structure).unwrap();
+        assert_eq!(fee_details, FeeDetails::new(5_000, 9_000));
+        assert_eq!(fee_details.total_fee(), 14_000);
+    }
+
+    #[test]
+    fn test_calculate_fee_details_precompile_signatures() {
+        let fee_structure = FeeStructure {
+            lamports_per_signature: 7,
+            ..FeeStructure::default()
+        };
+        let payer = Address::new_unique();
+        let message = sanitized_message(
+            &[
+                offsets_to_ed25519_instruction(&[Ed25519SignatureOffsets::default(); 3]),
+                secp256k1_instruction(2),
+                secp256r1_instruction(4),
+                ComputeBudgetInstruction::set_compute_unit_limit(1_000_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+            ],
+            &payer,
+        );
+
+        let signature_details = message.get_signature_details();
+        assert_eq!(signature_details.num_transaction_signatures(), 1);
+        assert_eq!(signature_details.num_ed25519_instruction_signatures(), 3);
+        assert_eq!(signature_details.num_secp256k1_instruction_signatures(), 2);
+        assert_eq!(signature_details.num_secp256r1_instruction_signatures(), 4);
+        assert_eq!(
+            calculate_fee_details(&message, &fee_structure),
+            Ok(FeeDetails::new(10 * 7, 1))
+        );
+    }
+
+    #[test]
+    fn test_calculate_fee_details_zero_fees() {
+        let fee_structure = FeeStructure {
+            lamports_per_signature: 0,
+            ..FeeStructure::default()
+        };
+        let message = sanitized_message(
+            &[ComputeBudgetInstruction::set_compute_unit_price(1_000_000)],
+            &Address::new_unique(),
+        );
+        assert_eq!(
+            calculate_fee_details(&message, &fee_structure),
+            Ok(FeeDetails::default())
+        );
+    }
+
+    #[test]
+    fn test_calculate_fee_details_invalid_compute_budget() {
+        let message = sanitized_message(
+            &[
+                ComputeBudgetInstruction::request_heap_frame(1),
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+            ],
+            &Address::new_unique(),
+        );
+        assert_eq!(
+            calculate_fee_details(&message, &FeeStructure::default()),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+    }
+}
@@ -7,6 +7,58 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_instruction::Instruction;
 pub use solana_sdk_ids::compute_budget::{check_id, id, ID};
 
+#[cfg(feature = "message")]
+pub mod builder;
+#[cfg(all(feature = "message", not(target_os = "solana")))]
+pub mod fee;
+#[cfg(all(feature = "message", not(target_os = "solana")))]
+pub mod limits;
+#[cfg(feature = "message")]
+mod requested;
+
+/// Maximum compute units a transaction may request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Compute units allotted to each instruction which doesn't invoke a builtin
+/// program when a transaction doesn't request a compute unit limit.
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+/// Compute units allotted to each instruction which invokes a builtin
+/// program, including compute budget instructions, when a transaction doesn't
+/// request a compute unit limit.
+pub const MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT: u32 = 3_000;
+/// Heap frame size of a transaction which doesn't request one.
+pub const MIN_HEAP_FRAME_BYTES: u32 = 32 * 1024;
+/// Largest heap frame a transaction may request.
+pub const MAX_HEAP_FRAME_BYTES: u32 = 256 * 1024;
+/// Requested heap frame sizes must be a multiple of this many bytes.
+pub const HEAP_FRAME_BYTES_GRANULARITY: u32 = 1024;
+/// Loaded accounts data size limit of a transaction which doesn't request one.
+pub const MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES: u32 = 64 * 1024 * 1024;
+/// Compute unit prices are expressed in micro-lamports.
+pub const MICRO_LAMPORTS_PER_LAMPORT: u64 = 1_000_000;
+
+/// How the runtime allots compute units to a transaction which doesn't
+/// request a compute unit limit.
+#[cfg(feature = "message")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultComputeUnitLimit {
+    /// [`MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT`] for each instruction
+    /// which invokes a builtin program and
+    /// [`DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT`] for every other instruction,
+    /// as the runtime does once `reserve_minimal_cus_for_builtin_instructions`
+    /// is active.
+    ///
+    /// The builtin programs are the system, vote and compute budget programs,
+    /// the BPF loaders and the ed25519 and secp256k1 precompiles. The stake,
+    /// config and address lookup table programs have been migrated to BPF
+    /// programs and are allotted the non-builtin default.
+    #[default]
+    ReserveMinimalForBuiltins,
+    /// [`DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT`] for each instruction other
+    /// than compute budget instructions, as the runtime does before
+    /// `reserve_minimal_cus_for_builtin_instructions` is active.
+    PerNonComputeBudgetInstruction,
+}
+
 /// Compute Budget Instructions
 #[cfg_attr(
     feature = "frozen-abi",
@@ -77,6 +129,49 @@ impl ComputeBudgetInstruction {
     pub fn set_loaded_accounts_data_size_limit(bytes: u32) -> Instruction {
         to_instruction!(4, bytes, u32)
     }
+
+    /// Decode the data of a compute budget `Instruction`.
+    ///
+    /// Like the runtime, any bytes following the encoded instruction are
+    /// ignored.
+    pub fn from_instruction_data(data: &[u8]) -> Option<Self> {
+        let (discriminator, rest) = data.split_first()?;
+        match discriminator {
+            0 => Some(Self::Unused),
+            1 => Some(Self::RequestHeapFrame(u32::from_le_bytes(
+                *rest.first_chunk()?,
+            ))),
+            2 => Some(Self::SetComputeUnitLimit(u32::from_le_bytes(
+                *rest.first_chunk()?,
+            ))),
+            3 => Some(Self::SetComputeUnitPrice(u64::from_le_bytes(
+                *rest.first_chunk()?,
+            ))),
+            4 => Some(Self::SetLoadedAccountsDataSizeLimit(u32::from_le_bytes(
+                *rest.first_chunk()?,
+            ))),
+            _ => None,
+        }
+    }
+
+    /// Create the `Instruction` which encodes this compute budget instruction.
+    pub fn to_instruction(&self) -> Instruction {
+        match self {
+            Self::Unused => Instruction {
+                program_id: id(),
+                data: vec![0],
+                accounts: vec![],
+            },
+            Self::RequestHeapFrame(bytes) => Self::request_heap_frame(*bytes),
+            Self::SetComputeUnitLimit(units) => Self::set_compute_unit_limit(*units),
+            Self::SetComputeUnitPrice(micro_lamports) => {
+                Self::set_compute_unit_price(*micro_lamports)
+            }
+            Self::SetLoadedAccountsDataSizeLimit(bytes) => {
+                Self::set_loaded_accounts_data_size_limit(*bytes)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -90,4 +185,36 @@ mod tests {
         let ix = ComputeBudgetInstruction::set_compute_unit_price(u64::MAX);
         assert_eq!(ix.data, vec![3, 255, 255, 255, 255, 255, 255, 255, 255]);
     }
+
+    #[test]
+    fn test_from_instruction_data() {
+        for instruction in [
+            ComputeBudgetInstruction::Unused,
+            ComputeBudgetInstruction::RequestHeapFrame(64 * 1024),
+            ComputeBudgetInstruction::SetComputeUnitLimit(257),
+            ComputeBudgetInstruction::SetComputeUnitPrice(u64::MAX),
+            ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(1),
+        ] {
+            let mut data = instruction.to_instruction().data;
+            assert_eq!(
+                ComputeBudgetInstruction::from_instruction_data(&data),
+                Some(instruction.clone())
+            );
+            data.push(0);
+            assert_eq!(
+                ComputeBudgetInstruction::from_instruction_data(&data),
+                Some(instruction)
+            );
+        }
+
+        assert_eq!(ComputeBudgetInstruction::from_instruction_data(&[]), None);
+        assert_eq!(
+            ComputeBudgetInstruction::from_instruction_data(&[2, 1, 1, 0]),
+            None
+        );
+        assert_eq!(
+            ComputeBudgetInstruction::from_instruction_data(&[5, 0, 0, 0, 0]),
+            None
+        );
+    }
 }
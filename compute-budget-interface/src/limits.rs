@@ -0,0 +1,375 @@
+//! Reading the compute budget requested by a message.
+
+use {
+    crate::{
+        check_id,
+        requested::{
+            invalid_instruction_data_error, prioritization_fee, InstructionCounts,
+            RequestedComputeBudget,
+        },
+        ComputeBudgetInstruction, DefaultComputeUnitLimit, MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES,
+        MIN_HEAP_FRAME_BYTES,
+    },
+    solana_address::Address,
+    solana_message::{SanitizedMessage, VersionedMessage},
+    solana_transaction_error::TransactionError,
+};
+
+/// The compute budget a transaction executes with.
+///
+/// Values which aren't requested by a compute budget instruction take the
+/// defaults the runtime applies:
+///
+/// - the compute unit limit is allotted per instruction as described by
+///   [`DefaultComputeUnitLimit`], and requested limits are capped at
+///   [`MAX_COMPUTE_UNIT_LIMIT`](crate::MAX_COMPUTE_UNIT_LIMIT);
+/// - the compute unit price is zero;
+/// - the heap frame is [`MIN_HEAP_FRAME_BYTES`];
+/// - the loaded accounts data size limit is
+///   [`MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES`], which also caps requested
+///   limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeBudgetLimits {
+    pub compute_unit_limit: u32,
+    /// Price in micro-lamports per compute unit.
+    pub compute_unit_price: u64,
+    pub heap_frame_bytes: u32,
+    pub loaded_accounts_data_size_limit: u32,
+}
+
+impl ComputeBudgetLimits {
+    /// Process the instructions of a transaction, given as program id and
+    /// instruction data pairs.
+    ///
+    /// Fails with [`TransactionError::DuplicateInstruction`] if a kind of
+    /// compute budget instruction appears more than once, and with
+    /// [`TransactionError::InstructionError`] if a compute budget instruction
+    /// can't be decoded. As in the runtime, the requested values are checked
+    /// only once every instruction has been read, so an invalid heap frame is
+    /// reported after any such error.
+    pub fn try_from_instructions<'a>(
+        instructions: impl IntoIterator<Item = (&'a Address, &'a [u8])>,
+    ) -> Result<Self, TransactionError> {
+        Self::try_from_instructions_with_default_limit(
+            instructions,
+            DefaultComputeUnitLimit::default(),
+        )
+    }
+
+    /// Process the instructions of a transaction like
+    /// [`try_from_instructions`](Self::try_from_instructions), allotting
+    /// compute units as `default_compute_unit_limit` describes if no limit is
+    /// requested.
+    pub fn try_from_instructions_with_default_limit<'a>(
+        instructions: impl IntoIterator<Item = (&'a Address, &'a [u8])>,
+        default_compute_unit_limit: DefaultComputeUnitLimit,
+    ) -> Result<Self, TransactionError> {
+        let mut requested = RequestedComputeBudget::default();
+        let mut instruction_counts = InstructionCounts::default();
+        for (index, (program_id, data)) in instructions.into_iter().enumerate() {
+            if check_id(program_id) {
+                let instruction = ComputeBudgetInstruction::from_instruction_data(data)
+                    .ok_or_else(|| invalid_instruction_data_error(index as u8))?;
+                requested.try_insert(index, &instruction)?;
+            }
+            instruction_counts.add(program_id);
+        }
+        requested.sanitize()?;
+
+        Ok(Self {
+            compute_unit_limit: requested
+                .compute_unit_limit(&instruction_counts, default_compute_unit_limit),
+            compute_unit_price: requested.compute_unit_price.unwrap_or_default(),
+            heap_frame_bytes: requested.heap_frame_bytes.unwrap_or(MIN_HEAP_FRAME_BYTES),
+            loaded_accounts_data_size_limit: requested
+                .loaded_accounts_data_size_limit
+                .unwrap_or(MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES)
+                .min(MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES),
+        })
+    }
+
+    /// Process the instructions of a sanitized message.
+    pub fn try_from_sanitized_message(
+        message: &SanitizedMessage,
+    ) -> Result<Self, TransactionError> {
+        Self::try_from_instructions(
+            message
+                .program_instructions_iter()
+                .map(|(program_id, instruction)| (program_id, instruction.data.as_slice())),
+        )
+    }
+
+    /// Process the instructions of a versioned message.
+    ///
+    /// The message isn't sanitized, so an instruction whose program id index
+    /// is out of bounds fails with [`TransactionError::SanitizeFailure`].
+    pub fn try_from_versioned_message(
+        message: &VersionedMessage,
+    ) -> Result<Self, TransactionError> {
+        let account_keys = message.static_account_keys();
+        let instructions = message
+            .instructions()
+            .iter()
+            .map(|instruction| {
+                account_keys
+                    .get(usize::from(instruction.program_id_index))
+                    .map(|program_id| (program_id, instruction.data.as_slice()))
+                    .ok_or(TransactionError::SanitizeFailure)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::try_from_instructions(instructions)
+    }
+
+    /// Prioritization fee in lamports, rounded up to the nearest lamport.
+    pub fn prioritization_fee(&self) -> u64 {
+        prioritization_fee(self.compute_unit_price, self.compute_unit_limit)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT, MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT,
+            MAX_COMPUTE_UNIT_LIMIT,
+        },
+        solana_hash::Hash,
+        solana_instruction::{AccountMeta, Instruction},
+        solana_instruction_error::InstructionError,
+        solana_message::{v0, Message},
+        std::collections::HashSet,
+    };
+
+    fn program_instruction() -> Instruction {
+        Instruction::new_with_bytes(Address::new_unique(), &[0], vec![])
+    }
+
+    fn limits(instructions: &[Instruction]) -> Result<ComputeBudgetLimits, TransactionError> {
+        let payer = Address::new_unique();
+        let message = Message::new(instructions, Some(&payer));
+        let sanitized_message =
+            SanitizedMessage::try_from_legacy_message(message.clone(), &HashSet::new()).unwrap();
+        let result = ComputeBudgetLimits::try_from_sanitized_message(&sanitized_message);
+        assert_eq!(
+            ComputeBudgetLimits::try_from_versioned_message(&VersionedMessage::Legacy(message)),
+            result
+        );
+
+        let message = v0::Message::try_compile(&payer, instructions, &[], Hash::default()).unwrap();
+        assert_eq!(
+            ComputeBudgetLimits::try_from_versioned_message(&VersionedMessage::V0(message)),
+            result
+        );
+        result
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(
+            limits(&[]),
+            Ok(ComputeBudgetLimits {
+                compute_unit_limit: 0,
+                compute_unit_price: 0,
+                heap_frame_bytes: MIN_HEAP_FRAME_BYTES,
+                loaded_accounts_data_size_limit: MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES,
+            })
+        );
+        assert_eq!(
+            limits(&[
+                program_instruction(),
+                ComputeBudgetInstruction::set_compute_unit_price(7),
+                program_instruction(),
+            ]),
+            Ok(ComputeBudgetLimits {
+                compute_unit_limit: MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+                    + 2 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+                compute_unit_price: 7,
+                heap_frame_bytes: MIN_HEAP_FRAME_BYTES,
+                loaded_accounts_data_size_limit: MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES,
+            })
+        );
+        assert_eq!(
+            limits(&vec![program_instruction(); 8])
+                .unwrap()
+                .compute_unit_limit,
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+    }
+
+    fn transfer_instruction() -> Instruction {
+        let mut data = vec![2, 0, 0, 0];
+        data.extend_from_slice(&1u64.to_le_bytes());
+        Instruction::new_with_bytes(
+            solana_sdk_ids::system_program::id(),
+            &data,
+            vec![
+                AccountMeta::new(Address::new_unique(), true),
+                AccountMeta::new(Address::new_unique(), false),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_default_compute_unit_limit_transfer() {
+        assert_eq!(
+            limits(&[transfer_instruction()])
+                .unwrap()
+                .compute_unit_limit,
+            MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+        );
+
+        // compute budget instructions are builtin instructions too
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_price(1),
+            transfer_instruction(),
+            transfer_instruction(),
+        ];
+        assert_eq!(
+            limits(&instructions).unwrap().compute_unit_limit,
+            3 * MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+        );
+        assert_eq!(
+            ComputeBudgetLimits::try_from_instructions_with_default_limit(
+                instructions
+                    .iter()
+                    .map(|instruction| (&instruction.program_id, instruction.data.as_slice())),
+                DefaultComputeUnitLimit::PerNonComputeBudgetInstruction,
+            )
+            .unwrap()
+            .compute_unit_limit,
+            2 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_default_compute_unit_limit_mixed() {
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_price(1),
+            transfer_instruction(),
+            program_instruction(),
+            Instruction::new_with_bytes(solana_sdk_ids::stake::id(), &[0], vec![]),
+        ];
+        assert_eq!(
+            limits(&instructions).unwrap().compute_unit_limit,
+            2 * MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+                + 2 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+        );
+
+        // a requested limit replaces the defaults
+        let mut instructions = instructions.to_vec();
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(50_000));
+        assert_eq!(limits(&instructions).unwrap().compute_unit_limit, 50_000);
+    }
+
+    #[test]
+    fn test_requested() {
+        assert_eq!(
+            limits(&[
+                ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(4096),
+                ComputeBudgetInstruction::request_heap_frame(40 * 1024),
+                program_instruction(),
+                ComputeBudgetInstruction::set_compute_unit_limit(1_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_500),
+            ]),
+            Ok(ComputeBudgetLimits {
+                compute_unit_limit: 1_000,
+                compute_unit_price: 1_500,
+                heap_frame_bytes: 40 * 1024,
+                loaded_accounts_data_size_limit: 4096,
+            })
+        );
+
+        let capped = limits(&[
+            ComputeBudgetInstruction::set_compute_unit_limit(u32::MAX),
+            ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(u32::MAX),
+        ])
+        .unwrap();
+        assert_eq!(capped.compute_unit_limit, MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(
+            capped.loaded_accounts_data_size_limit,
+            MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            limits(&[
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+                program_instruction(),
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+            ]),
+            Err(TransactionError::DuplicateInstruction(2))
+        );
+        assert_eq!(
+            limits(&[
+                program_instruction(),
+                ComputeBudgetInstruction::request_heap_frame(MIN_HEAP_FRAME_BYTES - 1),
+            ]),
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+        assert_eq!(
+            limits(&[Instruction::new_with_bytes(crate::id(), &[2, 0], vec![])]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+        assert_eq!(
+            limits(&[ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(0)]),
+            Err(TransactionError::InvalidLoadedAccountsDataSizeLimit)
+        );
+
+        // requested values are only checked after every instruction is read
+        assert_eq!(
+            limits(&[
+                ComputeBudgetInstruction::request_heap_frame(1),
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+                ComputeBudgetInstruction::set_compute_unit_price(2),
+            ]),
+            Err(TransactionError::DuplicateInstruction(2))
+        );
+        assert_eq!(
+            limits(&[
+                ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(0),
+                ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(1),
+            ]),
+            Err(TransactionError::DuplicateInstruction(1))
+        );
+        assert_eq!(
+            limits(&[
+                ComputeBudgetInstruction::set_loaded_accounts_data_size_limit(0),
+                ComputeBudgetInstruction::request_heap_frame(1),
+            ]),
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+    }
+
+    #[test]
+    fn test_invalid_program_id_index() {
+        let mut message = Message::new(&[program_instruction()], None);
+        message.instructions[0].program_id_index = 5;
+        assert_eq!(
+            ComputeBudgetLimits::try_from_versioned_message(&VersionedMessage::Legacy(message)),
+            Err(TransactionError::SanitizeFailure)
+        );
+    }
+
+    #[test]
+    fn test_prioritization_fee() {
+        let limits = limits(&[
+            ComputeBudgetInstruction::set_compute_unit_limit(3),
+            ComputeBudgetInstruction::set_compute_unit_price(500_000),
+        ])
+        .unwrap();
+        assert_eq!(limits.prioritization_fee(), 2);
+    }
+}
@@ -0,0 +1,214 @@
+use {
+    crate::{
+        ComputeBudgetInstruction, DefaultComputeUnitLimit, DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+        HEAP_FRAME_BYTES_GRANULARITY, MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT,
+        MAX_COMPUTE_UNIT_LIMIT, MAX_HEAP_FRAME_BYTES, MICRO_LAMPORTS_PER_LAMPORT,
+        MIN_HEAP_FRAME_BYTES,
+    },
+    solana_address::Address,
+    solana_instruction_error::InstructionError,
+    solana_sdk_ids::{
+        bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable, compute_budget, ed25519_program,
+        loader_v4, secp256k1_program, system_program, vote,
+    },
+    solana_transaction_error::TransactionError,
+};
+
+/// Values requested by the compute budget instructions of a transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequestedComputeBudget {
+    pub(crate) compute_unit_limit: Option<u32>,
+    pub(crate) compute_unit_price: Option<u64>,
+    pub(crate) heap_frame_bytes: Option<u32>,
+    pub(crate) loaded_accounts_data_size_limit: Option<u32>,
+    /// Index of the instruction which requested the heap frame, if any.
+    pub(crate) heap_frame_index: Option<u8>,
+}
+
+impl RequestedComputeBudget {
+    /// Returns true if `instruction` requests exactly what was already
+    /// requested by an earlier instruction.
+    pub(crate) fn contains(&self, instruction: &ComputeBudgetInstruction) -> bool {
+        match instruction {
+            ComputeBudgetInstruction::Unused => false,
+            ComputeBudgetInstruction::RequestHeapFrame(bytes) => {
+                self.heap_frame_bytes == Some(*bytes)
+            }
+            ComputeBudgetInstruction::SetComputeUnitLimit(units) => {
+                self.compute_unit_limit == Some(*units)
+            }
+            ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => {
+                self.compute_unit_price == Some(*micro_lamports)
+            }
+            ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => {
+                self.loaded_accounts_data_size_limit == Some(*bytes)
+            }
+        }
+    }
+
+    /// Record the compute budget instruction at `index` in a transaction,
+    /// rejecting it if it repeats an earlier request the same way the runtime
+    /// does.
+    ///
+    /// The requested values are only checked by [`sanitize`](Self::sanitize)
+    /// once every instruction is recorded, so that errors are reported in the
+    /// same order as by the runtime.
+    pub(crate) fn try_insert(
+        &mut self,
+        index: usize,
+        instruction: &ComputeBudgetInstruction,
+    ) -> Result<(), TransactionError> {
+        let index = index as u8;
+        let duplicate_instruction_error = TransactionError::DuplicateInstruction(index);
+        match instruction {
+            ComputeBudgetInstruction::Unused => Err(invalid_instruction_data_error(index)),
+            ComputeBudgetInstruction::RequestHeapFrame(bytes) => {
+                if self.heap_frame_bytes.is_some() {
+                    return Err(duplicate_instruction_error);
+                }
+                self.heap_frame_bytes = Some(*bytes);
+                self.heap_frame_index = Some(index);
+                Ok(())
+            }
+            ComputeBudgetInstruction::SetComputeUnitLimit(units) => {
+                if self.compute_unit_limit.is_some() {
+                    return Err(duplicate_instruction_error);
+                }
+                self.compute_unit_limit = Some(*units);
+                Ok(())
+            }
+            ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => {
+                if self.compute_unit_price.is_some() {
+                    return Err(duplicate_instruction_error);
+                }
+                self.compute_unit_price = Some(*micro_lamports);
+                Ok(())
+            }
+            ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => {
+                if self.loaded_accounts_data_size_limit.is_some() {
+                    return Err(duplicate_instruction_error);
+                }
+                self.loaded_accounts_data_size_limit = Some(*bytes);
+                Ok(())
+            }
+        }
+    }
+
+    /// Check the requested values once every compute budget instruction of a
+    /// transaction is recorded.
+    pub(crate) fn sanitize(&self) -> Result<(), TransactionError> {
+        if let Some(bytes) = self.heap_frame_bytes {
+            if !is_valid_heap_frame_bytes(bytes) {
+                return Err(invalid_instruction_data_error(
+                    self.heap_frame_index.unwrap_or_default(),
+                ));
+            }
+        }
+        if self.loaded_accounts_data_size_limit == Some(0) {
+            return Err(TransactionError::InvalidLoadedAccountsDataSizeLimit);
+        }
+        Ok(())
+    }
+
+    /// Compute unit limit of a transaction with these requests and the
+    /// instructions counted in `instruction_counts`.
+    pub(crate) fn compute_unit_limit(
+        &self,
+        instruction_counts: &InstructionCounts,
+        default_compute_unit_limit: DefaultComputeUnitLimit,
+    ) -> u32 {
+        self.compute_unit_limit
+            .unwrap_or_else(|| {
+                instruction_counts.default_compute_unit_limit(default_compute_unit_limit)
+            })
+            .min(MAX_COMPUTE_UNIT_LIMIT)
+    }
+}
+
+/// Number of instructions of a transaction invoking each kind of program,
+/// which determines the compute unit limit of a transaction that doesn't
+/// request one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InstructionCounts {
+    num_compute_budget_instructions: u32,
+    num_other_builtin_instructions: u32,
+    num_non_builtin_instructions: u32,
+}
+
+impl InstructionCounts {
+    /// Count an instruction invoking `program_id`.
+    pub(crate) fn add(&mut self, program_id: &Address) {
+        let count = if compute_budget::check_id(program_id) {
+            &mut self.num_compute_budget_instructions
+        } else if is_builtin_program(program_id) {
+            &mut self.num_other_builtin_instructions
+        } else {
+            &mut self.num_non_builtin_instructions
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Count `num_instructions` compute budget instructions.
+    pub(crate) fn add_compute_budget_instructions(&mut self, num_instructions: usize) {
+        self.num_compute_budget_instructions = self
+            .num_compute_budget_instructions
+            .saturating_add(u32::try_from(num_instructions).unwrap_or(u32::MAX));
+    }
+
+    fn default_compute_unit_limit(
+        &self,
+        default_compute_unit_limit: DefaultComputeUnitLimit,
+    ) -> u32 {
+        match default_compute_unit_limit {
+            DefaultComputeUnitLimit::ReserveMinimalForBuiltins => self
+                .num_compute_budget_instructions
+                .saturating_add(self.num_other_builtin_instructions)
+                .saturating_mul(MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT)
+                .saturating_add(
+                    self.num_non_builtin_instructions
+                        .saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT),
+                ),
+            DefaultComputeUnitLimit::PerNonComputeBudgetInstruction => self
+                .num_other_builtin_instructions
+                .saturating_add(self.num_non_builtin_instructions)
+                .saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT),
+        }
+    }
+}
+
+/// Returns true for the programs whose instructions are allotted
+/// [`MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT`] compute units by default.
+fn is_builtin_program(program_id: &Address) -> bool {
+    system_program::check_id(program_id)
+        || vote::check_id(program_id)
+        || compute_budget::check_id(program_id)
+        || bpf_loader::check_id(program_id)
+        || bpf_loader_deprecated::check_id(program_id)
+        || bpf_loader_upgradeable::check_id(program_id)
+        || loader_v4::check_id(program_id)
+        || ed25519_program::check_id(program_id)
+        || secp256k1_program::check_id(program_id)
+}
+
+pub(crate) fn invalid_instruction_data_error(index: u8) -> TransactionError {
+    TransactionError::InstructionError(index, InstructionError::InvalidInstructionData)
+}
+
+fn is_valid_heap_frame_bytes(bytes: u32) -> bool {
+    (MIN_HEAP_FRAME_BYTES..=MAX_HEAP_FRAME_BYTES).contains(&bytes)
+        && bytes % HEAP_FRAME_BYTES_GRANULARITY == 0
+}
+
+/// Prioritization fee in lamports of a transaction paying
+/// `compute_unit_price` micro-lamports for each of its `compute_unit_limit`
+/// compute units, rounded up to the nearest lamport.
+pub(crate) fn prioritization_fee(compute_unit_price: u64, compute_unit_limit: u32) -> u64 {
+    let micro_lamports =
+        u128::from(compute_unit_price).saturating_mul(u128::from(compute_unit_limit));
+    let micro_lamports_per_lamport = u128::from(MICRO_LAMPORTS_PER_LAMPORT);
+    micro_lamports
+        .saturating_add(micro_lamports_per_lamport.saturating_sub(1))
+        .checked_div(micro_lamports_per_lamport)
+        .and_then(|fee| u64::try_from(fee).ok())
+        .unwrap_or(u64::MAX)
+}
@@ -0,0 +1,473 @@
+//! Offline estimation of epoch rewards.
+//!
+//! [`RewardsEstimator`] follows the calculation of inflation rewards done at
+//! the start of an epoch for the previous epoch: the validator rewards of the
+//! epoch are derived from the [`Inflation`] schedule and the capitalization,
+//! divided among stake accounts by points, and split between each stake
+//! account and its vote account by the commission of the vote account. Stake
+//! rewards are then distributed over several blocks, and the estimate
+//! includes the partition each stake account falls into.
+//!
+//! The points of a stake account are its effective stake times the credits
+//! its vote account earned in the epoch. The total points of the cluster are
+//! an input of the estimate, as they depend on every stake account.
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use {
+    solana_address::Address,
+    solana_clock::{Epoch, Slot},
+    solana_epoch_rewards_hasher::EpochRewardsHasher,
+    solana_epoch_schedule::EpochSchedule,
+    solana_hash::Hash,
+    solana_inflation::Inflation,
+    solana_vote_interface::state::VoteStateV4,
+    std::collections::HashMap,
+};
+
+/// Number of stake accounts rewarded per block of the distribution.
+pub const STAKE_ACCOUNT_STORES_PER_BLOCK: u64 = 4096;
+
+/// The distribution spans at most this fraction of the slots of an epoch.
+const MAX_FACTOR_OF_REWARD_BLOCKS_IN_EPOCH: u64 = 10;
+
+/// Basis points of a 100% commission.
+const MAX_COMMISSION_BPS: u16 = 10_000;
+
+/// A stake account delegated to a vote account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakeInput {
+    pub stake_pubkey: Address,
+    pub vote_pubkey: Address,
+    /// Effective stake during the epoch, in lamports.
+    pub stake: u64,
+}
+
+/// The state of the cluster an estimate is computed from.
+#[derive(Debug, Clone)]
+pub struct EpochRewardsInputs<'a> {
+    /// The epoch the rewards are earned in.
+    pub epoch: Epoch,
+    /// Lamports in circulation at the end of the epoch.
+    pub capitalization: u64,
+    /// Points of all the stake accounts of the cluster.
+    pub total_points: u128,
+    /// Number of stake accounts of the cluster earning rewards, which
+    /// determines the number of partitions.
+    pub num_stake_rewards: u64,
+    /// Blockhash of the last block of the epoch, which seeds the partitions.
+    pub parent_blockhash: Hash,
+    /// The vote accounts the stake accounts are delegated to.
+    pub vote_accounts: &'a HashMap<Address, VoteStateV4>,
+    /// The stake accounts to estimate the rewards of.
+    pub stakes: &'a [StakeInput],
+}
+
+/// Estimated rewards of a stake account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakeRewardEstimate {
+    pub stake_pubkey: Address,
+    pub vote_pubkey: Address,
+    pub points: u128,
+    /// Lamports paid to the stake account.
+    pub stake_rewards: u64,
+    /// Lamports of commission paid to the vote account.
+    pub commission: u64,
+    /// Partition of the distribution the stake account is rewarded in.
+    pub partition: usize,
+}
+
+/// Estimated commission of a vote account, from all its stake accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteRewardEstimate {
+    pub vote_pubkey: Address,
+    pub commission_bps: u16,
+    pub lamports: u64,
+}
+
+/// Estimated rewards of an epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochRewardsEstimate {
+    pub epoch: Epoch,
+    /// Rewards of all the stake and vote accounts of the cluster.
+    pub validator_rewards: u64,
+    /// Number of partitions the stake rewards are distributed in.
+    pub num_partitions: usize,
+    /// Commission of the vote accounts, in order of first delegation in
+    /// [`EpochRewardsInputs::stakes`].
+    pub vote_rewards: Vec<VoteRewardEstimate>,
+    /// Rewards of the stake accounts, in the order of
+    /// [`EpochRewardsInputs::stakes`].
+    pub stake_rewards: Vec<StakeRewardEstimate>,
+}
+
+/// Estimates epoch rewards for an inflation schedule and epoch schedule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardsEstimator {
+    inflation: Inflation,
+    epoch_schedule: EpochSchedule,
+    slots_per_year: f64,
+    inflation_activation_slot: Slot,
+}
+
+impl RewardsEstimator {
+    /// Creates an estimator for a cluster with `slots_per_year` slots per
+    /// year, whose inflation is active since genesis.
+    pub fn new(inflation: Inflation, epoch_schedule: EpochSchedule, slots_per_year: f64) -> Self {
+        Self {
+            inflation,
+            epoch_schedule,
+            slots_per_year,
+            inflation_activation_slot: 0,
+        }
+    }
+
+    /// Sets the slot at which inflation was activated. The inflation
+    /// schedule starts at the beginning of the epoch before it.
+    pub fn with_inflation_activation_slot(mut self, slot: Slot) -> Self {
+        self.inflation_activation_slot = slot;
+        self
+    }
+
+    /// Years of inflation elapsed at the start of `epoch`.
+    pub fn slot_in_year(&self, epoch: Epoch) -> f64 {
+        let inflation_start_epoch = self
+            .epoch_schedule
+            .get_epoch(self.inflation_activation_slot)
+            .saturating_sub(1);
+        let num_slots = self
+            .epoch_schedule
+            .get_first_slot_in_epoch(epoch)
+            .saturating_sub(
+                self.epoch_schedule
+                    .get_first_slot_in_epoch(inflation_start_epoch),
+            );
+        num_slots as f64 / self.slots_per_year
+    }
+
+    /// Length of `epoch` in years.
+    pub fn epoch_duration_in_years(&self, epoch: Epoch) -> f64 {
+        self.epoch_schedule.get_slots_in_epoch(epoch) as f64 / self.slots_per_year
+    }
+
+    /// Rewards of all the stake and vote accounts for `epoch`, given the
+    /// `capitalization` at the end of the epoch.
+    ///
+    /// The inflation rate is the one at the start of the following epoch,
+    /// when the rewards are calculated.
+    pub fn validator_rewards(&self, epoch: Epoch, capitalization: u64) -> u64 {
+        let validator_rate = self
+            .inflation
+            .validator(self.slot_in_year(epoch.saturating_add(1)));
+        (validator_rate * capitalization as f64 * self.epoch_duration_in_years(epoch)) as u64
+    }
+
+    /// Number of partitions the rewards of `num_stake_rewards` stake accounts
+    /// for `epoch` are distributed in.
+    pub fn num_partitions(&self, epoch: Epoch, num_stake_rewards: u64) -> usize {
+        let distribution_epoch = epoch.saturating_add(1);
+        if self.epoch_schedule.warmup && distribution_epoch < self.epoch_schedule.first_normal_epoch
+        {
+            return 1;
+        }
+        let max_partitions =
+            (self.epoch_schedule.slots_per_epoch / MAX_FACTOR_OF_REWARD_BLOCKS_IN_EPOCH).max(1);
+        let num_partitions = num_stake_rewards
+            .div_ceil(STAKE_ACCOUNT_STORES_PER_BLOCK)
+            .clamp(1, max_partitions);
+        usize::try_from(num_partitions).unwrap_or(usize::MAX)
+    }
+
+    /// Estimates the rewards of the stake accounts of `inputs`, and the
+    /// commission of their vote accounts.
+    ///
+    /// Stake accounts whose vote account is missing from `inputs` earn no
+    /// rewards. As in the runtime, a stake account earns no rewards if either
+    /// side of a commission split would be paid nothing.
+    pub fn estimate(&self, inputs: &EpochRewardsInputs) -> EpochRewardsEstimate {
+        let validator_rewards = self.validator_rewards(inputs.epoch, inputs.capitalization);
+        let num_partitions = self.num_partitions(inputs.epoch, inputs.num_stake_rewards);
+        let hasher = EpochRewardsHasher::new(num_partitions, &inputs.parent_blockhash);
+
+        let mut vote_rewards: Vec<VoteRewardEstimate> = vec![];
+        let mut vote_reward_indexes = HashMap::new();
+        let stake_rewards = inputs
+            .stakes
+            .iter()
+            .map(|stake| {
+                let vote_state = inputs.vote_accounts.get(&stake.vote_pubkey);
+                let points = vote_state.map_or(0, |vote_state| {
+                    stake_points(stake.stake, vote_state, inputs.epoch)
+                });
+                let commission_bps =
+                    vote_state.map_or(0, |vote_state| vote_state.inflation_rewards_commission_bps);
+                let (commission, stake_rewards) =
+                    stake_rewards(points, validator_rewards, inputs.total_points)
+                        .and_then(|rewards| commission_split(commission_bps, rewards))
+                        .unwrap_or_default();
+
+                if vote_state.is_some() {
+                    let index = *vote_reward_indexes
+                        .entry(&stake.vote_pubkey)
+                        .or_insert_with(|| {
+                            vote_rewards.push(VoteRewardEstimate {
+                                vote_pubkey: stake.vote_pubkey,
+                                commission_bps,
+                                lamports: 0,
+                            });
+                            vote_rewards.len().saturating_sub(1)
+                        });
+                    let vote_reward = &mut vote_rewards[index];
+                    vote_reward.lamports = vote_reward.lamports.saturating_add(commission);
+                }
+
+                StakeRewardEstimate {
+                    stake_pubkey: stake.stake_pubkey,
+                    vote_pubkey: stake.vote_pubkey,
+                    points,
+                    stake_rewards,
+                    commission,
+                    partition: hasher
+                        .clone()
+                        .hash_address_to_partition(&stake.stake_pubkey),
+                }
+            })
+            .collect();
+
+        EpochRewardsEstimate {
+            epoch: inputs.epoch,
+            validator_rewards,
+            num_partitions,
+            vote_rewards,
+            stake_rewards,
+        }
+    }
+}
+
+/// Credits earned by the vote account in `epoch`.
+pub fn credits_in_epoch(vote_state: &VoteStateV4, epoch: Epoch) -> u64 {
+    vote_state
+        .epoch_credits
+        .iter()
+        .rev()
+        .find(|(credits_epoch, _, _)| *credits_epoch == epoch)
+        .map_or(0, |(_, credits, prev_credits)| {
+            credits.saturating_sub(*prev_credits)
+        })
+}
+
+/// Points earned in `epoch` by `stake` lamports delegated to the vote
+/// account.
+pub fn stake_points(stake: u64, vote_state: &VoteStateV4, epoch: Epoch) -> u128 {
+    u128::from(stake).saturating_mul(u128::from(credits_in_epoch(vote_state, epoch)))
+}
+
+/// Share of `validator_rewards` earned by `points`, if any.
+fn stake_rewards(points: u128, validator_rewards: u64, total_points: u128) -> Option<u64> {
+    points
+        .checked_mul(u128::from(validator_rewards))?
+        .checked_div(total_points)
+        .and_then(|rewards| u64::try_from(rewards).ok())
+        .filter(|rewards| *rewards > 0)
+}
+
+/// Splits `rewards` into the commission of the vote account and the rewards
+/// of the stake account, or `None` if one side of a split would get nothing.
+pub fn commission_split(commission_bps: u16, rewards: u64) -> Option<(u64, u64)> {
+    match commission_bps.min(MAX_COMMISSION_BPS) {
+        0 => Some((0, rewards)),
+        MAX_COMMISSION_BPS => Some((rewards, 0)),
+        split => {
+            let rewards = u128::from(rewards);
+            let max = u128::from(MAX_COMMISSION_BPS);
+            let commission = rewards.saturating_mul(u128::from(split)).checked_div(max)?;
+            let stake_rewards = rewards
+                .saturating_mul(max.saturating_sub(u128::from(split)))
+                .checked_div(max)?;
+            let commission = u64::try_from(commission).ok()?;
+            let stake_rewards = u64::try_from(stake_rewards).ok()?;
+            (commission > 0 && stake_rewards > 0).then_some((commission, stake_rewards))
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {super::*, solana_clock::DEFAULT_SLOTS_PER_EPOCH};
+
+    const SLOTS_PER_YEAR: f64 = (DEFAULT_SLOTS_PER_EPOCH * 10) as f64;
+
+    fn estimator() -> RewardsEstimator {
+        RewardsEstimator::new(
+            Inflation::new_fixed(0.1),
+            EpochSchedule::without_warmup(),
+            SLOTS_PER_YEAR,
+        )
+    }
+
+    fn vote_state(commission_bps: u16, epoch_credits: Vec<(Epoch, u64, u64)>) -> VoteStateV4 {
+        VoteStateV4 {
+            inflation_rewards_commission_bps: commission_bps,
+            epoch_credits,
+            ..VoteStateV4::default()
+        }
+    }
+
+    #[test]
+    fn test_validator_rewards() {
+        let estimator = estimator();
+        assert_eq!(estimator.epoch_duration_in_years(3), 0.1);
+        assert_eq!(estimator.slot_in_year(3), 0.3);
+        // 10% a year over a tenth of a year
+        assert_eq!(estimator.validator_rewards(3, 1_000_000_000), 10_000_000);
+
+        // the inflation schedule starts an epoch before activation
+        let estimator = estimator.with_inflation_activation_slot(DEFAULT_SLOTS_PER_EPOCH * 2);
+        assert_eq!(estimator.slot_in_year(3), 0.2);
+        assert_eq!(estimator.slot_in_year(0), 0.0);
+
+        // tapering inflation
+        let estimator = RewardsEstimator::new(
+            Inflation::default(),
+            EpochSchedule::without_warmup(),
+            SLOTS_PER_YEAR,
+        );
+        assert!(
+            estimator.validator_rewards(0, 1_000_000_000)
+                > estimator.validator_rewards(20, 1_000_000_000)
+        );
+    }
+
+    #[test]
+    fn test_num_partitions() {
+        let estimator = estimator();
+        assert_eq!(estimator.num_partitions(0, 0), 1);
+        assert_eq!(estimator.num_partitions(0, 4096), 1);
+        assert_eq!(estimator.num_partitions(0, 4097), 2);
+        assert_eq!(
+            estimator.num_partitions(0, u64::MAX),
+            (DEFAULT_SLOTS_PER_EPOCH / 10) as usize
+        );
+
+        let estimator = RewardsEstimator::new(
+            Inflation::default(),
+            EpochSchedule::default(),
+            SLOTS_PER_YEAR,
+        );
+        let first_normal_epoch = EpochSchedule::default().first_normal_epoch;
+        assert_eq!(estimator.num_partitions(first_normal_epoch - 2, 100_000), 1);
+        assert_eq!(
+            estimator.num_partitions(first_normal_epoch - 1, 100_000),
+            25
+        );
+    }
+
+    #[test]
+    fn test_commission_split() {
+        assert_eq!(commission_split(0, 100), Some((0, 100)));
+        assert_eq!(commission_split(10_000, 100), Some((100, 0)));
+        assert_eq!(commission_split(u16::MAX, 100), Some((100, 0)));
+        assert_eq!(commission_split(500, 100), Some((5, 95)));
+        // lamports lost to rounding
+        assert_eq!(commission_split(550, 100), Some((5, 94)));
+        assert_eq!(commission_split(50, 100), None);
+        assert_eq!(commission_split(9_950, 100), None);
+        assert_eq!(
+            commission_split(5_000, u64::MAX),
+            Some((u64::MAX / 2, u64::MAX / 2))
+        );
+    }
+
+    #[test]
+    fn test_credits_in_epoch() {
+        let vote_state = vote_state(0, vec![(1, 100, 0), (3, 150, 100), (4, 400, 150)]);
+        assert_eq!(credits_in_epoch(&vote_state, 1), 100);
+        assert_eq!(credits_in_epoch(&vote_state, 2), 0);
+        assert_eq!(credits_in_epoch(&vote_state, 3), 50);
+        assert_eq!(credits_in_epoch(&vote_state, 4), 250);
+        assert_eq!(stake_points(1_000, &vote_state, 4), 250_000);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let estimator = estimator();
+        let vote_a = Address::new_unique();
+        let vote_b = Address::new_unique();
+        let vote_accounts = HashMap::from([
+            (vote_a, vote_state(1_000, vec![(3, 400, 0)])),
+            (vote_b, vote_state(0, vec![(2, 400, 0), (3, 600, 400)])),
+        ]);
+        let stake = |vote_pubkey: &Address, stake| StakeInput {
+            stake_pubkey: Address::new_unique(),
+            vote_pubkey: *vote_pubkey,
+            stake,
+        };
+        let stakes = vec![
+            stake(&vote_a, 3_000),
+            stake(&vote_b, 2_000),
+            stake(&vote_a, 1_000),
+            stake(&Address::new_unique(), 5_000),
+        ];
+        let inputs = EpochRewardsInputs {
+            epoch: 3,
+            capitalization: 1_000_000_000,
+            // twice the points of the stakes above
+            total_points: 4_000_000,
+            num_stake_rewards: 10_000,
+            parent_blockhash: Hash::new_from_array([7; 32]),
+            vote_accounts: &vote_accounts,
+            stakes: &stakes,
+        };
+        let estimate = estimator.estimate(&inputs);
+
+        assert_eq!(estimate.epoch, 3);
+        assert_eq!(estimate.validator_rewards, 10_000_000);
+        assert_eq!(estimate.num_partitions, 3);
+        assert_eq!(
+            estimate
+                .stake_rewards
+                .iter()
+                .map(|reward| (reward.points, reward.commission, reward.stake_rewards))
+                .collect::<Vec<_>>(),
+            vec![
+                (1_200_000, 300_000, 2_700_000),
+                (400_000, 0, 1_000_000),
+                (400_000, 100_000, 900_000),
+                (0, 0, 0),
+            ]
+        );
+        assert_eq!(
+            estimate.vote_rewards,
+            vec![
+                VoteRewardEstimate {
+                    vote_pubkey: vote_a,
+                    commission_bps: 1_000,
+                    lamports: 400_000,
+                },
+                VoteRewardEstimate {
+                    vote_pubkey: vote_b,
+                    commission_bps: 0,
+                    lamports: 0,
+                },
+            ]
+        );
+        for (reward, stake) in estimate.stake_rewards.iter().zip(&stakes) {
+            assert_eq!(reward.stake_pubkey, stake.stake_pubkey);
+            assert_eq!(
+                reward.partition,
+                EpochRewardsHasher::new(3, &inputs.parent_blockhash)
+                    .hash_address_to_partition(&stake.stake_pubkey)
+            );
+        }
+
+        // no points, no rewards
+        let estimate = estimator.estimate(&EpochRewardsInputs {
+            total_points: 0,
+            ..inputs
+        });
+        assert!(estimate
+            .stake_rewards
+            .iter()
+            .all(|reward| reward.stake_rewards == 0 && reward.commission == 0));
+    }
+}
@@ -0,0 +1,273 @@
+//! Discovery of the accounts derived from a seed.
+//!
+//! [`AccountDiscovery`] derives the BIP-44 keypairs `m/44'/501'/account'` and
+//! `m/44'/501'/account'/change'` of a seed for increasing account indexes,
+//! and stops after [a number](AccountDiscovery::with_gap_limit) of
+//! consecutive unused accounts, as wallets do when they recover accounts from
+//! a mnemonic. Whether an account is used is answered by a
+//! [predicate](AccountDiscovery::with_predicate) supplied by the caller,
+//! typically by checking for on-chain state.
+
+use {
+    crate::{seed_derivable::keypair_from_seed_and_derivation_path, Keypair},
+    solana_address::Address,
+    solana_derivation_path::DerivationPath,
+    solana_signer::Signer,
+    std::{error, fmt},
+};
+
+/// Number of consecutive unused accounts after which discovery stops, as
+/// recommended by BIP-44.
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Largest account index, since account indexes are hardened.
+const MAX_ACCOUNT: u32 = (1 << 31) - 1;
+
+type Predicate<'a> = Box<dyn FnMut(&Address) -> bool + 'a>;
+
+/// A keypair derived by [`AccountDiscovery`].
+#[derive(Debug)]
+pub struct DiscoveredKeypair {
+    pub account: u32,
+    pub change: Option<u32>,
+    pub derivation_path: DerivationPath,
+    pub keypair: Keypair,
+    /// Whether the predicate reported the keypair as used.
+    pub used: bool,
+}
+
+/// An iterator over the keypairs derived from a seed, for each account index
+/// and each change index.
+///
+/// An account is used if the predicate returns `true` for any of its
+/// keypairs. Iteration stops after the keypairs of `gap_limit` consecutive
+/// unused accounts. Without a predicate no account is used, so the keypairs
+/// of the first `gap_limit` accounts are returned.
+pub struct AccountDiscovery<'a> {
+    seed: &'a [u8],
+    change_indexes: Vec<Option<u32>>,
+    gap_limit: u32,
+    predicate: Option<Predicate<'a>>,
+    account: u32,
+    next_change_index: usize,
+    account_used: bool,
+    unused_accounts: u32,
+    done: bool,
+}
+
+impl<'a> AccountDiscovery<'a> {
+    /// Discover the accounts of `seed`, with change indexes `None` and
+    /// `Some(0)`, i.e. both `m/44'/501'/account'` and
+    /// `m/44'/501'/account'/0'`, and a gap limit of [`DEFAULT_GAP_LIMIT`].
+    pub fn new(seed: &'a [u8]) -> Self {
+        Self {
+            seed,
+            change_indexes: vec![None, Some(0)],
+            gap_limit: DEFAULT_GAP_LIMIT,
+            predicate: None,
+            account: 0,
+            next_change_index: 0,
+            account_used: false,
+            unused_accounts: 0,
+            done: false,
+        }
+    }
+
+    /// Derive a keypair for each of `change_indexes` in every account. A
+    /// change index of `None` derives `m/44'/501'/account'`.
+    pub fn with_change_indexes(
+        mut self,
+        change_indexes: impl IntoIterator<Item = Option<u32>>,
+    ) -> Self {
+        self.change_indexes = change_indexes.into_iter().collect();
+        self
+    }
+
+    /// Stop after `gap_limit` consecutive unused accounts.
+    pub fn with_gap_limit(mut self, gap_limit: u32) -> Self {
+        self.gap_limit = gap_limit;
+        self
+    }
+
+    /// Report the keypairs with a public key for which `predicate` returns
+    /// `true` as used.
+    pub fn with_predicate(mut self, predicate: impl FnMut(&Address) -> bool + 'a) -> Self {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// Start discovery at account index `account` instead of 0.
+    pub fn with_start_account(mut self, account: u32) -> Self {
+        self.account = account;
+        self
+    }
+
+    fn finish_account(&mut self) {
+        if self.account_used {
+            self.unused_accounts = 0;
+        } else {
+            self.unused_accounts = self.unused_accounts.saturating_add(1);
+        }
+        self.account_used = false;
+        self.next_change_index = 0;
+        if self.account == MAX_ACCOUNT {
+            self.done = true;
+        } else {
+            self.account = self.account.saturating_add(1);
+        }
+    }
+}
+
+impl Iterator for AccountDiscovery<'_> {
+    type Item = Result<DiscoveredKeypair, Box<dyn error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done || self.unused_accounts >= self.gap_limit || self.account > MAX_ACCOUNT {
+                return None;
+            }
+            let Some(&change) = self.change_indexes.get(self.next_change_index) else {
+                self.finish_account();
+                continue;
+            };
+            self.next_change_index = self.next_change_index.saturating_add(1);
+
+            let account = self.account;
+            let derivation_path = DerivationPath::new_bip44(Some(account), change);
+            let keypair = match keypair_from_seed_and_derivation_path(
+                self.seed,
+                Some(derivation_path.clone()),
+            ) {
+                Ok(keypair) => keypair,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            let used = self
+                .predicate
+                .as_mut()
+                .is_some_and(|predicate| predicate(&keypair.pubkey()));
+            self.account_used |= used;
+            return Some(Ok(DiscoveredKeypair {
+                account,
+                change,
+                derivation_path,
+                keypair,
+                used,
+            }));
+        }
+    }
+}
+
+impl fmt::Debug for AccountDiscovery<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountDiscovery")
+            .field("change_indexes", &self.change_indexes)
+            .field("gap_limit", &self.gap_limit)
+            .field("account", &self.account)
+            .field("unused_accounts", &self.unused_accounts)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {super::*, std::collections::HashSet};
+
+    const SEED: [u8; 64] = [7; 64];
+
+    fn keypair(account: u32, change: Option<u32>) -> Keypair {
+        keypair_from_seed_and_derivation_path(
+            &SEED,
+            Some(DerivationPath::new_bip44(Some(account), change)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_discovery_without_predicate() {
+        let discovered = AccountDiscovery::new(&SEED)
+            .with_gap_limit(3)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            discovered
+                .iter()
+                .map(|discovered| (discovered.account, discovered.change, discovered.used))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, None, false),
+                (0, Some(0), false),
+                (1, None, false),
+                (1, Some(0), false),
+                (2, None, false),
+                (2, Some(0), false),
+            ]
+        );
+        for discovered in discovered {
+            assert_eq!(
+                discovered.keypair,
+                keypair(discovered.account, discovered.change)
+            );
+            assert_eq!(
+                discovered.derivation_path,
+                DerivationPath::new_bip44(Some(discovered.account), discovered.change)
+            );
+        }
+
+        assert_eq!(AccountDiscovery::new(&SEED).count(), 40);
+        assert_eq!(AccountDiscovery::new(&SEED).with_gap_limit(0).count(), 0);
+    }
+
+    #[test]
+    fn test_discovery_with_predicate() {
+        // accounts 1 and 4 are used, through different change indexes
+        let used = HashSet::from([keypair(1, None).pubkey(), keypair(4, Some(1)).pubkey()]);
+        let mut queried = 0;
+        let discovered = AccountDiscovery::new(&SEED)
+            .with_change_indexes([None, Some(0), Some(1)])
+            .with_gap_limit(3)
+            .with_predicate(|pubkey| {
+                queried += 1;
+                used.contains(pubkey)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(queried, discovered.len());
+
+        // accounts 0 to 4, then the 3 unused accounts 5 to 7
+        assert_eq!(discovered.len(), 8 * 3);
+        assert_eq!(discovered.last().unwrap().account, 7);
+        assert_eq!(
+            discovered
+                .iter()
+                .filter(|discovered| discovered.used)
+                .map(|discovered| (discovered.account, discovered.change))
+                .collect::<Vec<_>>(),
+            vec![(1, None), (4, Some(1))]
+        );
+    }
+
+    #[test]
+    fn test_discovery_start_account() {
+        let discovered = AccountDiscovery::new(&SEED)
+            .with_change_indexes([Some(0)])
+            .with_start_account(MAX_ACCOUNT - 1)
+            .map(|discovered| discovered.unwrap().account)
+            .collect::<Vec<_>>();
+        assert_eq!(discovered, vec![MAX_ACCOUNT - 1, MAX_ACCOUNT]);
+
+        assert_eq!(
+            AccountDiscovery::new(&SEED)
+                .with_start_account(MAX_ACCOUNT + 1)
+                .count(),
+            0
+        );
+        assert_eq!(
+            AccountDiscovery::new(&SEED).with_change_indexes([]).count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,582 @@
+//! Password-encrypted keypair files.
+//!
+//! An encrypted keypair file is a JSON object instead of the JSON byte array
+//! written by [`write_keypair`](crate::write_keypair):
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "pubkey": "<base58 public key>",
+//!   "kdf": { "name": "scrypt", "logN": 17, "r": 8, "p": 1, "salt": "<hex>" },
+//!   "cipher": { "name": "xchacha20poly1305", "nonce": "<hex>" },
+//!   "ciphertext": "<hex>"
+//! }
+//! ```
+//!
+//! The 32 byte secret key is encrypted with XChaCha20-Poly1305 under a key
+//! derived from the password with scrypt. The public key is stored in the
+//! clear, so that it can be read without the password, and authenticated as
+//! associated data.
+//!
+//! [`read_keypair_with_password`] and [`read_keypair_file_with_password`]
+//! accept both plaintext and encrypted files.
+
+use {
+    crate::{read_keypair, Keypair},
+    chacha20poly1305::{
+        aead::{Aead, KeyInit, Payload},
+        XChaCha20Poly1305, XNonce,
+    },
+    serde_derive::{Deserialize, Serialize},
+    solana_address::Address,
+    solana_signer::{EncodableKey, EncodableKeypair, Signer},
+    std::{
+        error, fmt,
+        fs::File,
+        io::{Cursor, Read, Write},
+        path::Path,
+    },
+    zeroize::Zeroizing,
+};
+
+/// Version of the encrypted keypair file format.
+pub const ENCRYPTED_KEYPAIR_VERSION: u32 = 1;
+
+const KDF_NAME: &str = "scrypt";
+const CIPHER_NAME: &str = "xchacha20poly1305";
+const SALT_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+/// Largest scrypt cost accepted, as a base 2 logarithm.
+pub const MAX_SCRYPT_LOG_N: u8 = 20;
+/// Largest product of the scrypt block size and parallelization accepted.
+///
+/// Together with [`MAX_SCRYPT_LOG_N`], this bounds the memory used to derive
+/// a key to 1 GiB.
+pub const MAX_SCRYPT_R_P: u32 = 8;
+
+/// Errors encrypting or decrypting a keypair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// The keypair file is encrypted and no password was given.
+    PasswordRequired,
+    /// The file was written with an unknown version of the format.
+    UnsupportedVersion(u32),
+    /// The file uses an unknown key derivation function or cipher.
+    UnsupportedAlgorithm(String),
+    /// The scrypt parameters are out of range.
+    InvalidKdfParams,
+    /// Encrypting the keypair failed.
+    EncryptionFailed,
+    /// A field of the file is malformed.
+    InvalidField(&'static str),
+    /// The password is wrong or the file was modified.
+    DecryptionFailed,
+}
+
+impl error::Error for EncryptionError {}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PasswordRequired => f.write_str("keypair is encrypted, a password is required"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported encrypted keypair version {version}")
+            }
+            Self::UnsupportedAlgorithm(name) => write!(f, "unsupported algorithm {name}"),
+            Self::InvalidKdfParams => f.write_str("invalid key derivation parameters"),
+            Self::EncryptionFailed => f.write_str("encryption failed"),
+            Self::InvalidField(field) => write!(f, "invalid encrypted keypair field {field}"),
+            Self::DecryptionFailed => {
+                f.write_str("decryption failed, the password is wrong or the keypair is corrupt")
+            }
+        }
+    }
+}
+
+/// Cost parameters of the scrypt key derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScryptParams {
+    /// Base 2 logarithm of the CPU/memory cost.
+    pub log_n: u8,
+    /// Block size.
+    pub r: u32,
+    /// Parallelization.
+    pub p: u32,
+}
+
+impl Default for ScryptParams {
+    /// The parameters recommended by the scrypt crate, using 128 MiB of
+    /// memory per derivation.
+    fn default() -> Self {
+        Self {
+            log_n: scrypt::Params::RECOMMENDED_LOG_N,
+            r: scrypt::Params::RECOMMENDED_R,
+            p: scrypt::Params::RECOMMENDED_P,
+        }
+    }
+}
+
+impl ScryptParams {
+    /// Derive the encryption key, refusing parameters above
+    /// [`MAX_SCRYPT_LOG_N`] and [`MAX_SCRYPT_R_P`] since they are read from
+    /// untrusted files.
+    fn derive_key(
+        &self,
+        password: &str,
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; KEY_LENGTH]>, EncryptionError> {
+        let r_p = self
+            .r
+            .checked_mul(self.p)
+            .ok_or(EncryptionError::InvalidKdfParams)?;
+        if self.log_n > MAX_SCRYPT_LOG_N || r_p > MAX_SCRYPT_R_P {
+            return Err(EncryptionError::InvalidKdfParams);
+        }
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, KEY_LENGTH)
+            .map_err(|_| EncryptionError::InvalidKdfParams)?;
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        scrypt::scrypt(password.as_bytes(), salt, &params, key.as_mut())
+            .map_err(|_| EncryptionError::InvalidKdfParams)?;
+        Ok(key)
+    }
+}
+
+/// A [`Keypair`] encrypted with a password.
+///
+/// Reading and writing an `EncryptedKeypair` through [`EncodableKey`] doesn't
+/// need the password; only [`EncryptedKeypair::decrypt`] does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedKeypair {
+    pubkey: Address,
+    kdf_params: ScryptParams,
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedKeypair {
+    /// Encrypt `keypair` with `password`, using the default
+    /// [`ScryptParams`].
+    pub fn encrypt(keypair: &Keypair, password: &str) -> Result<Self, EncryptionError> {
+        Self::encrypt_with_params(keypair, password, ScryptParams::default())
+    }
+
+    /// Encrypt `keypair` with `password`, deriving the encryption key with
+    /// `kdf_params`.
+    pub fn encrypt_with_params(
+        keypair: &Keypair,
+        password: &str,
+        kdf_params: ScryptParams,
+    ) -> Result<Self, EncryptionError> {
+        let pubkey = keypair.pubkey();
+        let salt = rand::random::<[u8; SALT_LENGTH]>();
+        let nonce = rand::random::<[u8; NONCE_LENGTH]>();
+        let key = kdf_params.derive_key(password, &salt)?;
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: keypair.secret_bytes(),
+                    aad: pubkey.as_ref(),
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+        Ok(Self {
+            pubkey,
+            kdf_params,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypt the keypair with `password`.
+    pub fn decrypt(&self, password: &str) -> Result<Keypair, EncryptionError> {
+        let key = self.kdf_params.derive_key(password, &self.salt)?;
+        let secret_key = XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: self.pubkey.as_ref(),
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| EncryptionError::DecryptionFailed)?;
+        let secret_key = <[u8; Keypair::SECRET_KEY_LENGTH]>::try_from(secret_key.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| EncryptionError::InvalidField("ciphertext"))?;
+        let keypair = Keypair::new_from_array(*secret_key);
+        if keypair.pubkey() != self.pubkey {
+            return Err(EncryptionError::InvalidField("pubkey"));
+        }
+        Ok(keypair)
+    }
+
+    /// The public key of the encrypted keypair.
+    pub fn pubkey(&self) -> &Address {
+        &self.pubkey
+    }
+
+    /// The key derivation parameters the keypair was encrypted with.
+    pub fn kdf_params(&self) -> &ScryptParams {
+        &self.kdf_params
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct EncryptedKeypairFile {
+    version: u32,
+    pubkey: String,
+    kdf: KdfFile,
+    cipher: CipherFile,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct KdfFile {
+    name: String,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CipherFile {
+    name: String,
+    nonce: String,
+}
+
+fn decode_hex<const N: usize>(
+    value: &str,
+    field: &'static str,
+) -> Result<[u8; N], EncryptionError> {
+    let mut out = [0u8; N];
+    hex::decode_to_slice(value, &mut out).map_err(|_| EncryptionError::InvalidField(field))?;
+    Ok(out)
+}
+
+impl TryFrom<EncryptedKeypairFile> for EncryptedKeypair {
+    type Error = EncryptionError;
+
+    fn try_from(file: EncryptedKeypairFile) -> Result<Self, Self::Error> {
+        if file.version != ENCRYPTED_KEYPAIR_VERSION {
+            return Err(EncryptionError::UnsupportedVersion(file.version));
+        }
+        if file.kdf.name != KDF_NAME {
+            return Err(EncryptionError::UnsupportedAlgorithm(file.kdf.name));
+        }
+        if file.cipher.name != CIPHER_NAME {
+            return Err(EncryptionError::UnsupportedAlgorithm(file.cipher.name));
+        }
+        Ok(Self {
+            pubkey: file
+                .pubkey
+                .parse()
+                .map_err(|_| EncryptionError::InvalidField("pubkey"))?,
+            kdf_params: ScryptParams {
+                log_n: file.kdf.log_n,
+                r: file.kdf.r,
+                p: file.kdf.p,
+            },
+            salt: decode_hex(&file.kdf.salt, "salt")?,
+            nonce: decode_hex(&file.cipher.nonce, "nonce")?,
+            ciphertext: hex::decode(&file.ciphertext)
+                .map_err(|_| EncryptionError::InvalidField("ciphertext"))?,
+        })
+    }
+}
+
+impl From<&EncryptedKeypair> for EncryptedKeypairFile {
+    fn from(keypair: &EncryptedKeypair) -> Self {
+        Self {
+            version: ENCRYPTED_KEYPAIR_VERSION,
+            pubkey: keypair.pubkey.to_string(),
+            kdf: KdfFile {
+                name: KDF_NAME.to_string(),
+                log_n: keypair.kdf_params.log_n,
+                r: keypair.kdf_params.r,
+                p: keypair.kdf_params.p,
+                salt: hex::encode(keypair.salt),
+            },
+            cipher: CipherFile {
+                name: CIPHER_NAME.to_string(),
+                nonce: hex::encode(keypair.nonce),
+            },
+            ciphertext: hex::encode(&keypair.ciphertext),
+        }
+    }
+}
+
+impl EncodableKey for EncryptedKeypair {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, Box<dyn error::Error>> {
+        let file: EncryptedKeypairFile = serde_json::from_reader(reader)?;
+        Ok(Self::try_from(file)?)
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<String, Box<dyn error::Error>> {
+        let json = serde_json::to_string_pretty(&EncryptedKeypairFile::from(self))?;
+        writer.write_all(json.as_bytes())?;
+        Ok(json)
+    }
+}
+
+impl EncodableKeypair for EncryptedKeypair {
+    type Pubkey = Address;
+
+    fn encodable_pubkey(&self) -> Self::Pubkey {
+        self.pubkey
+    }
+}
+
+/// Whether `contents` is an encrypted keypair rather than a plaintext one.
+pub(crate) fn is_encrypted(contents: &str) -> bool {
+    contents.trim_start().starts_with('{')
+}
+
+/// Reads a plaintext or encrypted `Keypair` from a `Reader` implementor,
+/// decrypting it with `password` if needed
+pub fn read_keypair_with_password<R: Read>(
+    reader: &mut R,
+    password: &str,
+) -> Result<Keypair, Box<dyn error::Error>> {
+    let mut contents = Zeroizing::new(String::new());
+    reader.read_to_string(&mut contents)?;
+    if is_encrypted(&contents) {
+        let encrypted = EncryptedKeypair::read(&mut contents.as_bytes())?;
+        Ok(encrypted.decrypt(password)?)
+    } else {
+        read_keypair(&mut Cursor::new(contents.as_bytes()))
+    }
+}
+
+/// Reads a plaintext or encrypted `Keypair` from a file, decrypting it with
+/// `password` if needed
+pub fn read_keypair_file_with_password<F: AsRef<Path>>(
+    path: F,
+    password: &str,
+) -> Result<Keypair, Box<dyn error::Error>> {
+    let mut file = File::open(path.as_ref())?;
+    read_keypair_with_password(&mut file, password)
+}
+
+/// Writes a `Keypair` encrypted with `password` to a file
+pub fn write_encrypted_keypair_file<F: AsRef<Path>>(
+    keypair: &Keypair,
+    password: &str,
+    outfile: F,
+) -> Result<String, Box<dyn error::Error>> {
+    EncryptedKeypair::encrypt(keypair, password)?.write_to_file(outfile)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{read_keypair_file, write_keypair, write_keypair_file},
+    };
+
+    // cheap parameters, so that tests run quickly
+    const TEST_PARAMS: ScryptParams = ScryptParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    fn write_to_string(encrypted: &EncryptedKeypair) -> String {
+        let mut buffer = vec![];
+        encrypted.write(&mut buffer).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let keypair = Keypair::new();
+        let encrypted =
+            EncryptedKeypair::encrypt_with_params(&keypair, "hunter2", TEST_PARAMS).unwrap();
+        assert_eq!(encrypted.pubkey(), &keypair.pubkey());
+        assert_eq!(encrypted.encodable_pubkey(), keypair.pubkey());
+        assert_eq!(encrypted.kdf_params(), &TEST_PARAMS);
+        assert_eq!(encrypted.decrypt("hunter2").unwrap(), keypair);
+        assert_eq!(
+            encrypted.decrypt("hunter3"),
+            Err(EncryptionError::DecryptionFailed)
+        );
+
+        // salts and nonces are random
+        let encrypted_again =
+            EncryptedKeypair::encrypt_with_params(&keypair, "hunter2", TEST_PARAMS).unwrap();
+        assert_ne!(encrypted, encrypted_again);
+    }
+
+    #[test]
+    fn test_encodable_key() {
+        let keypair = Keypair::new();
+        let encrypted =
+            EncryptedKeypair::encrypt_with_params(&keypair, "password", TEST_PARAMS).unwrap();
+        let json = write_to_string(&encrypted);
+        assert!(!json.contains(&keypair.to_base58_string()));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["pubkey"], keypair.pubkey().to_string());
+        assert_eq!(value["kdf"]["name"], "scrypt");
+        assert_eq!(value["kdf"]["logN"], 4);
+        assert_eq!(value["cipher"]["name"], "xchacha20poly1305");
+
+        let read = EncryptedKeypair::read(&mut json.as_bytes()).unwrap();
+        assert_eq!(read, encrypted);
+        assert_eq!(read.decrypt("password").unwrap(), keypair);
+    }
+
+    #[test]
+    fn test_tampered_file() {
+        let keypair = Keypair::new();
+        let encrypted =
+            EncryptedKeypair::encrypt_with_params(&keypair, "password", TEST_PARAMS).unwrap();
+
+        let mut swapped_pubkey = encrypted.clone();
+        swapped_pubkey.pubkey = Keypair::new().pubkey();
+        assert_eq!(
+            swapped_pubkey.decrypt("password"),
+            Err(EncryptionError::DecryptionFailed)
+        );
+
+        let mut flipped = encrypted.clone();
+        flipped.ciphertext[0] ^= 1;
+        assert_eq!(
+            flipped.decrypt("password"),
+            Err(EncryptionError::DecryptionFailed)
+        );
+
+        let json = write_to_string(&encrypted);
+        let read_err = |json: String| {
+            EncryptedKeypair::read(&mut json.as_bytes())
+                .unwrap_err()
+                .downcast::<EncryptionError>()
+                .map(|err| *err)
+                .unwrap()
+        };
+        assert_eq!(
+            read_err(json.replace("\"version\": 1", "\"version\": 2")),
+            EncryptionError::UnsupportedVersion(2)
+        );
+        assert_eq!(
+            read_err(json.replace("scrypt", "argon2id")),
+            EncryptionError::UnsupportedAlgorithm("argon2id".to_string())
+        );
+        assert_eq!(
+            read_err(json.replace("\"nonce\": \"", "\"nonce\": \"00")),
+            EncryptionError::InvalidField("nonce")
+        );
+        assert!(EncryptedKeypair::read(&mut "{}".as_bytes()).is_err());
+
+        let mut invalid_params = encrypted;
+        invalid_params.kdf_params.log_n = 64;
+        assert_eq!(
+            invalid_params.decrypt("password"),
+            Err(EncryptionError::InvalidKdfParams)
+        );
+    }
+
+    #[test]
+    fn test_excessive_kdf_params() {
+        let keypair = Keypair::new();
+        let encrypted =
+            EncryptedKeypair::encrypt_with_params(&keypair, "password", TEST_PARAMS).unwrap();
+        let json = write_to_string(&encrypted);
+
+        // would need 128 GiB of memory to derive the key
+        let read =
+            EncryptedKeypair::read(&mut json.replace("\"logN\": 4", "\"logN\": 30").as_bytes())
+                .unwrap();
+        assert_eq!(
+            read.decrypt("password"),
+            Err(EncryptionError::InvalidKdfParams)
+        );
+
+        for (r, p) in [(MAX_SCRYPT_R_P + 1, 1), (8, 2), (u32::MAX, u32::MAX)] {
+            let mut excessive = encrypted.clone();
+            excessive.kdf_params.r = r;
+            excessive.kdf_params.p = p;
+            assert_eq!(
+                excessive.decrypt("password"),
+                Err(EncryptionError::InvalidKdfParams)
+            );
+        }
+
+        let excessive = ScryptParams {
+            log_n: MAX_SCRYPT_LOG_N + 1,
+            ..TEST_PARAMS
+        };
+        assert_eq!(
+            EncryptedKeypair::encrypt_with_params(&keypair, "password", excessive),
+            Err(EncryptionError::InvalidKdfParams)
+        );
+        assert!(ScryptParams::default().log_n <= MAX_SCRYPT_LOG_N);
+        assert!(ScryptParams::default().r * ScryptParams::default().p <= MAX_SCRYPT_R_P);
+    }
+
+    #[test]
+    fn test_read_keypair_with_password() {
+        let keypair = Keypair::new();
+
+        let mut plaintext = vec![];
+        write_keypair(&keypair, &mut plaintext).unwrap();
+        assert_eq!(
+            read_keypair_with_password(&mut plaintext.as_slice(), "ignored").unwrap(),
+            keypair
+        );
+
+        let encrypted =
+            EncryptedKeypair::encrypt_with_params(&keypair, "password", TEST_PARAMS).unwrap();
+        let json = write_to_string(&encrypted);
+        assert_eq!(
+            read_keypair_with_password(&mut json.as_bytes(), "password").unwrap(),
+            keypair
+        );
+        assert!(read_keypair_with_password(&mut json.as_bytes(), "wrong").is_err());
+        assert_eq!(
+            *read_keypair(&mut json.as_bytes())
+                .unwrap_err()
+                .downcast::<EncryptionError>()
+                .unwrap(),
+            EncryptionError::PasswordRequired
+        );
+    }
+
+    #[test]
+    fn test_read_keypair_file_with_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = Keypair::new();
+
+        let plaintext_path = dir.path().join("plaintext.json");
+        write_keypair_file(&keypair, &plaintext_path).unwrap();
+        assert_eq!(
+            read_keypair_file_with_password(&plaintext_path, "ignored").unwrap(),
+            keypair
+        );
+
+        let encrypted_path = dir.path().join("encrypted.json");
+        EncryptedKeypair::encrypt_with_params(&keypair, "password", TEST_PARAMS)
+            .unwrap()
+            .write_to_file(&encrypted_path)
+            .unwrap();
+        assert_eq!(
+            read_keypair_file_with_password(&encrypted_path, "password").unwrap(),
+            keypair
+        );
+        assert!(read_keypair_file(&encrypted_path).is_err());
+        assert_eq!(
+            EncryptedKeypair::read_from_file(&encrypted_path)
+                .unwrap()
+                .encodable_pubkey(),
+            keypair.pubkey()
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! [SLIP-10] extended keypairs.
+//!
+//! An [`ExtendedKeypair`] is a node of the ed25519 derivation tree of a
+//! seed: a keypair and the chain code needed to derive its children. Handing
+//! out the node at a hardened path such as `m/44'/501'/0'` lets its holder
+//! derive the keypairs below it without learning the seed or any other
+//! branch of the tree.
+//!
+//! ed25519 only supports hardened derivation, so deriving children always
+//! takes the secret key and there is no extended public key.
+//!
+//! # Serialization
+//!
+//! Extended keypairs serialize like BIP-32 extended private keys: the 78
+//! bytes
+//!
+//! | bytes | content                                                       |
+//! |-------|---------------------------------------------------------------|
+//! | 4     | version, [`EXTENDED_KEYPAIR_VERSION`] big-endian              |
+//! | 1     | depth, 0 for the master node                                  |
+//! | 4     | fingerprint of the parent node, 0 for the master node         |
+//! | 4     | child index big-endian, including the hardened bit            |
+//! | 32    | chain code                                                    |
+//! | 33    | `0x00` followed by the secret key                             |
+//!
+//! are followed by the first 4 bytes of their double SHA-256 checksum and
+//! encoded in base58 by [`ExtendedKeypair::to_base58_string`], which gives
+//! strings starting with `sxprv`. The fingerprint of a node is the first 4
+//! bytes of the RIPEMD-160 hash of the SHA-256 hash of `0x00` followed by its
+//! public key.
+//!
+//! [SLIP-10]: https://github.com/satoshilabs/slips/blob/master/slip-0010.md
+
+use {
+    crate::Keypair,
+    ed25519_dalek_bip32::{ChildIndex, ExtendedSigningKey},
+    ripemd::Ripemd160,
+    sha2::{Digest, Sha256},
+    solana_derivation_path::DerivationPath,
+    solana_signer::Signer,
+    std::{error, fmt},
+};
+
+/// Version bytes of serialized extended keypairs.
+pub const EXTENDED_KEYPAIR_VERSION: u32 = 0xf00f_8202;
+
+const SERIALIZED_LENGTH: usize = 78;
+const CHECKSUM_LENGTH: usize = 4;
+const HARDENED_BIT: u32 = 1 << 31;
+
+/// Errors deriving or parsing an extended keypair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtendedKeypairError {
+    /// Child indexes must be below 2^31, the hardened bit is implied.
+    InvalidChildIndex(u32),
+    /// The derivation tree is limited to a depth of 255.
+    MaxDepthExceeded,
+    /// The derivation path has a non-hardened index.
+    NonHardenedPath,
+    /// The string isn't valid base58.
+    InvalidBase58,
+    /// The decoded string has the wrong length.
+    InvalidLength(usize),
+    /// The checksum doesn't match.
+    InvalidChecksum,
+    /// The version bytes aren't [`EXTENDED_KEYPAIR_VERSION`].
+    InvalidVersion(u32),
+    /// The serialized node is inconsistent, e.g. a master node with a
+    /// parent.
+    InvalidKeyData,
+}
+
+impl error::Error for ExtendedKeypairError {}
+
+impl fmt::Display for ExtendedKeypairError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidChildIndex(index) => write!(f, "invalid child index {index}"),
+            Self::MaxDepthExceeded => f.write_str("maximum derivation depth exceeded"),
+            Self::NonHardenedPath => f.write_str("derivation path must be hardened"),
+            Self::InvalidBase58 => f.write_str("invalid base58 string"),
+            Self::InvalidLength(len) => write!(f, "invalid extended keypair length {len}"),
+            Self::InvalidChecksum => f.write_str("invalid extended keypair checksum"),
+            Self::InvalidVersion(version) => {
+                write!(f, "invalid extended keypair version {version:#010x}")
+            }
+            Self::InvalidKeyData => f.write_str("invalid extended keypair data"),
+        }
+    }
+}
+
+/// A keypair with the chain code to derive its children, at some depth of
+/// the derivation tree of a seed.
+pub struct ExtendedKeypair {
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_index: u32,
+    chain_code: [u8; 32],
+    keypair: Keypair,
+}
+
+impl ExtendedKeypair {
+    /// The master node of `seed`.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        // deriving the master node from a seed can't fail
+        let extended = ExtendedSigningKey::from_seed(seed).unwrap();
+        Self {
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_index: 0,
+            chain_code: extended.chain_code,
+            keypair: Keypair(extended.signing_key),
+        }
+    }
+
+    /// The node of `seed` at `derivation_path`.
+    ///
+    /// Its keypair is the one returned by
+    /// [`keypair_from_seed_and_derivation_path`](crate::seed_derivable::keypair_from_seed_and_derivation_path)
+    /// for the same seed and path.
+    pub fn from_seed_and_derivation_path(
+        seed: &[u8],
+        derivation_path: &DerivationPath,
+    ) -> Result<Self, ExtendedKeypairError> {
+        derivation_path
+            .path()
+            .iter()
+            .try_fold(Self::from_seed(seed), |node, index| match index {
+                ChildIndex::Hardened(index) => node.derive_child(*index),
+                ChildIndex::Normal(_) => Err(ExtendedKeypairError::NonHardenedPath),
+            })
+    }
+
+    /// The child at hardened index `index`, i.e. `index'`.
+    pub fn derive_child(&self, index: u32) -> Result<Self, ExtendedKeypairError> {
+        if index >= HARDENED_BIT {
+            return Err(ExtendedKeypairError::InvalidChildIndex(index));
+        }
+        if self.depth == u8::MAX {
+            return Err(ExtendedKeypairError::MaxDepthExceeded);
+        }
+        let child = ExtendedSigningKey {
+            depth: self.depth,
+            child_index: ChildIndex::Hardened(0),
+            signing_key: self.keypair.0.clone(),
+            chain_code: self.chain_code,
+        }
+        .derive_child(ChildIndex::Hardened(index))
+        .map_err(|_| ExtendedKeypairError::InvalidChildIndex(index))?;
+        Ok(Self {
+            depth: child.depth,
+            parent_fingerprint: self.fingerprint(),
+            child_index: index | HARDENED_BIT,
+            chain_code: child.chain_code,
+            keypair: Keypair(child.signing_key),
+        })
+    }
+
+    /// The descendant at hardened indexes `indexes`, relative to this node.
+    pub fn derive(&self, indexes: &[u32]) -> Result<Self, ExtendedKeypairError> {
+        indexes
+            .iter()
+            .try_fold(self.insecure_clone(), |node, index| {
+                node.derive_child(*index)
+            })
+    }
+
+    /// The keypair of the node.
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
+    /// The chain code of the node.
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    /// Number of derivations from the master node.
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Index of the node in its parent, without the hardened bit, or `None`
+    /// for the master node.
+    pub fn child_index(&self) -> Option<u32> {
+        (self.depth > 0).then_some(self.child_index & !HARDENED_BIT)
+    }
+
+    /// Fingerprint of the parent node, zero for the master node.
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    /// Fingerprint of the node, identifying it as the parent of its
+    /// children.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let mut key = [0u8; 33];
+        key[1..].copy_from_slice(self.keypair.pubkey().as_ref());
+        let hash = Ripemd160::digest(Sha256::digest(key));
+        let mut fingerprint = [0u8; 4];
+        fingerprint.copy_from_slice(&hash[..4]);
+        fingerprint
+    }
+
+    /// Copy the node, including its secret key.
+    ///
+    /// Like [`Keypair::insecure_clone`], only use this when strictly
+    /// required.
+    pub fn insecure_clone(&self) -> Self {
+        Self {
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_index: self.child_index,
+            chain_code: self.chain_code,
+            keypair: self.keypair.insecure_clone(),
+        }
+    }
+
+    /// The node serialized as a base58 string starting with `sxprv`, which
+    /// includes its secret key.
+    pub fn to_base58_string(&self) -> String {
+        let bytes = self.to_serialized_bytes();
+        let mut with_checksum = [0u8; SERIALIZED_LENGTH + CHECKSUM_LENGTH];
+        with_checksum[..SERIALIZED_LENGTH].copy_from_slice(&bytes);
+        with_checksum[SERIALIZED_LENGTH..].copy_from_slice(&checksum(&bytes));
+        bs58::encode(with_checksum).into_string()
+    }
+
+    /// Parse a node serialized by [`to_base58_string`](Self::to_base58_string).
+    pub fn from_base58_string(s: &str) -> Result<Self, ExtendedKeypairError> {
+        let decoded = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| ExtendedKeypairError::InvalidBase58)?;
+        if decoded.len() != SERIALIZED_LENGTH + CHECKSUM_LENGTH {
+            return Err(ExtendedKeypairError::InvalidLength(decoded.len()));
+        }
+        let (bytes, expected_checksum) = decoded.split_at(SERIALIZED_LENGTH);
+        if checksum(bytes) != expected_checksum {
+            return Err(ExtendedKeypairError::InvalidChecksum);
+        }
+
+        let version = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        if version != EXTENDED_KEYPAIR_VERSION {
+            return Err(ExtendedKeypairError::InvalidVersion(version));
+        }
+        let depth = bytes[4];
+        let parent_fingerprint: [u8; 4] = bytes[5..9].try_into().unwrap();
+        let child_index = u32::from_be_bytes(bytes[9..13].try_into().unwrap());
+        let is_master = depth == 0;
+        let is_valid = bytes[45] == 0
+            && if is_master {
+                parent_fingerprint == [0; 4] && child_index == 0
+            } else {
+                child_index & HARDENED_BIT != 0
+            };
+        if !is_valid {
+            return Err(ExtendedKeypairError::InvalidKeyData);
+        }
+        Ok(Self {
+            depth,
+            parent_fingerprint,
+            child_index,
+            chain_code: bytes[13..45].try_into().unwrap(),
+            keypair: Keypair::new_from_array(bytes[46..].try_into().unwrap()),
+        })
+    }
+
+    fn to_serialized_bytes(&self) -> [u8; SERIALIZED_LENGTH] {
+        let mut bytes = [0u8; SERIALIZED_LENGTH];
+        bytes[..4].copy_from_slice(&EXTENDED_KEYPAIR_VERSION.to_be_bytes());
+        bytes[4] = self.depth;
+        bytes[5..9].copy_from_slice(&self.parent_fingerprint);
+        bytes[9..13].copy_from_slice(&self.child_index.to_be_bytes());
+        bytes[13..45].copy_from_slice(&self.chain_code);
+        bytes[46..].copy_from_slice(self.keypair.secret_bytes());
+        bytes
+    }
+}
+
+impl PartialEq for ExtendedKeypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_serialized_bytes() == other.to_serialized_bytes()
+    }
+}
+
+impl Eq for ExtendedKeypair {}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let hash = Sha256::digest(Sha256::digest(bytes));
+    let mut checksum = [0u8; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(&hash[..CHECKSUM_LENGTH]);
+    checksum
+}
+
+// the chain code and secret key are left out, so that formatting a node
+// doesn't leak them
+impl fmt::Debug for ExtendedKeypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedKeypair")
+            .field("depth", &self.depth)
+            .field("parent_fingerprint", &self.parent_fingerprint)
+            .field("child_index", &self.child_index)
+            .field("pubkey", &self.keypair.pubkey())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::seed_derivable::keypair_from_seed_and_derivation_path};
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i.saturating_add(2)], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_slip10_vector() {
+        // test vector 1 for ed25519 from SLIP-10
+        let seed = decode_hex("000102030405060708090a0b0c0d0e0f");
+        let master = ExtendedKeypair::from_seed(&seed);
+        assert_eq!(
+            master.chain_code().to_vec(),
+            decode_hex("90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb")
+        );
+        assert_eq!(
+            master.keypair().secret_bytes().to_vec(),
+            decode_hex("2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7")
+        );
+        assert_eq!(master.fingerprint(), [0xdd, 0xeb, 0xc6, 0x75]);
+
+        let child = master.derive_child(0).unwrap();
+        assert_eq!(child.depth(), 1);
+        assert_eq!(child.child_index(), Some(0));
+        assert_eq!(child.parent_fingerprint(), [0xdd, 0xeb, 0xc6, 0x75]);
+        assert_eq!(
+            child.chain_code().to_vec(),
+            decode_hex("8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69")
+        );
+        assert_eq!(
+            child.keypair().secret_bytes().to_vec(),
+            decode_hex("68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3")
+        );
+    }
+
+    #[test]
+    fn test_derivation_path() {
+        let seed = [42u8; 64];
+        let account = ExtendedKeypair::from_seed_and_derivation_path(
+            &seed,
+            &DerivationPath::new_bip44(Some(3), None),
+        )
+        .unwrap();
+        assert_eq!(account.depth(), 3);
+        assert_eq!(account.child_index(), Some(3));
+        assert_eq!(
+            account.keypair(),
+            &keypair_from_seed_and_derivation_path(
+                &seed,
+                Some(DerivationPath::new_bip44(Some(3), None))
+            )
+            .unwrap()
+        );
+
+        // a subordinate holding the account node derives the same keypairs
+        // as the holder of the seed
+        let account = ExtendedKeypair::from_base58_string(&account.to_base58_string()).unwrap();
+        let change = account.derive(&[7]).unwrap();
+        assert_eq!(
+            change.keypair(),
+            &keypair_from_seed_and_derivation_path(
+                &seed,
+                Some(DerivationPath::new_bip44(Some(3), Some(7)))
+            )
+            .unwrap()
+        );
+        assert_eq!(change.parent_fingerprint(), account.fingerprint());
+        assert_eq!(account.derive(&[]).unwrap(), account);
+
+        assert_eq!(
+            ExtendedKeypair::from_seed_and_derivation_path(
+                &seed,
+                &DerivationPath::from_absolute_path_str("m/44/501").unwrap()
+            ),
+            ExtendedKeypair::from_seed_and_derivation_path(
+                &seed,
+                &DerivationPath::from_absolute_path_str("m/44'/501'").unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_derive_errors() {
+        let master = ExtendedKeypair::from_seed(&[1; 32]);
+        assert_eq!(
+            master.derive_child(HARDENED_BIT),
+            Err(ExtendedKeypairError::InvalidChildIndex(HARDENED_BIT))
+        );
+        let deepest = master.derive(&[0; 255]).unwrap();
+        assert_eq!(deepest.depth(), u8::MAX);
+        assert_eq!(
+            deepest.derive_child(0),
+            Err(ExtendedKeypairError::MaxDepthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_serialization() {
+        let master = ExtendedKeypair::from_seed(&[9; 64]);
+        let node = master.derive(&[44, 501, 0]).unwrap();
+        for node in [master, node] {
+            let serialized = node.to_base58_string();
+            assert!(serialized.starts_with("sxprv"));
+            let debug = format!("{node:?}");
+            assert!(!debug.contains(&format!("{:?}", node.chain_code())));
+            assert!(!debug.contains(&format!("{:?}", node.keypair().secret_bytes())));
+            assert_eq!(ExtendedKeypair::from_base58_string(&serialized), Ok(node));
+        }
+
+        assert_eq!(
+            ExtendedKeypair::from_base58_string("0OIl"),
+            Err(ExtendedKeypairError::InvalidBase58)
+        );
+        assert_eq!(
+            ExtendedKeypair::from_base58_string("sxprv"),
+            Err(ExtendedKeypairError::InvalidLength(4))
+        );
+
+        let encode = |bytes: &[u8]| {
+            let mut with_checksum = bytes.to_vec();
+            with_checksum.extend_from_slice(&checksum(bytes));
+            bs58::encode(with_checksum).into_string()
+        };
+        let bytes = ExtendedKeypair::from_seed(&[9; 64])
+            .derive_child(1)
+            .unwrap()
+            .to_serialized_bytes();
+
+        let mut corrupted = encode(&bytes).into_bytes();
+        corrupted[20] = if corrupted[20] == b'2' { b'3' } else { b'2' };
+        assert_eq!(
+            ExtendedKeypair::from_base58_string(std::str::from_utf8(&corrupted).unwrap()),
+            Err(ExtendedKeypairError::InvalidChecksum)
+        );
+
+        let mut xprv = bytes;
+        xprv[..4].copy_from_slice(&0x0488_ade4u32.to_be_bytes());
+        assert_eq!(
+            ExtendedKeypair::from_base58_string(&encode(&xprv)),
+            Err(ExtendedKeypairError::InvalidVersion(0x0488_ade4))
+        );
+
+        let mut non_hardened = bytes;
+        non_hardened[9] = 0;
+        assert_eq!(
+            ExtendedKeypair::from_base58_string(&encode(&non_hardened)),
+            Err(ExtendedKeypairError::InvalidKeyData)
+        );
+
+        let mut orphan_master = bytes;
+        orphan_master[4] = 0;
+        assert_eq!(
+            ExtendedKeypair::from_base58_string(&encode(&orphan_master)),
+            Err(ExtendedKeypairError::InvalidKeyData)
+        );
+    }
+}
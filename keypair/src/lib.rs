@@ -16,6 +16,12 @@ pub use {
     solana_signer::{EncodableKey, EncodableKeypair, Signer},
 };
 
+#[cfg(feature = "seed-derivable")]
+pub mod account_discovery;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+#[cfg(feature = "seed-derivable")]
+pub mod extended_keypair;
 #[cfg(feature = "seed-derivable")]
 pub mod seed_derivable;
 pub mod signable;
@@ -162,6 +168,10 @@ impl EncodableKeypair for Keypair {
 pub fn read_keypair<R: Read>(reader: &mut R) -> Result<Keypair, Box<dyn error::Error>> {
     let mut buffer = String::new();
     reader.read_to_string(&mut buffer)?;
+    #[cfg(feature = "encryption")]
+    if encrypted::is_encrypted(&buffer) {
+        return Err(encrypted::EncryptionError::PasswordRequired.into());
+    }
     let trimmed = buffer.trim();
     if !trimmed.starts_with('[') || !trimmed.ends_with(']') {
         return Err(std::io::Error::new(
@@ -196,6 +206,13 @@ pub fn read_keypair<R: Read>(reader: &mut R) -> Result<Keypair, Box<dyn error::E
 }
 
 /// Reads a `Keypair` from a file
+///
+/// With the `encryption` feature, encrypted keypair files are detected and
+/// rejected with [`EncryptionError::PasswordRequired`]; use
+/// [`read_keypair_file_with_password`] to read them.
+///
+/// [`EncryptionError::PasswordRequired`]: encrypted::EncryptionError::PasswordRequired
+/// [`read_keypair_file_with_password`]: encrypted::read_keypair_file_with_password
 pub fn read_keypair_file<F: AsRef<Path>>(path: F) -> Result<Keypair, Box<dyn error::Error>> {
     Keypair::read_from_file(path)
 }
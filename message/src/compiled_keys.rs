@@ -53,6 +53,15 @@ struct CompiledKeyMeta {
     is_nonce: bool,
 }
 
+impl CompiledKeyMeta {
+    /// Signers, invoked programs and the nonce account must be static keys,
+    /// all other keys may be loaded from an address lookup table.
+    #[cfg(not(target_os = "solana"))]
+    fn is_lookup_table_candidate(&self) -> bool {
+        !self.is_signer && !self.is_invoked && !self.is_nonce
+    }
+}
+
 impl CompiledKeys {
     /// Compiles the pubkeys referenced by a list of instructions and organizes by
     /// signer/non-signer and writable/readonly.
@@ -139,6 +148,23 @@ impl CompiledKeys {
         Ok((header, static_account_keys))
     }
 
+    /// Number of distinct keys, which is the number of static account keys
+    /// a message would have if no keys were loaded from lookup tables.
+    #[cfg(not(target_os = "solana"))]
+    pub(crate) fn num_keys(&self) -> usize {
+        self.key_meta_map.len()
+    }
+
+    /// Iterates over the keys which could be loaded from a lookup table,
+    /// along with whether each of them is writable.
+    #[cfg(not(target_os = "solana"))]
+    pub(crate) fn lookup_table_candidates(&self) -> impl Iterator<Item = (&Address, bool)> {
+        self.key_meta_map
+            .iter()
+            .filter(|(_, meta)| meta.is_lookup_table_candidate())
+            .map(|(key, meta)| (key, meta.is_writable))
+    }
+
     #[cfg(not(target_os = "solana"))]
     pub(crate) fn try_extract_table_lookup(
         &mut self,
@@ -146,11 +172,11 @@ impl CompiledKeys {
     ) -> Result<Option<(MessageAddressTableLookup, LoadedAddresses)>, CompileError> {
         let (writable_indexes, drained_writable_keys) = self
             .try_drain_keys_found_in_lookup_table(&lookup_table_account.addresses, |meta| {
-                !meta.is_signer && !meta.is_invoked && !meta.is_nonce && meta.is_writable
+                meta.is_lookup_table_candidate() && meta.is_writable
             })?;
         let (readonly_indexes, drained_readonly_keys) = self
             .try_drain_keys_found_in_lookup_table(&lookup_table_account.addresses, |meta| {
-                !meta.is_signer && !meta.is_invoked && !meta.is_nonce && !meta.is_writable
+                meta.is_lookup_table_candidate() && !meta.is_writable
             })?;
 
         // Don't extract lookup if no keys were found
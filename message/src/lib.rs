@@ -44,6 +44,7 @@ mod compiled_keys;
 pub mod inline_nonce;
 pub mod inner_instruction;
 pub mod legacy;
+pub mod transaction_size;
 #[cfg(feature = "serde")]
 use serde_derive::{Deserialize, Serialize};
 #[cfg(feature = "frozen-abi")]
@@ -0,0 +1,451 @@
+//! Serialized size of transactions, computed without serializing them.
+//!
+//! A transaction must fit in a single packet of [`PACKET_DATA_SIZE`] bytes to
+//! be accepted by the network. [`TransactionSize`] breaks the wire size of a
+//! transaction down into its components so that callers can check whether a
+//! message will fit before signing it, and find out what makes it too large
+//! when it doesn't.
+//!
+//! [`PACKET_DATA_SIZE`]: https://docs.rs/solana-packet/latest/solana_packet/constant.PACKET_DATA_SIZE.html
+
+use {
+    crate::{compiled_instruction::CompiledInstruction, legacy::Message, MESSAGE_HEADER_LENGTH},
+    core::fmt,
+    solana_address::Address,
+};
+
+// inlined to avoid solana_packet dep
+pub(crate) const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
+
+const SIGNATURE_BYTES: usize = 64;
+pub(crate) const ADDRESS_BYTES: usize = 32;
+
+/// Number of bytes used by `solana_short_vec` to encode a length.
+pub(crate) fn short_vec_len_size(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+/// Number of bytes used by `solana_short_vec` to encode a byte vector.
+pub(crate) fn short_vec_size(len: usize) -> usize {
+    short_vec_len_size(len).saturating_add(len)
+}
+
+/// A component of a serialized transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionSizeComponent {
+    Signatures,
+    VersionPrefix,
+    Header,
+    AccountKeys,
+    RecentBlockhash,
+    /// A single instruction, identified by its index in the message.
+    Instruction(usize),
+    AddressTableLookups,
+}
+
+impl fmt::Display for TransactionSizeComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Signatures => f.write_str("signatures"),
+            Self::VersionPrefix => f.write_str("version prefix"),
+            Self::Header => f.write_str("message header"),
+            Self::AccountKeys => f.write_str("account keys"),
+            Self::RecentBlockhash => f.write_str("recent blockhash"),
+            Self::Instruction(index) => write!(f, "instruction {index}"),
+            Self::AddressTableLookups => f.write_str("address table lookups"),
+        }
+    }
+}
+
+/// Size in bytes of each component of a serialized transaction.
+///
+/// Sizes of variable length components include their `solana_short_vec`
+/// length prefix.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransactionSize {
+    /// Signatures, one slot per required signer.
+    pub signatures: usize,
+    /// Message version prefix, zero for legacy messages.
+    pub version_prefix: usize,
+    /// Message header.
+    pub header: usize,
+    /// Static account keys.
+    pub account_keys: usize,
+    /// Recent blockhash.
+    pub recent_blockhash: usize,
+    /// All instructions, including the length prefix of the instruction list.
+    pub instructions: usize,
+    /// Size of each instruction in the message.
+    pub instruction_sizes: Vec<usize>,
+    /// Address table lookups, zero for legacy messages.
+    pub address_table_lookups: usize,
+}
+
+impl TransactionSize {
+    pub(crate) fn new(
+        num_signatures: usize,
+        version_prefix: usize,
+        account_keys: &[Address],
+        instructions: &[CompiledInstruction],
+    ) -> Self {
+        let instruction_sizes: Vec<usize> = instructions
+            .iter()
+            .map(|ix| {
+                // program id index
+                1usize
+                    .saturating_add(short_vec_size(ix.accounts.len()))
+                    .saturating_add(short_vec_size(ix.data.len()))
+            })
+            .collect();
+        let instructions = instruction_sizes
+            .iter()
+            .fold(short_vec_len_size(instructions.len()), |size, ix_size| {
+                size.saturating_add(*ix_size)
+            });
+
+        Self {
+            signatures: 0,
+            version_prefix,
+            header: MESSAGE_HEADER_LENGTH,
+            account_keys: short_vec_len_size(account_keys.len())
+                .saturating_add(account_keys.len().saturating_mul(ADDRESS_BYTES)),
+            recent_blockhash: ADDRESS_BYTES,
+            instructions,
+            instruction_sizes,
+            address_table_lookups: 0,
+        }
+        .with_num_signatures(num_signatures)
+    }
+
+    /// Returns this size with room for `num_signatures` signatures.
+    pub fn with_num_signatures(mut self, num_signatures: usize) -> Self {
+        self.signatures = short_vec_len_size(num_signatures)
+            .saturating_add(num_signatures.saturating_mul(SIGNATURE_BYTES));
+        self
+    }
+
+    /// Size of the serialized message, which is the transaction without its
+    /// signatures.
+    pub fn message_size(&self) -> usize {
+        self.version_prefix
+            .saturating_add(self.header)
+            .saturating_add(self.account_keys)
+            .saturating_add(self.recent_blockhash)
+            .saturating_add(self.instructions)
+            .saturating_add(self.address_table_lookups)
+    }
+
+    /// Size of the serialized transaction.
+    pub fn total(&self) -> usize {
+        self.signatures.saturating_add(self.message_size())
+    }
+
+    /// Returns true if the transaction fits in a single packet.
+    pub fn fits_in_packet(&self) -> bool {
+        self.total() <= PACKET_DATA_SIZE
+    }
+
+    /// Returns the largest component of the transaction and its size.
+    ///
+    /// Instructions are considered individually rather than as a whole.
+    pub fn largest_component(&self) -> (TransactionSizeComponent, usize) {
+        [
+            (TransactionSizeComponent::Signatures, self.signatures),
+            (TransactionSizeComponent::VersionPrefix, self.version_prefix),
+            (TransactionSizeComponent::Header, self.header),
+            (TransactionSizeComponent::AccountKeys, self.account_keys),
+            (
+                TransactionSizeComponent::RecentBlockhash,
+                self.recent_blockhash,
+            ),
+            (
+                TransactionSizeComponent::AddressTableLookups,
+                self.address_table_lookups,
+            ),
+        ]
+        .into_iter()
+        .chain(
+            self.instruction_sizes
+                .iter()
+                .enumerate()
+                .map(|(index, size)| (TransactionSizeComponent::Instruction(index), *size)),
+        )
+        .fold(
+            (TransactionSizeComponent::Signatures, 0),
+            |largest, component| {
+                if component.1 > largest.1 {
+                    component
+                } else {
+                    largest
+                }
+            },
+        )
+    }
+
+    /// Returns an error describing what makes the transaction too large if
+    /// it doesn't fit in a single packet.
+    pub fn check_fits_in_packet(&self) -> Result<(), TransactionTooLarge> {
+        if self.fits_in_packet() {
+            return Ok(());
+        }
+        let (largest_component, largest_component_size) = self.largest_component();
+        Err(TransactionTooLarge {
+            size: self.total(),
+            largest_component,
+            largest_component_size,
+        })
+    }
+}
+
+/// A transaction does not fit in a single packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionTooLarge {
+    /// Size of the serialized transaction.
+    pub size: usize,
+    /// The component of the transaction which uses the most bytes.
+    pub largest_component: TransactionSizeComponent,
+    /// Size of `largest_component`.
+    pub largest_component_size: usize,
+}
+
+impl TransactionTooLarge {
+    /// Number of bytes which must be removed for the transaction to fit.
+    pub fn excess(&self) -> usize {
+        self.size.saturating_sub(PACKET_DATA_SIZE)
+    }
+}
+
+impl core::error::Error for TransactionTooLarge {}
+
+impl fmt::Display for TransactionTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "transaction is {} bytes, {} bytes over the {PACKET_DATA_SIZE} byte limit; the \
+             largest component is {} with {} bytes",
+            self.size,
+            self.excess(),
+            self.largest_component,
+            self.largest_component_size,
+        )
+    }
+}
+
+impl Message {
+    /// Compute the size of this message once serialized, without serializing
+    /// it.
+    pub fn serialized_size(&self) -> usize {
+        self.transaction_size().message_size()
+    }
+
+    /// Compute the size of a transaction carrying this message and a
+    /// signature for each required signer, without serializing it.
+    pub fn transaction_size(&self) -> TransactionSize {
+        TransactionSize::new(
+            usize::from(self.header.num_required_signatures),
+            0,
+            &self.account_keys,
+            &self.instructions,
+        )
+    }
+}
+
+#[cfg(not(target_os = "solana"))]
+mod versioned {
+    use {
+        super::*,
+        crate::{v0, VersionedMessage},
+    };
+
+    impl v0::Message {
+        /// Compute the size of this message once serialized with its version
+        /// prefix, without serializing it.
+        pub fn serialized_size(&self) -> usize {
+            self.transaction_size().message_size()
+        }
+
+        /// Compute the size of a transaction carrying this message and a
+        /// signature for each required signer, without serializing it.
+        pub fn transaction_size(&self) -> TransactionSize {
+            let mut size = TransactionSize::new(
+                usize::from(self.header.num_required_signatures),
+                1,
+                &self.account_keys,
+                &self.instructions,
+            );
+            size.address_table_lookups = self.address_table_lookups.iter().fold(
+                short_vec_len_size(self.address_table_lookups.len()),
+                |size, lookup| {
+                    size.saturating_add(ADDRESS_BYTES)
+                        .saturating_add(short_vec_size(lookup.writable_indexes.len()))
+                        .saturating_add(short_vec_size(lookup.readonly_indexes.len()))
+                },
+            );
+            size
+        }
+    }
+
+    impl VersionedMessage {
+        /// Compute the size of this message once serialized, without
+        /// serializing it.
+        pub fn serialized_size(&self) -> usize {
+            self.transaction_size().message_size()
+        }
+
+        /// Compute the size of a transaction carrying this message and a
+        /// signature for each required signer, without serializing it.
+        pub fn transaction_size(&self) -> TransactionSize {
+            match self {
+                Self::Legacy(message) => message.transaction_size(),
+                Self::V0(message) => message.transaction_size(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            v0::{self, MessageAddressTableLookup},
+            AddressLookupTableAccount, VersionedMessage,
+        },
+        solana_hash::Hash,
+        solana_instruction::{AccountMeta, Instruction},
+    };
+
+    static_assertions::const_assert_eq!(PACKET_DATA_SIZE, solana_packet::PACKET_DATA_SIZE);
+
+    fn instruction(num_accounts: usize, data_len: usize) -> Instruction {
+        Instruction {
+            program_id: Address::new_unique(),
+            accounts: (0..num_accounts)
+                .map(|_| AccountMeta::new(Address::new_unique(), false))
+                .collect(),
+            data: vec![7; data_len],
+        }
+    }
+
+    #[test]
+    fn test_short_vec_len_size() {
+        for len in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, usize::from(u16::MAX)] {
+            let encoded = bincode::serialize(&solana_short_vec::ShortU16(len as u16)).unwrap();
+            assert_eq!(short_vec_len_size(len), encoded.len());
+        }
+    }
+
+    #[test]
+    fn test_legacy_message_size() {
+        let payer = Address::new_unique();
+        for (num_accounts, data_len) in [(0, 0), (3, 10), (2, 200), (130, 0)] {
+            let message = Message::new_with_blockhash(
+                &[instruction(num_accounts, data_len), instruction(1, 1)],
+                Some(&payer),
+                &Hash::new_unique(),
+            );
+            let size = message.transaction_size();
+            assert_eq!(message.serialized_size(), message.serialize().len());
+            assert_eq!(size.signatures, 1 + 64);
+            assert_eq!(size.version_prefix, 0);
+            assert_eq!(size.address_table_lookups, 0);
+            assert_eq!(size.instruction_sizes.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_v0_message_size() {
+        let payer = Address::new_unique();
+        let instructions = [instruction(4, 10), instruction(140, 300)];
+        let lookup_table_account = AddressLookupTableAccount {
+            key: Address::new_unique(),
+            addresses: instructions[1]
+                .accounts
+                .iter()
+                .map(|meta| meta.pubkey)
+                .collect(),
+        };
+        let message = v0::Message::try_compile(
+            &payer,
+            &instructions,
+            &[lookup_table_account],
+            Hash::new_unique(),
+        )
+        .unwrap();
+        assert_eq!(message.serialized_size(), message.serialize().len());
+        assert_eq!(message.transaction_size().version_prefix, 1);
+
+        let message = v0::Message {
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: Address::new_unique(),
+                writable_indexes: (0..200).collect(),
+                readonly_indexes: vec![1],
+            }],
+            ..message
+        };
+        assert_eq!(message.serialized_size(), message.serialize().len());
+
+        let versioned_message = VersionedMessage::V0(message.clone());
+        assert_eq!(
+            versioned_message.serialized_size(),
+            versioned_message.serialize().len()
+        );
+        assert_eq!(
+            versioned_message.transaction_size(),
+            message.transaction_size()
+        );
+    }
+
+    #[test]
+    fn test_check_fits_in_packet() {
+        let payer = Address::new_unique();
+        let message = Message::new(&[instruction(2, 100)], Some(&payer));
+        let size = message.transaction_size();
+        assert!(size.fits_in_packet());
+        assert_eq!(size.check_fits_in_packet(), Ok(()));
+
+        let message = Message::new(&[instruction(2, 100), instruction(3, 1200)], Some(&payer));
+        let size = message.transaction_size();
+        assert!(!size.fits_in_packet());
+        let err = size.check_fits_in_packet().unwrap_err();
+        assert_eq!(err.size, size.total());
+        assert_eq!(err.excess(), size.total() - PACKET_DATA_SIZE);
+        assert_eq!(
+            err.largest_component,
+            TransactionSizeComponent::Instruction(1)
+        );
+        assert_eq!(err.largest_component_size, 1 + 1 + 3 + 2 + 1200);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "transaction is {} bytes, {} bytes over the 1232 byte limit; the largest \
+                 component is instruction 1 with 1207 bytes",
+                size.total(),
+                err.excess(),
+            )
+        );
+
+        let message = Message::new(&[instruction(40, 0)], Some(&payer));
+        assert_eq!(
+            message
+                .transaction_size()
+                .check_fits_in_packet()
+                .unwrap_err()
+                .largest_component,
+            TransactionSizeComponent::AccountKeys
+        );
+    }
+
+    #[test]
+    fn test_with_num_signatures() {
+        let message = Message::new(&[instruction(1, 1)], Some(&Address::new_unique()));
+        let size = message.transaction_size().with_num_signatures(0);
+        assert_eq!(size.signatures, 1);
+        assert_eq!(size.total(), 1 + message.serialize().len());
+    }
+}
@@ -0,0 +1,426 @@
+use {
+    super::Message,
+    crate::{
+        compiled_keys::{CompileError, CompiledKeys},
+        transaction_size::{short_vec_len_size, short_vec_size, ADDRESS_BYTES, PACKET_DATA_SIZE},
+        AddressLookupTableAccount,
+    },
+    solana_address::Address,
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+};
+
+/// Above this many useful lookup tables, every subset of tables is no longer
+/// evaluated and a greedy selection is used instead.
+pub const MAX_EXHAUSTIVE_LOOKUP_TABLES: usize = 16;
+
+/// A v0 message compiled with the set of address lookup tables which
+/// minimizes the size of the transaction carrying it.
+///
+/// Returned by [`Message::try_compile_with_optimal_lookup_tables`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OptimizedMessage {
+    /// The compiled message.
+    pub message: Message,
+    /// Serialized size in bytes of a transaction containing `message` and
+    /// all of its required signatures.
+    pub transaction_size: usize,
+}
+
+impl OptimizedMessage {
+    /// Returns true if a transaction containing this message fits in a single
+    /// packet.
+    pub fn fits_in_packet(&self) -> bool {
+        self.transaction_size <= PACKET_DATA_SIZE
+    }
+}
+
+impl Message {
+    /// Create a signable transaction message like [`Message::try_compile`],
+    /// but only use the subset of `address_lookup_table_accounts` which makes
+    /// the resulting transaction as small as possible.
+    ///
+    /// Each lookup table costs more than 32 bytes to reference, so tables
+    /// which would only load a single key, or whose keys are all found in
+    /// other selected tables, are left out. Selected tables are used in the
+    /// order they are given, and a key found in several selected tables is
+    /// loaded from the first of them.
+    ///
+    /// Tables which contain a key at a position beyond what a `u8` lookup
+    /// index can address are never selected.
+    ///
+    /// Every subset of tables is evaluated when at most
+    /// [`MAX_EXHAUSTIVE_LOOKUP_TABLES`] tables contain a key which could be
+    /// loaded from them, otherwise tables are added greedily while they
+    /// reduce the transaction size.
+    pub fn try_compile_with_optimal_lookup_tables(
+        payer: &Address,
+        instructions: &[Instruction],
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+        recent_blockhash: Hash,
+    ) -> Result<OptimizedMessage, CompileError> {
+        let compiled_keys = CompiledKeys::compile(instructions, Some(*payer));
+        let selector = TableSelector::new(&compiled_keys, address_lookup_table_accounts);
+        let selection = if selector.tables.len() <= MAX_EXHAUSTIVE_LOOKUP_TABLES {
+            selector.select_exhaustive()
+        } else {
+            selector.select_greedy()
+        };
+
+        let selected_tables: Vec<AddressLookupTableAccount> = selection
+            .into_iter()
+            .map(|index| selector.tables[index].account.clone())
+            .collect();
+        let message = Self::try_compile(payer, instructions, &selected_tables, recent_blockhash)?;
+        let transaction_size = message.transaction_size().total();
+
+        Ok(OptimizedMessage {
+            message,
+            transaction_size,
+        })
+    }
+}
+
+struct CandidateTable<'a> {
+    account: &'a AddressLookupTableAccount,
+    /// Bitset of the candidate keys found in this table
+    keys: Vec<u64>,
+}
+
+/// Evaluates the part of a message's serialized size which depends on the
+/// selected lookup tables.
+struct TableSelector<'a> {
+    num_keys: usize,
+    /// Bitset of the candidate keys which are writable
+    writable_keys: Vec<u64>,
+    tables: Vec<CandidateTable<'a>>,
+}
+
+impl<'a> TableSelector<'a> {
+    fn new(
+        compiled_keys: &CompiledKeys,
+        address_lookup_table_accounts: &'a [AddressLookupTableAccount],
+    ) -> Self {
+        let candidates: Vec<(&Address, bool)> = compiled_keys.lookup_table_candidates().collect();
+        let num_words = candidates.len().div_ceil(64);
+
+        let mut writable_keys = vec![0; num_words];
+        for (index, (_, is_writable)) in candidates.iter().enumerate() {
+            if *is_writable {
+                set_bit(&mut writable_keys, index);
+            }
+        }
+
+        let tables = address_lookup_table_accounts
+            .iter()
+            .filter_map(|account| {
+                let mut keys = vec![0; num_words];
+                for (index, (key, _)) in candidates.iter().enumerate() {
+                    match account.addresses.iter().position(|address| address == *key) {
+                        Some(position) if position <= usize::from(u8::MAX) => {
+                            set_bit(&mut keys, index);
+                        }
+                        // compiling with this table would fail to index the key
+                        Some(_) => return None,
+                        None => {}
+                    }
+                }
+                keys.iter()
+                    .any(|word| *word != 0)
+                    .then_some(CandidateTable { account, keys })
+            })
+            .collect();
+
+        Self {
+            num_keys: compiled_keys.num_keys(),
+            writable_keys,
+            tables,
+        }
+    }
+
+    /// Evaluate every subset of tables and return the smallest one.
+    fn select_exhaustive(&self) -> Vec<usize> {
+        let mut selection = Vec::with_capacity(self.tables.len());
+        let mut best_selection = Vec::new();
+        let mut best_size = self.variable_size(&best_selection);
+        for mask in 1u32..(1u32 << self.tables.len()) {
+            selection.clear();
+            selection.extend((0..self.tables.len()).filter(|index| mask & (1 << index) != 0));
+            let size = self.variable_size(&selection);
+            if size < best_size {
+                best_size = size;
+                best_selection.clone_from(&selection);
+            }
+        }
+        best_selection
+    }
+
+    /// Repeatedly add the table which reduces the size the most.
+    fn select_greedy(&self) -> Vec<usize> {
+        let mut selection = Vec::new();
+        let mut best_size = self.variable_size(&selection);
+        loop {
+            let best_addition = (0..self.tables.len())
+                .filter(|index| !selection.contains(index))
+                .map(|index| {
+                    let mut candidate_selection = selection.clone();
+                    candidate_selection.push(index);
+                    candidate_selection.sort_unstable();
+                    (
+                        self.variable_size(&candidate_selection),
+                        candidate_selection,
+                    )
+                })
+                .min_by_key(|(size, _)| *size);
+            match best_addition {
+                Some((size, candidate_selection)) if size < best_size => {
+                    best_size = size;
+                    selection = candidate_selection;
+                }
+                _ => return selection,
+            }
+        }
+    }
+
+    /// Size of the static account keys and address table lookups when keys
+    /// are loaded from the tables at the given (ascending) indexes.
+    fn variable_size(&self, selection: &[usize]) -> usize {
+        let mut loaded_keys = vec![0u64; self.writable_keys.len()];
+        let mut num_lookups = 0usize;
+        let mut lookups_size = 0usize;
+        for table in selection.iter().map(|index| &self.tables[*index]) {
+            let mut num_writable = 0usize;
+            let mut num_readonly = 0usize;
+            for ((loaded, table_keys), writable) in loaded_keys
+                .iter_mut()
+                .zip(&table.keys)
+                .zip(&self.writable_keys)
+            {
+                let new_keys = table_keys & !*loaded;
+                *loaded |= new_keys;
+                num_writable =
+                    num_writable.saturating_add((new_keys & writable).count_ones() as usize);
+                num_readonly =
+                    num_readonly.saturating_add((new_keys & !writable).count_ones() as usize);
+            }
+            // tables which don't load any new keys are skipped during compilation
+            if num_writable == 0 && num_readonly == 0 {
+                continue;
+            }
+            num_lookups = num_lookups.saturating_add(1);
+            lookups_size = lookups_size
+                .saturating_add(ADDRESS_BYTES)
+                .saturating_add(short_vec_size(num_writable))
+                .saturating_add(short_vec_size(num_readonly));
+        }
+
+        let num_loaded_keys = loaded_keys
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum();
+        let num_static_keys = self.num_keys.saturating_sub(num_loaded_keys);
+        short_vec_len_size(num_static_keys)
+            .saturating_add(num_static_keys.saturating_mul(ADDRESS_BYTES))
+            .saturating_add(short_vec_len_size(num_lookups))
+            .saturating_add(lookups_size)
+    }
+}
+
+fn set_bit(bitset: &mut [u64], index: usize) {
+    bitset[index / 64] |= 1 << (index % 64);
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {super::*, solana_instruction::AccountMeta};
+
+    fn instruction_with_accounts(accounts: &[Address]) -> Instruction {
+        Instruction {
+            program_id: Address::new_unique(),
+            accounts: accounts
+                .iter()
+                .map(|key| AccountMeta::new(*key, false))
+                .collect(),
+            data: vec![1, 2, 3],
+        }
+    }
+
+    fn table(addresses: &[Address]) -> AddressLookupTableAccount {
+        AddressLookupTableAccount {
+            key: Address::new_unique(),
+            addresses: addresses.to_vec(),
+        }
+    }
+
+    fn assert_size_matches_serialization(optimized: &OptimizedMessage) {
+        let num_signatures = usize::from(optimized.message.header.num_required_signatures);
+        assert_eq!(
+            optimized.transaction_size,
+            1 + num_signatures * 64 + optimized.message.serialize().len()
+        );
+    }
+
+    #[test]
+    fn test_try_compile_with_optimal_lookup_tables_prefers_covering_table() {
+        let payer = Address::new_unique();
+        let keys: Vec<Address> = (0..4).map(|_| Address::new_unique()).collect();
+        let instructions = vec![instruction_with_accounts(&keys)];
+        let tables = vec![
+            table(&keys[..2]),
+            table(&keys[2..]),
+            table(&[Address::new_unique(), keys[3], keys[1], keys[0], keys[2]]),
+        ];
+        let recent_blockhash = Hash::new_unique();
+
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &tables,
+            recent_blockhash,
+        )
+        .unwrap();
+        assert_eq!(
+            optimized.message,
+            Message::try_compile(&payer, &instructions, &tables[2..], recent_blockhash).unwrap()
+        );
+        assert_size_matches_serialization(&optimized);
+        assert!(optimized.fits_in_packet());
+
+        let greedy =
+            Message::try_compile(&payer, &instructions, &tables, recent_blockhash).unwrap();
+        assert_eq!(greedy.address_table_lookups.len(), 2);
+        assert!(optimized.transaction_size < greedy.transaction_size().total());
+    }
+
+    #[test]
+    fn test_try_compile_with_optimal_lookup_tables_skips_single_key_table() {
+        let payer = Address::new_unique();
+        let keys: Vec<Address> = (0..3).map(|_| Address::new_unique()).collect();
+        let instructions = vec![instruction_with_accounts(&keys)];
+        let tables = vec![table(&keys[..1]), table(&keys[1..])];
+        let recent_blockhash = Hash::new_unique();
+
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &tables,
+            recent_blockhash,
+        )
+        .unwrap();
+        assert_eq!(optimized.message.address_table_lookups.len(), 1);
+        assert_eq!(
+            optimized.message.address_table_lookups[0].account_key,
+            tables[1].key
+        );
+        assert!(optimized.message.account_keys.contains(&keys[0]));
+        assert_size_matches_serialization(&optimized);
+    }
+
+    #[test]
+    fn test_try_compile_with_optimal_lookup_tables_without_tables() {
+        let payer = Address::new_unique();
+        let instructions = vec![instruction_with_accounts(&[Address::new_unique()])];
+        let recent_blockhash = Hash::new_unique();
+
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &[],
+            recent_blockhash,
+        )
+        .unwrap();
+        assert_eq!(
+            optimized.message,
+            Message::try_compile(&payer, &instructions, &[], recent_blockhash).unwrap()
+        );
+        assert_size_matches_serialization(&optimized);
+    }
+
+    #[test]
+    fn test_try_compile_with_optimal_lookup_tables_greedy() {
+        let payer = Address::new_unique();
+        let keys: Vec<Address> = (0..2 * (MAX_EXHAUSTIVE_LOOKUP_TABLES + 4))
+            .map(|_| Address::new_unique())
+            .collect();
+        let instructions = vec![instruction_with_accounts(&keys)];
+        let mut tables: Vec<_> = keys.chunks(2).map(table).collect();
+        // redundant with the tables above
+        tables.push(table(&keys[..2]));
+        let recent_blockhash = Hash::new_unique();
+
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &tables,
+            recent_blockhash,
+        )
+        .unwrap();
+        assert_eq!(
+            optimized.message.address_table_lookups.len(),
+            MAX_EXHAUSTIVE_LOOKUP_TABLES + 4
+        );
+        // only the payer and program id are static keys
+        assert_eq!(optimized.message.account_keys.len(), 2);
+        assert_size_matches_serialization(&optimized);
+        assert!(optimized.fits_in_packet());
+    }
+
+    #[test]
+    fn test_try_compile_with_optimal_lookup_tables_skips_unindexable_table() {
+        let payer = Address::new_unique();
+        let keys: Vec<Address> = (0..4).map(|_| Address::new_unique()).collect();
+        let instructions = vec![instruction_with_accounts(&keys)];
+        let mut large_table_addresses: Vec<Address> =
+            (0..256).map(|_| Address::new_unique()).collect();
+        large_table_addresses.extend_from_slice(&keys);
+        let tables = vec![table(&large_table_addresses), table(&keys[..2])];
+        let recent_blockhash = Hash::new_unique();
+
+        assert_eq!(
+            Message::try_compile(&payer, &instructions, &tables, recent_blockhash),
+            Err(CompileError::AddressTableLookupIndexOverflow)
+        );
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &tables,
+            recent_blockhash,
+        )
+        .unwrap();
+        assert_eq!(optimized.message.address_table_lookups.len(), 1);
+        assert_eq!(
+            optimized.message.address_table_lookups[0].account_key,
+            tables[1].key
+        );
+        assert_size_matches_serialization(&optimized);
+    }
+
+    #[test]
+    fn test_try_compile_with_optimal_lookup_tables_too_large() {
+        let payer = Address::new_unique();
+        let keys: Vec<Address> = (0..40).map(|_| Address::new_unique()).collect();
+        let instructions = vec![instruction_with_accounts(&keys)];
+        let recent_blockhash = Hash::new_unique();
+
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &[],
+            recent_blockhash,
+        )
+        .unwrap();
+        assert!(!optimized.fits_in_packet());
+        assert_size_matches_serialization(&optimized);
+
+        let optimized = Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &[table(&keys)],
+            recent_blockhash,
+        )
+        .unwrap();
+        assert!(optimized.fits_in_packet());
+        assert_size_matches_serialization(&optimized);
+    }
+}
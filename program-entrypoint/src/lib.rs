@@ -5,9 +5,12 @@ extern crate alloc;
 use {
     alloc::vec::Vec,
     solana_account_info::AccountInfo,
+    solana_account_view::{AccountView, RuntimeAccount},
+    solana_program_error::ProgramError,
     solana_pubkey::Pubkey,
     std::{
         alloc::Layout,
+        marker::PhantomData,
         mem::{size_of, MaybeUninit},
         ptr::null_mut,
         slice::{from_raw_parts, from_raw_parts_mut},
@@ -17,6 +20,7 @@ use {
 pub use {
     solana_account_info::AccountInfo as __AccountInfo,
     solana_account_info::MAX_PERMITTED_DATA_INCREASE,
+    solana_account_view::AccountView as __AccountView,
     // Re-exporting for custom_panic
     solana_define_syscall::definitions::{sol_log_ as __log, sol_panic_ as __panic},
     solana_program_error::ProgramResult,
@@ -41,6 +45,12 @@ pub const HEAP_LENGTH: usize = 32 * 1024;
 /// Value used to indicate that a serialized account is not a duplicate
 pub const NON_DUP_MARKER: u8 = u8::MAX;
 
+/// Default maximum number of accounts supported by [`entrypoint_view!`].
+pub const MAX_ACCOUNT_VIEWS: usize = 64;
+
+#[cfg(any(test, feature = "dev-context-only-utils"))]
+pub mod test_utils;
+
 /// Declare the program entrypoint and set up global handlers.
 ///
 /// This macro emits the common boilerplate necessary to begin program
@@ -196,6 +206,142 @@ macro_rules! entrypoint_no_alloc {
     };
 }
 
+/// Declare a program entrypoint that passes accounts as [`AccountView`]s.
+///
+/// This is similar to the `entrypoint_no_alloc!` macro, except that the input
+/// accounts are [`AccountView`]s pointing directly into the input buffer
+/// provided by the runtime, which are written into a pre-allocated array on
+/// the stack. Parsing the input performs no heap allocations.
+///
+/// By default, up to [`MAX_ACCOUNT_VIEWS`] input accounts are supported. A
+/// different limit can be given as a second argument; an instruction with more
+/// accounts than the limit aborts the program.
+///
+/// For more information about how the program entrypoint behaves and what it
+/// does, please see the documentation for [`entrypoint!`].
+///
+/// The first argument is the name of a function with this type signature:
+///
+/// ```ignore
+/// fn process_instruction(
+///     program_id: &Pubkey,      // Public key of the account the program was loaded into
+///     accounts: &[AccountView], // All accounts required to process the instruction
+///     instruction_data: &[u8],  // Serialized instruction-specific data
+/// ) -> ProgramResult;
+/// ```
+///
+/// # Examples
+///
+/// ```no_run
+/// #[cfg(not(feature = "no-entrypoint"))]
+/// pub mod entrypoint {
+///
+///     use solana_account_view::AccountView;
+///     use solana_program_entrypoint::entrypoint_view;
+///     use solana_program_entrypoint::ProgramResult;
+///     use solana_pubkey::Pubkey;
+///
+///     entrypoint_view!(process_instruction, 16);
+///
+///     pub fn process_instruction(
+///         program_id: &Pubkey,
+///         accounts: &[AccountView],
+///         instruction_data: &[u8],
+///     ) -> ProgramResult {
+///         Ok(())
+///     }
+///
+/// }
+/// ```
+#[macro_export]
+macro_rules! entrypoint_view {
+    ($process_instruction:ident) => {
+        $crate::entrypoint_view!($process_instruction, { $crate::MAX_ACCOUNT_VIEWS });
+    };
+    ($process_instruction:ident, $max_accounts:expr) => {
+        /// # Safety
+        #[no_mangle]
+        pub unsafe extern "C" fn entrypoint(input: *mut u8) -> u64 {
+            const UNINIT_ACCOUNT_VIEW: core::mem::MaybeUninit<$crate::__AccountView> =
+                core::mem::MaybeUninit::<$crate::__AccountView>::uninit();
+            let mut accounts = [UNINIT_ACCOUNT_VIEW; $max_accounts];
+            let (program_id, num_accounts, instruction_data) =
+                unsafe { $crate::deserialize_account_views(input, &mut accounts) };
+            // Use `slice_assume_init_ref` once it's stabilized
+            let accounts = &*(&accounts[..num_accounts]
+                as *const [core::mem::MaybeUninit<$crate::__AccountView>]
+                as *const [$crate::__AccountView]);
+
+            match $process_instruction(program_id, accounts, instruction_data) {
+                Ok(()) => $crate::SUCCESS,
+                Err(error) => error.into(),
+            }
+        }
+        $crate::custom_heap_default!();
+        $crate::custom_panic_default!();
+    };
+}
+
+/// Declare a program entrypoint that parses its input lazily.
+///
+/// Instead of deserializing every input account up front, the entrypoint
+/// hands an [`InstructionContext`] to the provided function, which reads
+/// accounts from the input buffer only as they are requested. Programs that
+/// receive many accounts but only use a few of them avoid paying to
+/// deserialize the rest.
+///
+/// The input buffer doesn't record where the instruction data starts, so
+/// reading it or the program id still steps over every account that hasn't
+/// been read, see [`InstructionContext`].
+///
+/// For more information about how the program entrypoint behaves and what it
+/// does, please see the documentation for [`entrypoint!`].
+///
+/// The argument is the name of a function with this type signature:
+///
+/// ```ignore
+/// fn process_instruction(
+///     context: InstructionContext, // Lazy cursor over the program input
+/// ) -> ProgramResult;
+/// ```
+///
+/// # Examples
+///
+/// ```no_run
+/// #[cfg(not(feature = "no-entrypoint"))]
+/// pub mod entrypoint {
+///
+///     use solana_program_entrypoint::{lazy_entrypoint, InstructionContext, MaybeAccount};
+///     use solana_program_entrypoint::ProgramResult;
+///
+///     lazy_entrypoint!(process_instruction);
+///
+///     pub fn process_instruction(mut context: InstructionContext) -> ProgramResult {
+///         let MaybeAccount::Account(payer) = context.next_account()? else {
+///             unreachable!("the first account cannot be a duplicate");
+///         };
+///         let instruction_data = context.instruction_data();
+///         Ok(())
+///     }
+///
+/// }
+/// ```
+#[macro_export]
+macro_rules! lazy_entrypoint {
+    ($process_instruction:ident) => {
+        /// # Safety
+        #[no_mangle]
+        pub unsafe extern "C" fn entrypoint(input: *mut u8) -> u64 {
+            match $process_instruction(unsafe { $crate::InstructionContext::new(input) }) {
+                Ok(()) => $crate::SUCCESS,
+                Err(error) => error.into(),
+            }
+        }
+        $crate::custom_heap_default!();
+        $crate::custom_panic_default!();
+    };
+}
+
 /// Define the default global allocator.
 ///
 /// The default global allocator is enabled only if the calling crate has not
@@ -563,9 +709,371 @@ pub unsafe fn deserialize_into<'a>(
     (program_id, num_accounts, instruction_data)
 }
 
+#[allow(clippy::arithmetic_side_effects)]
+#[inline(always)]
+unsafe fn deserialize_account_view(input: *mut u8, mut offset: usize) -> (AccountView, usize) {
+    // The duplicate marker doubles as the borrow state of the view, starting
+    // out as `NOT_BORROWED`
+    #[allow(clippy::cast_ptr_alignment)]
+    let raw = input.add(offset) as *mut RuntimeAccount;
+    let data_len = (*raw).data_len as usize;
+
+    // rent epoch is not deserialized, so skip it
+    offset +=
+        size_of::<RuntimeAccount>() + data_len + MAX_PERMITTED_DATA_INCREASE + size_of::<u64>();
+    offset += (offset as *const u8).align_offset(BPF_ALIGN_OF_U128); // padding
+
+    (AccountView::new_unchecked(raw), offset)
+}
+
+/// Deserialize the input arguments into [`AccountView`]s
+///
+/// Differs from `deserialize_into` by writing views into the input buffer
+/// rather than account infos, so each account is represented by a single
+/// pointer to its [`RuntimeAccount`] header. Duplicate accounts share the view
+/// of the account they duplicate.
+///
+/// Panics if the input slice is not large enough.
+///
+/// The integer arithmetic in this method is safe when called on a buffer that was
+/// serialized by runtime. Use with buffers serialized otherwise is unsupported and
+/// done at one's own risk.
+///
+/// # Safety
+#[allow(clippy::arithmetic_side_effects)]
+pub unsafe fn deserialize_account_views<'a>(
+    input: *mut u8,
+    accounts: &mut [MaybeUninit<AccountView>],
+) -> (&'a Pubkey, usize, &'a [u8]) {
+    let mut offset: usize = 0;
+
+    // Number of accounts present
+
+    #[allow(clippy::cast_ptr_alignment)]
+    let num_accounts = *(input.add(offset) as *const u64) as usize;
+    offset += size_of::<u64>();
+
+    if num_accounts > accounts.len() {
+        panic!(
+            "{} accounts provided, but only {} are supported",
+            num_accounts,
+            accounts.len()
+        );
+    }
+
+    // Account Views
+
+    for i in 0..num_accounts {
+        let dup_info = *(input.add(offset) as *const u8);
+        if dup_info == NON_DUP_MARKER {
+            let (account_view, new_offset) = deserialize_account_view(input, offset);
+            offset = new_offset;
+            accounts[i].write(account_view);
+        } else {
+            offset += size_of::<u64>(); // marker and padding
+
+            // Duplicate account, clone the original
+            accounts[i].write(accounts[dup_info as usize].assume_init_ref().clone());
+        }
+    }
+
+    // Instruction data
+
+    let (instruction_data, new_offset) = deserialize_instruction_data(input, offset);
+    offset = new_offset;
+
+    // Program Id
+
+    let program_id: &Pubkey = &*(input.add(offset) as *const Pubkey);
+
+    (program_id, num_accounts, instruction_data)
+}
+
+/// An account read by an [`InstructionContext`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MaybeAccount {
+    /// The first occurrence of an account in the input.
+    Account(AccountView),
+    /// A duplicate of the account at the given index.
+    Duplicated(u8),
+}
+
+/// A lazy cursor over the serialized program input.
+///
+/// Accounts are read one at a time with [`next_account`], so a program only
+/// pays for the accounts it uses.
+///
+/// Jumping straight to the instruction data and program id is not possible:
+/// the loader serializes them after the data of every account without
+/// recording their offset, and the length of each account's data is only
+/// known once the account is reached. [`instruction_data`] and
+/// [`program_id`] therefore step over the accounts that haven't been read,
+/// reading only the length of each account's data, which is free when every
+/// account has already been read. Programs that need the instruction data to
+/// decide which accounts to read should read all of their accounts first.
+///
+/// Duplicate accounts are returned as [`MaybeAccount::Duplicated`] with the
+/// index of the original account, which the program resolves itself, as the
+/// cursor keeps no record of the accounts it has returned.
+///
+/// [`next_account`]: InstructionContext::next_account
+/// [`instruction_data`]: InstructionContext::instruction_data
+/// [`program_id`]: InstructionContext::program_id
+#[derive(Debug)]
+pub struct InstructionContext<'a> {
+    input: *mut u8,
+    offset: usize,
+    remaining: u64,
+    _input: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> InstructionContext<'a> {
+    /// Creates a cursor over the input buffer provided by the runtime.
+    ///
+    /// # Safety
+    ///
+    /// `input` must point to a buffer serialized by the runtime for the
+    /// latest BPF loader, which must outlive `'a`.
+    #[inline(always)]
+    pub unsafe fn new(input: *mut u8) -> Self {
+        #[allow(clippy::cast_ptr_alignment)]
+        let remaining = *(input as *const u64);
+        Self {
+            input,
+            offset: size_of::<u64>(),
+            remaining,
+            _input: PhantomData,
+        }
+    }
+
+    /// The number of accounts that have not been read yet.
+    #[inline(always)]
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Reads the next account.
+    ///
+    /// Returns `NotEnoughAccountKeys` if all accounts have been read.
+    #[inline(always)]
+    pub fn next_account(&mut self) -> Result<MaybeAccount, ProgramError> {
+        if self.remaining == 0 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        // SAFETY: there is at least one more account in the input.
+        Ok(unsafe { self.next_account_unchecked() })
+    }
+
+    /// Reads the next account without checking that one remains.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that [`remaining`](Self::remaining) is nonzero.
+    #[allow(clippy::arithmetic_side_effects)]
+    #[inline(always)]
+    pub unsafe fn next_account_unchecked(&mut self) -> MaybeAccount {
+        self.remaining -= 1;
+        let dup_info = *self.input.add(self.offset);
+        if dup_info == NON_DUP_MARKER {
+            let (account_view, new_offset) = deserialize_account_view(self.input, self.offset);
+            self.offset = new_offset;
+            MaybeAccount::Account(account_view)
+        } else {
+            self.offset += size_of::<u64>(); // marker and padding
+            MaybeAccount::Duplicated(dup_info)
+        }
+    }
+
+    /// Skips over the accounts that have not been read yet.
+    #[allow(clippy::arithmetic_side_effects)]
+    #[inline(always)]
+    pub fn skip_remaining_accounts(&mut self) {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            // SAFETY: there is at least one more account in the input.
+            unsafe {
+                if *self.input.add(self.offset) == NON_DUP_MARKER {
+                    #[allow(clippy::cast_ptr_alignment)]
+                    let data_len =
+                        (*(self.input.add(self.offset) as *const RuntimeAccount)).data_len as usize;
+                    self.offset += size_of::<RuntimeAccount>()
+                        + data_len
+                        + MAX_PERMITTED_DATA_INCREASE
+                        + size_of::<u64>();
+                    self.offset += (self.offset as *const u8).align_offset(BPF_ALIGN_OF_U128);
+                } else {
+                    self.offset += size_of::<u64>(); // marker and padding
+                }
+            }
+        }
+    }
+
+    /// The instruction data, skipping any accounts that have not been read.
+    #[inline(always)]
+    pub fn instruction_data(&mut self) -> &'a [u8] {
+        self.skip_remaining_accounts();
+        // SAFETY: all accounts have been read, so the offset points to the
+        // instruction data.
+        unsafe { deserialize_instruction_data(self.input, self.offset).0 }
+    }
+
+    /// The id of the executing program, skipping any accounts that have not
+    /// been read.
+    #[inline(always)]
+    pub fn program_id(&mut self) -> &'a Pubkey {
+        let instruction_data = self.instruction_data();
+        // SAFETY: the program id directly follows the instruction data.
+        unsafe { &*(instruction_data.as_ptr_range().end as *const Pubkey) }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
 mod test {
-    use {super::*, std::alloc::GlobalAlloc};
+    use {
+        super::*,
+        crate::test_utils::{serialize_input, SerializedAccount},
+        std::alloc::GlobalAlloc,
+    };
+
+    fn test_input(program_id: &Pubkey) -> Vec<u64> {
+        serialize_input(
+            &[
+                SerializedAccount::Account {
+                    key: Pubkey::new_from_array([1; 32]),
+                    owner: Pubkey::new_from_array([2; 32]),
+                    lamports: 42,
+                    data: &[1, 2, 3],
+                    is_signer: true,
+                    is_writable: true,
+                },
+                SerializedAccount::Duplicate(0),
+                SerializedAccount::Account {
+                    key: Pubkey::new_from_array([3; 32]),
+                    owner: Pubkey::new_from_array([4; 32]),
+                    lamports: 7,
+                    data: &[],
+                    is_signer: false,
+                    is_writable: false,
+                },
+            ],
+            &[9, 8, 7],
+            program_id,
+        )
+    }
+
+    #[test]
+    fn test_deserialize_account_views() {
+        let program_id = Pubkey::new_from_array([5; 32]);
+        let mut input = test_input(&program_id);
+
+        const UNINIT_ACCOUNT_VIEW: MaybeUninit<AccountView> = MaybeUninit::uninit();
+        let mut accounts = [UNINIT_ACCOUNT_VIEW; 3];
+        let (deserialized_program_id, num_accounts, instruction_data) =
+            unsafe { deserialize_account_views(input.as_mut_ptr() as *mut u8, &mut accounts) };
+        assert_eq!(deserialized_program_id, &program_id);
+        assert_eq!(num_accounts, 3);
+        assert_eq!(instruction_data, &[9, 8, 7]);
+
+        let accounts = unsafe {
+            &*(&accounts[..] as *const [MaybeUninit<AccountView>] as *const [AccountView])
+        };
+        assert_eq!(accounts[0].address(), &Pubkey::new_from_array([1; 32]));
+        assert!(accounts[0].owned_by(&Pubkey::new_from_array([2; 32])));
+        assert_eq!(accounts[0].lamports(), 42);
+        assert!(accounts[0].is_signer());
+        assert!(accounts[0].is_writable());
+        assert_eq!(&*accounts[0].try_borrow().unwrap(), &[1, 2, 3]);
+        assert_eq!(accounts[2].address(), &Pubkey::new_from_array([3; 32]));
+        assert_eq!(accounts[2].lamports(), 7);
+        assert!(!accounts[2].is_signer());
+        assert!(!accounts[2].is_writable());
+        assert!(accounts[2].is_data_empty());
+
+        // the duplicate shares the account, including its borrow state
+        assert_eq!(accounts[1], accounts[0]);
+        accounts[1].set_lamports(43);
+        assert_eq!(accounts[0].lamports(), 43);
+        let data = accounts[0].try_borrow_mut().unwrap();
+        assert_eq!(
+            accounts[1].try_borrow().unwrap_err(),
+            ProgramError::AccountBorrowFailed
+        );
+        drop(data);
+        assert!(accounts[1].try_borrow().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "3 accounts provided, but only 2 are supported")]
+    fn test_deserialize_account_views_too_many_accounts() {
+        let mut input = test_input(&Pubkey::new_from_array([5; 32]));
+        const UNINIT_ACCOUNT_VIEW: MaybeUninit<AccountView> = MaybeUninit::uninit();
+        let mut accounts = [UNINIT_ACCOUNT_VIEW; 2];
+        unsafe { deserialize_account_views(input.as_mut_ptr() as *mut u8, &mut accounts) };
+    }
+
+    #[test]
+    fn test_instruction_context() {
+        let program_id = Pubkey::new_from_array([5; 32]);
+        let mut input = test_input(&program_id);
+
+        let mut context = unsafe { InstructionContext::new(input.as_mut_ptr() as *mut u8) };
+        assert_eq!(context.remaining(), 3);
+        let MaybeAccount::Account(first) = context.next_account().unwrap() else {
+            panic!("first account should not be a duplicate");
+        };
+        assert_eq!(first.address(), &Pubkey::new_from_array([1; 32]));
+        assert_eq!(first.lamports(), 42);
+        assert_eq!(context.next_account().unwrap(), MaybeAccount::Duplicated(0));
+        assert_eq!(context.remaining(), 1);
+
+        // the remaining account is skipped
+        assert_eq!(context.instruction_data(), &[9, 8, 7]);
+        assert_eq!(context.remaining(), 0);
+        assert_eq!(context.program_id(), &program_id);
+        assert_eq!(
+            context.next_account(),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // reading every account yields the same instruction data
+        let mut context = unsafe { InstructionContext::new(input.as_mut_ptr() as *mut u8) };
+        let accounts: Vec<MaybeAccount> = (0..3).map(|_| context.next_account().unwrap()).collect();
+        let MaybeAccount::Account(last) = &accounts[2] else {
+            panic!("last account should not be a duplicate");
+        };
+        assert_eq!(last.address(), &Pubkey::new_from_array([3; 32]));
+        assert_eq!(context.program_id(), &program_id);
+        assert_eq!(context.instruction_data(), &[9, 8, 7]);
+    }
+
+    // the macro emits cfgs for features of the program crate
+    #[allow(unexpected_cfgs)]
+    mod view_program {
+        use super::*;
+
+        entrypoint_view!(process_instruction, 4);
+
+        fn process_instruction(
+            program_id: &Pubkey,
+            accounts: &[AccountView],
+            instruction_data: &[u8],
+        ) -> ProgramResult {
+            if accounts.len() != 3 || program_id != &Pubkey::new_from_array([5; 32]) {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let lamports = u64::from(instruction_data[0]);
+            accounts[1].set_lamports(lamports);
+            Err(ProgramError::Custom(accounts[0].lamports() as u32))
+        }
+
+        #[test]
+        fn test_entrypoint_view() {
+            let mut input = test_input(&Pubkey::new_from_array([5; 32]));
+            let result = unsafe { entrypoint(input.as_mut_ptr() as *mut u8) };
+            assert_eq!(result, u64::from(ProgramError::Custom(9)));
+        }
+    }
 
     #[test]
     fn test_bump_allocator() {
@@ -0,0 +1,72 @@
+//! Input buffers in the layout used by the latest loader, for testing
+//! entrypoints without a runtime.
+
+use {
+    crate::{BPF_ALIGN_OF_U128, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER},
+    alloc::vec::Vec,
+    solana_pubkey::Pubkey,
+    std::mem::size_of,
+};
+
+/// An account in a serialized input buffer.
+pub enum SerializedAccount<'a> {
+    Account {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: &'a [u8],
+        is_signer: bool,
+        is_writable: bool,
+    },
+    /// A duplicate of the account at the given index.
+    Duplicate(u8),
+}
+
+/// Serializes the input buffer in the layout used by the latest loader,
+/// backed by `u64`s so that it is 8-byte aligned.
+#[allow(clippy::arithmetic_side_effects)]
+pub fn serialize_input(
+    accounts: &[SerializedAccount],
+    instruction_data: &[u8],
+    program_id: &Pubkey,
+) -> Vec<u64> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+    for account in accounts {
+        match account {
+            SerializedAccount::Account {
+                key,
+                owner,
+                lamports,
+                data,
+                is_signer,
+                is_writable,
+            } => {
+                bytes.extend_from_slice(&[NON_DUP_MARKER, *is_signer as u8, *is_writable as u8]);
+                bytes.extend_from_slice(&[0; 5]); // executable and padding
+                bytes.extend_from_slice(key.as_ref());
+                bytes.extend_from_slice(owner.as_ref());
+                bytes.extend_from_slice(&lamports.to_le_bytes());
+                bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                bytes.extend_from_slice(data);
+                bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+                bytes.resize(bytes.len().next_multiple_of(BPF_ALIGN_OF_U128), 0);
+                bytes.extend_from_slice(&u64::MAX.to_le_bytes()); // rent epoch
+            }
+            SerializedAccount::Duplicate(index) => {
+                bytes.extend_from_slice(&[*index, 0, 0, 0, 0, 0, 0, 0]);
+            }
+        }
+    }
+    bytes.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(instruction_data);
+    bytes.extend_from_slice(program_id.as_ref());
+
+    let mut input = vec![0u64; bytes.len().div_ceil(size_of::<u64>())];
+    for (word, chunk) in input.iter_mut().zip(bytes.chunks(size_of::<u64>())) {
+        let mut word_bytes = [0; size_of::<u64>()];
+        word_bytes[..chunk.len()].copy_from_slice(chunk);
+        *word = u64::from_ne_bytes(word_bytes);
+    }
+    input
+}
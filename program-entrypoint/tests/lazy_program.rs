@@ -0,0 +1,65 @@
+//! A program declared with `lazy_entrypoint!`, in its own test binary since
+//! the entrypoint symbol may only be defined once.
+
+// the macro emits cfgs for features of the program crate
+#![allow(unexpected_cfgs)]
+
+use {
+    solana_program_entrypoint::{
+        lazy_entrypoint,
+        test_utils::{self, SerializedAccount},
+        InstructionContext, MaybeAccount, ProgramResult,
+    },
+    solana_program_error::ProgramError,
+    solana_pubkey::Pubkey,
+};
+
+lazy_entrypoint!(process_instruction);
+
+const PROGRAM_ID: Pubkey = Pubkey::new_from_array([5; 32]);
+
+fn process_instruction(mut context: InstructionContext) -> ProgramResult {
+    let MaybeAccount::Account(first) = context.next_account()? else {
+        return Err(ProgramError::InvalidArgument);
+    };
+    // the remaining accounts are skipped
+    let instruction_data = context.instruction_data();
+    if context.program_id() != &PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    first.set_lamports(u64::from(instruction_data[1]));
+    Err(ProgramError::Custom(first.lamports() as u32))
+}
+
+/// An input buffer with an account holding `data`, a duplicate of it and an
+/// empty account.
+fn serialize_input(data: &[u8], instruction_data: &[u8], program_id: &Pubkey) -> Vec<u64> {
+    let account = |key, data| SerializedAccount::Account {
+        key: Pubkey::new_from_array(key),
+        owner: Pubkey::new_from_array([2; 32]),
+        lamports: 42,
+        data,
+        is_signer: true,
+        is_writable: true,
+    };
+    test_utils::serialize_input(
+        &[
+            account([1; 32], data),
+            SerializedAccount::Duplicate(0),
+            account([3; 32], &[]),
+        ],
+        instruction_data,
+        program_id,
+    )
+}
+
+#[test]
+fn test_lazy_entrypoint() {
+    let mut input = serialize_input(&[1, 2, 3, 4, 5], &[9, 8, 7], &PROGRAM_ID);
+    let result = unsafe { entrypoint(input.as_mut_ptr() as *mut u8) };
+    assert_eq!(result, u64::from(ProgramError::Custom(8)));
+
+    let mut input = serialize_input(&[], &[9, 8, 7], &Pubkey::new_from_array([6; 32]));
+    let result = unsafe { entrypoint(input.as_mut_ptr() as *mut u8) };
+    assert_eq!(result, u64::from(ProgramError::IncorrectProgramId));
+}
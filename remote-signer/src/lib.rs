@@ -0,0 +1,376 @@
+//! A [`Signer`] whose key lives in a separate signing process.
+//!
+//! [`RemoteSigner`] forwards signing requests over a Unix socket or the
+//! standard input and output of a subprocess, so that the key can be kept by
+//! a process with privileges the client doesn't have. The requests and
+//! responses are described in [`protocol`]; [`serve`] implements the server
+//! side for any [`Signer`].
+//!
+//! The public key is requested once when connecting. Every signature
+//! returned afterwards is verified against it, so a `RemoteSigner` behaves
+//! like the keypair it stands for or fails with
+//! [`SignerError::Protocol`].
+#![cfg_attr(docsrs, feature(doc_cfg))]
+pub mod protocol;
+mod server;
+
+pub use server::serve;
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+use {
+    protocol::{
+        decode_error, read_frame, write_frame, MAX_FRAME_LEN, REQUEST_PUBKEY, REQUEST_SIGN_MESSAGE,
+        STATUS_OK,
+    },
+    solana_pubkey::Pubkey,
+    solana_signature::Signature,
+    solana_signer::{Signer, SignerError},
+    std::{
+        fmt,
+        io::{self, Read, Write},
+        process::{Child, Command, Stdio},
+        sync::{Mutex, PoisonError},
+    },
+};
+
+struct Connection {
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+    /// Set once an I/O error leaves the stream in an unknown state.
+    closed: bool,
+}
+
+impl Connection {
+    fn request(&mut self, tag: u8, payload: &[u8]) -> Result<Vec<u8>, SignerError> {
+        if self.closed {
+            return Err(SignerError::Connection(
+                "connection to the remote signer is closed".to_string(),
+            ));
+        }
+        // rejected before anything is written, so the connection stays usable
+        if payload.len().saturating_add(1) > MAX_FRAME_LEN {
+            return Err(SignerError::InvalidInput(
+                "request exceeds the maximum frame length".to_string(),
+            ));
+        }
+        let response = write_frame(&mut self.writer, tag, payload)
+            .and_then(|()| read_frame(&mut self.reader))
+            .and_then(|response| {
+                response.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "remote signer closed the connection",
+                    )
+                })
+            });
+        let response = response.map_err(|err| {
+            self.closed = true;
+            SignerError::Connection(err.to_string())
+        })?;
+        match response.split_first() {
+            Some((&STATUS_OK, payload)) => Ok(payload.to_vec()),
+            Some((&status, reason)) => Err(decode_error(status, reason)),
+            None => Err(SignerError::Protocol("empty response".to_string())),
+        }
+    }
+}
+
+/// A [`Signer`] that forwards requests to a signing process.
+pub struct RemoteSigner {
+    pubkey: Pubkey,
+    connection: Mutex<Connection>,
+    child: Option<Child>,
+    interactive: bool,
+}
+
+impl RemoteSigner {
+    /// Connect to a signing process that reads requests from `reader` and
+    /// writes responses to `writer`, and request its public key.
+    pub fn new<R, W>(reader: R, writer: W) -> Result<Self, SignerError>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let mut connection = Connection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            closed: false,
+        };
+        let pubkey = connection.request(REQUEST_PUBKEY, &[])?;
+        let pubkey = <[u8; 32]>::try_from(pubkey.as_slice())
+            .map(Pubkey::from)
+            .map_err(|_| {
+                SignerError::Protocol(format!("invalid public key length {}", pubkey.len()))
+            })?;
+        Ok(Self {
+            pubkey,
+            connection: Mutex::new(connection),
+            child: None,
+            interactive: false,
+        })
+    }
+
+    /// Connect to a signing process listening on the Unix socket at `path`.
+    #[cfg(unix)]
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, SignerError> {
+        let stream =
+            UnixStream::connect(path).map_err(|err| SignerError::Connection(err.to_string()))?;
+        let reader = stream
+            .try_clone()
+            .map_err(|err| SignerError::Connection(err.to_string()))?;
+        Self::new(reader, stream)
+    }
+
+    /// Spawn `command` as a signing process serving requests on its standard
+    /// input and output.
+    ///
+    /// The process is expected to exit once its standard input is closed,
+    /// which happens when the `RemoteSigner` is dropped.
+    pub fn spawn(command: &mut Command) -> Result<Self, SignerError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| SignerError::Connection(err.to_string()))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(SignerError::Connection(
+                "signing process has no standard input or output".to_string(),
+            ));
+        };
+        // Dropping `child` on failure doesn't wait for it, but the process
+        // sees its standard input closed and exits.
+        let mut signer = Self::new(stdout, stdin)?;
+        signer.child = Some(child);
+        Ok(signer)
+    }
+
+    /// Set whether the signing process interacts with a user, e.g. to confirm
+    /// each signature. Remote signers are not interactive by default.
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let signature = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .request(REQUEST_SIGN_MESSAGE, message)?;
+        let signature = Signature::try_from(signature.as_slice()).map_err(|_| {
+            SignerError::Protocol(format!("invalid signature length {}", signature.len()))
+        })?;
+        if signature.verify(self.pubkey.as_ref(), message) {
+            Ok(signature)
+        } else {
+            Err(SignerError::Protocol(
+                "remote signature does not verify".to_string(),
+            ))
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+}
+
+impl fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("pubkey", &self.pubkey)
+            .field("interactive", &self.interactive)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for RemoteSigner {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            // Close the standard input of the process so that it exits.
+            self.connection
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .writer = Box::new(io::sink());
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use {
+        super::*,
+        solana_keypair::Keypair,
+        std::{
+            io::Cursor,
+            os::unix::net::UnixListener,
+            thread::{self, JoinHandle},
+        },
+    };
+
+    fn stand_in<S: Signer + Send + 'static>(signer: S) -> (RemoteSigner, JoinHandle<()>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            serve(&signer, server.try_clone().unwrap(), server).unwrap();
+        });
+        let remote_signer = RemoteSigner::new(client.try_clone().unwrap(), client).unwrap();
+        (remote_signer, handle)
+    }
+
+    /// Reports the public key of one keypair but signs with another.
+    struct Impostor(Keypair, Keypair);
+
+    impl Signer for Impostor {
+        fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+            self.0.try_pubkey()
+        }
+
+        fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+            if message.is_empty() {
+                Err(SignerError::UserCancel("empty message".to_string()))
+            } else {
+                self.1.try_sign_message(message)
+            }
+        }
+
+        fn is_interactive(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let expected = keypair.sign_message(b"message");
+        let (remote_signer, handle) = stand_in(keypair);
+
+        assert_eq!(remote_signer.pubkey(), pubkey);
+        assert!(!remote_signer.is_interactive());
+        assert_eq!(remote_signer.try_sign_message(b"message"), Ok(expected));
+        assert_eq!(remote_signer.try_sign_message(b"message"), Ok(expected));
+        assert!(remote_signer
+            .try_sign_message(b"other")
+            .unwrap()
+            .verify(pubkey.as_ref(), b"other"));
+        assert!(remote_signer.with_interactive(true).is_interactive());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_remote_signer_oversize_request() {
+        let keypair = Keypair::new();
+        let expected = keypair.sign_message(b"message");
+        let (remote_signer, handle) = stand_in(keypair);
+
+        assert!(matches!(
+            remote_signer.try_sign_message(&vec![0; MAX_FRAME_LEN]),
+            Err(SignerError::InvalidInput(_))
+        ));
+        // the connection is still usable
+        assert_eq!(remote_signer.try_sign_message(b"message"), Ok(expected));
+        drop(remote_signer);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_remote_signer_errors() {
+        let (remote_signer, handle) = stand_in(Impostor(Keypair::new(), Keypair::new()));
+        assert_eq!(
+            remote_signer.try_sign_message(&[]),
+            Err(SignerError::UserCancel("empty message".to_string()))
+        );
+        assert!(matches!(
+            remote_signer.try_sign_message(b"message"),
+            Err(SignerError::Protocol(_))
+        ));
+        drop(remote_signer);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_remote_signer_malformed_responses() {
+        assert!(matches!(
+            RemoteSigner::new(io::empty(), io::sink()),
+            Err(SignerError::Connection(_))
+        ));
+
+        let mut responses = vec![];
+        write_frame(&mut responses, STATUS_OK, &[1; 31]).unwrap();
+        assert!(matches!(
+            RemoteSigner::new(Cursor::new(responses), io::sink()),
+            Err(SignerError::Protocol(_))
+        ));
+
+        let mut responses = vec![];
+        write_frame(&mut responses, STATUS_OK, &[1; 32]).unwrap();
+        write_frame(&mut responses, STATUS_OK, &[1; 32]).unwrap();
+        let remote_signer = RemoteSigner::new(Cursor::new(responses), io::sink()).unwrap();
+        assert_eq!(remote_signer.pubkey(), Pubkey::from([1; 32]));
+        assert!(matches!(
+            remote_signer.try_sign_message(b"message"),
+            Err(SignerError::Protocol(_))
+        ));
+        // the stream ended, so the connection is closed for good
+        for _ in 0..2 {
+            assert!(matches!(
+                remote_signer.try_sign_message(b"message"),
+                Err(SignerError::Connection(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_connect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signer.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(&keypair, stream.try_clone().unwrap(), stream).unwrap();
+        });
+
+        let remote_signer = RemoteSigner::connect(&path).unwrap();
+        assert_eq!(remote_signer.pubkey(), pubkey);
+        assert!(remote_signer
+            .sign_message(b"message")
+            .verify(pubkey.as_ref(), b"message"));
+        drop(remote_signer);
+        handle.join().unwrap();
+
+        assert!(matches!(
+            RemoteSigner::connect(dir.path().join("missing.sock")),
+            Err(SignerError::Connection(_))
+        ));
+    }
+
+    #[test]
+    fn test_spawn() {
+        // a process answering the public key request, then waiting for its
+        // standard input to close
+        let remote_signer = RemoteSigner::spawn(Command::new("sh").args([
+            "-c",
+            "printf '\\041\\000\\000\\000\\000'; head -c 32 /dev/zero | tr '\\000' '\\007'; cat > /dev/null",
+        ]))
+        .unwrap();
+        assert_eq!(remote_signer.pubkey(), Pubkey::from([7; 32]));
+        drop(remote_signer);
+
+        assert!(matches!(
+            RemoteSigner::spawn(&mut Command::new("true")),
+            Err(SignerError::Connection(_))
+        ));
+        assert!(matches!(
+            RemoteSigner::spawn(&mut Command::new("/nonexistent/signer")),
+            Err(SignerError::Connection(_))
+        ));
+    }
+}
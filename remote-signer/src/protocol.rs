@@ -0,0 +1,182 @@
+//! Wire format shared by [`RemoteSigner`](crate::RemoteSigner) and
+//! [`serve`](crate::serve).
+//!
+//! Every request and response is a frame: a little-endian `u32` body length
+//! followed by the body. Bodies longer than [`MAX_FRAME_LEN`] are rejected.
+//!
+//! A request body starts with a request tag:
+//!
+//! | tag | request                | rest of the body     |
+//! |-----|------------------------|----------------------|
+//! | `0` | [`REQUEST_PUBKEY`]     | empty                |
+//! | `1` | [`REQUEST_SIGN_MESSAGE`] | the message to sign |
+//!
+//! A response body starts with a status byte:
+//!
+//! | status | response                   | rest of the body                                 |
+//! |--------|----------------------------|--------------------------------------------------|
+//! | `0`    | [`STATUS_OK`]              | the 32 byte public key or the 64 byte signature  |
+//! | `1`    | [`STATUS_USER_CANCEL`]     | UTF-8 reason the request was refused             |
+//! | `2`    | [`STATUS_NO_DEVICE_FOUND`] | empty                                            |
+//! | `3`    | [`STATUS_INVALID_INPUT`]   | UTF-8 description of the problem                 |
+//! | `4`    | [`STATUS_ERROR`]           | UTF-8 description of any other failure           |
+//!
+//! The client sends one request at a time and waits for its response. The
+//! server serves requests until the client closes the connection.
+
+use {
+    solana_signer::SignerError,
+    std::io::{self, Read, Write},
+};
+
+/// Maximum length of a frame body.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Request the public key of the signer.
+pub const REQUEST_PUBKEY: u8 = 0;
+/// Request a signature of the message in the rest of the body.
+pub const REQUEST_SIGN_MESSAGE: u8 = 1;
+
+/// The request succeeded.
+pub const STATUS_OK: u8 = 0;
+/// The request was refused, e.g. by the operator of the signer.
+pub const STATUS_USER_CANCEL: u8 = 1;
+/// The signer has no key available.
+pub const STATUS_NO_DEVICE_FOUND: u8 = 2;
+/// The request was malformed or can't be signed.
+pub const STATUS_INVALID_INPUT: u8 = 3;
+/// The request failed for any other reason.
+pub const STATUS_ERROR: u8 = 4;
+
+/// Read a frame body, or `None` if the stream ends before the frame starts.
+pub(crate) fn read_frame<R: Read + ?Sized>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => filled = filled.saturating_add(read),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let len = usize::try_from(u32::from_le_bytes(len)).unwrap_or(usize::MAX);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame exceeds the maximum length",
+        ));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Write a frame with a body made of `tag` followed by `payload`.
+pub(crate) fn write_frame<W: Write + ?Sized>(
+    writer: &mut W,
+    tag: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let len = payload
+        .len()
+        .checked_add(1)
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .and_then(|len| u32::try_from(len).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame exceeds the maximum length",
+            )
+        })?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(&[tag])?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Status and payload of the response reporting `err`.
+pub(crate) fn encode_error(err: &SignerError) -> (u8, String) {
+    match err {
+        SignerError::UserCancel(reason) => (STATUS_USER_CANCEL, reason.clone()),
+        SignerError::NoDeviceFound => (STATUS_NO_DEVICE_FOUND, String::new()),
+        SignerError::InvalidInput(reason) => (STATUS_INVALID_INPUT, reason.clone()),
+        SignerError::Custom(reason) => (STATUS_ERROR, reason.clone()),
+        err => (STATUS_ERROR, err.to_string()),
+    }
+}
+
+/// The error reported by a response with a status other than [`STATUS_OK`].
+pub(crate) fn decode_error(status: u8, payload: &[u8]) -> SignerError {
+    let reason = String::from_utf8_lossy(payload).into_owned();
+    match status {
+        STATUS_USER_CANCEL => SignerError::UserCancel(reason),
+        STATUS_NO_DEVICE_FOUND => SignerError::NoDeviceFound,
+        STATUS_INVALID_INPUT => SignerError::InvalidInput(reason),
+        STATUS_ERROR => SignerError::Custom(reason),
+        status => SignerError::Protocol(format!("unknown response status {status}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Cursor};
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let mut buffer = vec![];
+        write_frame(&mut buffer, REQUEST_SIGN_MESSAGE, b"hello").unwrap();
+        write_frame(&mut buffer, REQUEST_PUBKEY, &[]).unwrap();
+        assert_eq!(&buffer[..4], &6u32.to_le_bytes());
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(
+            read_frame(&mut reader).unwrap(),
+            Some(b"\x01hello".to_vec())
+        );
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(vec![REQUEST_PUBKEY]));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_frame_errors() {
+        let truncated_len = [1u8, 0];
+        assert_eq!(
+            read_frame(&mut &truncated_len[..]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let truncated_body = [4u8, 0, 0, 0, 1];
+        assert_eq!(
+            read_frame(&mut &truncated_body[..]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let too_long = u32::MAX.to_le_bytes();
+        assert_eq!(
+            read_frame(&mut &too_long[..]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_error_roundtrip() {
+        for err in [
+            SignerError::UserCancel("declined".to_string()),
+            SignerError::NoDeviceFound,
+            SignerError::InvalidInput("message too long".to_string()),
+            SignerError::Custom("key store locked".to_string()),
+        ] {
+            let (status, reason) = encode_error(&err);
+            assert_eq!(decode_error(status, reason.as_bytes()), err);
+        }
+
+        let (status, reason) = encode_error(&SignerError::Connection("lost".to_string()));
+        assert_eq!(
+            decode_error(status, reason.as_bytes()),
+            SignerError::Custom("connection error: lost".to_string())
+        );
+        assert!(matches!(decode_error(42, &[]), SignerError::Protocol(_)));
+    }
+}
@@ -0,0 +1,125 @@
+//! Serving [`Signer`] requests over a stream.
+
+use {
+    crate::protocol::{
+        encode_error, read_frame, write_frame, REQUEST_PUBKEY, REQUEST_SIGN_MESSAGE,
+        STATUS_INVALID_INPUT, STATUS_OK,
+    },
+    solana_signer::Signer,
+    std::io::{self, Read, Write},
+};
+
+/// Answer the requests read from `reader` with `signer`, writing the
+/// responses to `writer`, until `reader` reaches end of file.
+///
+/// This is the server side of the [protocol](crate::protocol). It can be used
+/// as is by a process holding the key, and serves as a stand-in for such a
+/// process in tests. Errors of `signer` are reported to the client; only I/O
+/// errors and malformed frames end serving.
+pub fn serve<S: Signer + ?Sized, R: Read, W: Write>(
+    signer: &S,
+    mut reader: R,
+    mut writer: W,
+) -> io::Result<()> {
+    while let Some(request) = read_frame(&mut reader)? {
+        let response = match request.split_first() {
+            Some((&REQUEST_PUBKEY, [])) => {
+                signer.try_pubkey().map(|pubkey| pubkey.to_bytes().to_vec())
+            }
+            Some((&REQUEST_SIGN_MESSAGE, message)) => signer
+                .try_sign_message(message)
+                .map(|signature| signature.as_ref().to_vec()),
+            _ => {
+                write_frame(&mut writer, STATUS_INVALID_INPUT, b"unknown request")?;
+                continue;
+            }
+        };
+        match response {
+            Ok(payload) => write_frame(&mut writer, STATUS_OK, &payload)?,
+            Err(err) => {
+                let (status, reason) = encode_error(&err);
+                write_frame(&mut writer, status, reason.as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::protocol::STATUS_NO_DEVICE_FOUND, solana_keypair::Keypair,
+        solana_pubkey::Pubkey, solana_signature::Signature, solana_signer::SignerError,
+        std::io::Cursor,
+    };
+
+    fn frames(frames: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut buffer = vec![];
+        for (tag, payload) in frames {
+            write_frame(&mut buffer, *tag, payload).unwrap();
+        }
+        buffer
+    }
+
+    struct NoDevice;
+
+    impl Signer for NoDevice {
+        fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+            Err(SignerError::NoDeviceFound)
+        }
+
+        fn try_sign_message(&self, _message: &[u8]) -> Result<Signature, SignerError> {
+            Err(SignerError::NoDeviceFound)
+        }
+
+        fn is_interactive(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_serve() {
+        let keypair = Keypair::new();
+        let requests = frames(&[
+            (REQUEST_PUBKEY, &[]),
+            (REQUEST_SIGN_MESSAGE, b"message"),
+            (7, &[]),
+        ]);
+        let mut responses = vec![];
+        serve(&keypair, requests.as_slice(), &mut responses).unwrap();
+
+        assert_eq!(
+            responses,
+            frames(&[
+                (STATUS_OK, keypair.pubkey().as_ref()),
+                (STATUS_OK, keypair.sign_message(b"message").as_ref()),
+                (STATUS_INVALID_INPUT, b"unknown request"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serve_signer_error() {
+        let requests = frames(&[(REQUEST_PUBKEY, &[]), (REQUEST_SIGN_MESSAGE, &[])]);
+        let mut responses = vec![];
+        serve(&NoDevice, requests.as_slice(), &mut responses).unwrap();
+        assert_eq!(
+            responses,
+            frames(&[(STATUS_NO_DEVICE_FOUND, &[]), (STATUS_NO_DEVICE_FOUND, &[])])
+        );
+    }
+
+    #[test]
+    fn test_serve_truncated_request() {
+        let mut requests = frames(&[(REQUEST_SIGN_MESSAGE, b"message")]);
+        requests.pop();
+        let mut responses = vec![];
+        assert_eq!(
+            serve(&NoDevice, Cursor::new(requests), &mut responses)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert!(responses.is_empty());
+    }
+}
@@ -14,13 +14,17 @@ build_sbf_excludes=(
   --exclude solana-keypair
   --exclude solana-offchain-message
   --exclude solana-presigner
+  --exclude solana-remote-signer
   --exclude solana-sdk-wasm-js
   --exclude solana-sdk-wasm-js-tests
   --exclude solana-secp256k1-program
   --exclude solana-secp256r1-program
+  --exclude solana-syscall-harness
+  --exclude solana-system-processor
   --exclude solana-system-transaction
   --exclude solana-system-wasm-js
   --exclude solana-transaction
+  --exclude solana-transaction-inspector
   --exclude solana-sdk
 )
 
@@ -28,6 +28,7 @@ all_crate_dirs=(
   ed25519-program
   epoch-info
   epoch-rewards
+  epoch-rewards-estimator
   epoch-rewards-hasher
   epoch-schedule
   epoch-stake
@@ -70,6 +71,7 @@ all_crate_dirs=(
   program-option
   program-pack
   pubkey
+  remote-signer
   rent
   reward-info
   sanitize
@@ -93,12 +95,15 @@ all_crate_dirs=(
   slot-hashes
   slot-history
   stable-layout
+  syscall-harness
+  system-processor
   system-transaction
   sysvar
   sysvar-id
   time-utils
   transaction
   transaction-error
+  transaction-inspector
   validator-exit
   vote-interface
 )
@@ -0,0 +1,101 @@
+//! BIP-39 wordlists.
+//!
+//! The wordlists are the ones published with [BIP-39], stored in Unicode
+//! normalization form NFKD.
+//!
+//! [BIP-39]: https://github.com/bitcoin/bips/blob/master/bip-0039/bip-0039-wordlists.md
+
+mod chinese_simplified;
+mod chinese_traditional;
+mod english;
+mod french;
+mod italian;
+mod japanese;
+mod korean;
+mod spanish;
+
+/// Number of words in a wordlist.
+pub const WORDLIST_LENGTH: usize = 2048;
+
+/// Language of a mnemonic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Spanish,
+}
+
+impl Language {
+    /// All supported languages.
+    pub const ALL: [Language; 8] = [
+        Language::English,
+        Language::ChineseSimplified,
+        Language::ChineseTraditional,
+        Language::French,
+        Language::Italian,
+        Language::Japanese,
+        Language::Korean,
+        Language::Spanish,
+    ];
+
+    /// The wordlist of the language.
+    pub fn wordlist(self) -> &'static [&'static str; WORDLIST_LENGTH] {
+        match self {
+            Language::English => &english::WORDS,
+            Language::ChineseSimplified => &chinese_simplified::WORDS,
+            Language::ChineseTraditional => &chinese_traditional::WORDS,
+            Language::French => &french::WORDS,
+            Language::Italian => &italian::WORDS,
+            Language::Japanese => &japanese::WORDS,
+            Language::Korean => &korean::WORDS,
+            Language::Spanish => &spanish::WORDS,
+        }
+    }
+
+    /// Index of `word` in the wordlist. `word` must be normalized to NFKD.
+    pub fn find_word(self, word: &str) -> Option<u16> {
+        self.wordlist()
+            .iter()
+            .position(|candidate| *candidate == word)
+            .and_then(|index| u16::try_from(index).ok())
+    }
+
+    /// Separator between the words of a phrase. Japanese phrases are
+    /// separated by ideographic spaces, which normalize to ASCII spaces.
+    pub fn separator(self) -> &'static str {
+        match self {
+            Language::Japanese => "\u{3000}",
+            _ => " ",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::HashSet, unicode_normalization::is_nfkd};
+
+    #[test]
+    fn test_wordlists() {
+        for language in Language::ALL {
+            let wordlist = language.wordlist();
+            assert!(wordlist.iter().all(|word| is_nfkd(word)));
+            assert_eq!(
+                wordlist.iter().collect::<HashSet<_>>().len(),
+                WORDLIST_LENGTH
+            );
+            for (index, word) in wordlist.iter().enumerate() {
+                assert_eq!(language.find_word(word), Some(index as u16));
+            }
+        }
+        assert_eq!(Language::English.find_word("abandon"), Some(0));
+        assert_eq!(Language::English.find_word("zoo"), Some(2047));
+        assert_eq!(Language::English.find_word("abandonn"), None);
+        assert_eq!(Language::French.find_word("abandon"), Some(1));
+    }
+}
@@ -2,6 +2,15 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 use hmac::Hmac;
 
+pub mod language;
+pub mod mnemonic;
+
+/// Derives the BIP-39 seed of `seed_phrase` and `passphrase`.
+///
+/// The phrase isn't validated or normalized; use
+/// [`Mnemonic::from_phrase`](mnemonic::Mnemonic::from_phrase) and
+/// [`Mnemonic::to_seed`](mnemonic::Mnemonic::to_seed) to check the phrase
+/// against a wordlist.
 pub fn generate_seed_from_seed_phrase_and_passphrase(
     seed_phrase: &str,
     passphrase: &str,
@@ -0,0 +1,424 @@
+//! BIP-39 mnemonics.
+//!
+//! A mnemonic encodes 128 to 256 bits of entropy, followed by a checksum of
+//! one bit per 32 bits of entropy taken from the SHA-256 hash of the entropy,
+//! as a sequence of 11 bit indexes into a [wordlist](Language::wordlist).
+//!
+//! Phrases and passphrases are normalized to Unicode NFKD before they are
+//! looked up in the wordlist or turned into a seed, as BIP-39 specifies.
+
+use {
+    crate::{generate_seed_from_seed_phrase_and_passphrase, language::Language},
+    sha2::{Digest, Sha256},
+    std::{error, fmt},
+    unicode_normalization::UnicodeNormalization,
+    zeroize::Zeroizing,
+};
+
+const BITS_PER_WORD: usize = 11;
+
+/// Number of words in a mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WordCount {
+    Words12,
+    Words15,
+    Words18,
+    Words21,
+    Words24,
+}
+
+impl WordCount {
+    /// The word count of a mnemonic with `word_count` words.
+    pub fn from_word_count(word_count: usize) -> Option<Self> {
+        match word_count {
+            12 => Some(Self::Words12),
+            15 => Some(Self::Words15),
+            18 => Some(Self::Words18),
+            21 => Some(Self::Words21),
+            24 => Some(Self::Words24),
+            _ => None,
+        }
+    }
+
+    /// The word count of a mnemonic encoding `entropy_len` bytes of entropy.
+    pub fn from_entropy_len(entropy_len: usize) -> Option<Self> {
+        match entropy_len {
+            16 => Some(Self::Words12),
+            20 => Some(Self::Words15),
+            24 => Some(Self::Words18),
+            28 => Some(Self::Words21),
+            32 => Some(Self::Words24),
+            _ => None,
+        }
+    }
+
+    /// Number of words.
+    pub fn word_count(self) -> usize {
+        match self {
+            Self::Words12 => 12,
+            Self::Words15 => 15,
+            Self::Words18 => 18,
+            Self::Words21 => 21,
+            Self::Words24 => 24,
+        }
+    }
+
+    /// Number of bytes of entropy encoded by the words.
+    pub fn entropy_len(self) -> usize {
+        match self {
+            Self::Words12 => 16,
+            Self::Words15 => 20,
+            Self::Words18 => 24,
+            Self::Words21 => 28,
+            Self::Words24 => 32,
+        }
+    }
+
+    /// Number of checksum bits encoded by the words.
+    fn checksum_bits(self) -> usize {
+        self.entropy_len() / 4
+    }
+}
+
+/// Errors parsing or creating a mnemonic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MnemonicError {
+    /// The entropy isn't 16, 20, 24, 28 or 32 bytes long.
+    InvalidEntropyLength(usize),
+    /// The phrase doesn't have 12, 15, 18, 21 or 24 words.
+    InvalidWordCount(usize),
+    /// The word at zero-based `index` of the phrase isn't in the wordlist.
+    ///
+    /// The word itself isn't kept, since it is part of a secret.
+    UnknownWord { index: usize },
+    /// The checksum encoded by the last word doesn't match the entropy.
+    InvalidChecksum,
+}
+
+impl error::Error for MnemonicError {}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEntropyLength(len) => write!(
+                f,
+                "invalid entropy length {len}, expected 16, 20, 24, 28 or 32 bytes"
+            ),
+            Self::InvalidWordCount(count) => write!(
+                f,
+                "invalid word count {count}, expected 12, 15, 18, 21 or 24 words"
+            ),
+            Self::UnknownWord { index } => {
+                write!(f, "word {} is not in the wordlist", index.saturating_add(1))
+            }
+            Self::InvalidChecksum => f.write_str("invalid mnemonic checksum"),
+        }
+    }
+}
+
+/// A BIP-39 mnemonic.
+///
+/// The phrase and entropy are zeroed when the mnemonic is dropped, and are
+/// left out of its `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+    phrase: Zeroizing<String>,
+    entropy: Zeroizing<Vec<u8>>,
+    language: Language,
+}
+
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mnemonic")
+            .field("word_count", &self.word_count())
+            .field("language", &self.language)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Mnemonic {
+    /// Encode `entropy` as a mnemonic in `language`.
+    pub fn from_entropy(entropy: &[u8], language: Language) -> Result<Self, MnemonicError> {
+        let word_count = WordCount::from_entropy_len(entropy.len())
+            .ok_or(MnemonicError::InvalidEntropyLength(entropy.len()))?;
+        let checksum = checksum(entropy);
+        let mut bits = bits(entropy).chain(bits_of(checksum));
+        let wordlist = language.wordlist();
+        let words = (0..word_count.word_count())
+            .map(|_| {
+                let index = bits
+                    .by_ref()
+                    .take(BITS_PER_WORD)
+                    .fold(0usize, |index, bit| index << 1 | usize::from(bit));
+                wordlist[index]
+            })
+            .collect::<Vec<_>>();
+        Ok(Self {
+            phrase: Zeroizing::new(words.join(language.separator())),
+            entropy: Zeroizing::new(entropy.to_vec()),
+            language,
+        })
+    }
+
+    /// Parse and validate a mnemonic `phrase` in `language`.
+    ///
+    /// Words may be separated by any whitespace.
+    pub fn from_phrase(phrase: &str, language: Language) -> Result<Self, MnemonicError> {
+        let normalized = Zeroizing::new(phrase.nfkd().collect::<String>());
+        let words = normalized.split_whitespace().collect::<Vec<_>>();
+        let word_count = WordCount::from_word_count(words.len())
+            .ok_or(MnemonicError::InvalidWordCount(words.len()))?;
+
+        let mut bits = Zeroizing::new(Vec::with_capacity(
+            words.len().saturating_mul(BITS_PER_WORD),
+        ));
+        for (index, word) in words.iter().enumerate() {
+            let word_index = language
+                .find_word(word)
+                .ok_or(MnemonicError::UnknownWord { index })?;
+            bits.extend(
+                (0..BITS_PER_WORD)
+                    .rev()
+                    .map(|shift| word_index >> shift & 1 == 1),
+            );
+        }
+
+        let (entropy_bits, checksum_bits) =
+            bits.split_at(word_count.entropy_len().saturating_mul(8));
+        let entropy = Zeroizing::new(
+            entropy_bits
+                .chunks(8)
+                .map(|byte| {
+                    byte.iter()
+                        .fold(0u8, |byte, bit| byte << 1 | u8::from(*bit))
+                })
+                .collect::<Vec<_>>(),
+        );
+        if !bits_of(checksum(&entropy))
+            .take(word_count.checksum_bits())
+            .eq(checksum_bits.iter().copied())
+        {
+            return Err(MnemonicError::InvalidChecksum);
+        }
+
+        Ok(Self {
+            phrase: Zeroizing::new(words.join(language.separator())),
+            entropy,
+            language,
+        })
+    }
+
+    /// The phrase, with words separated by the
+    /// [separator](Language::separator) of the language.
+    pub fn phrase(&self) -> &str {
+        &self.phrase
+    }
+
+    /// The entropy encoded by the phrase.
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    /// The language of the phrase.
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// The number of words of the phrase.
+    pub fn word_count(&self) -> WordCount {
+        // the entropy length was validated on construction
+        WordCount::from_entropy_len(self.entropy.len()).unwrap()
+    }
+
+    /// The 64 byte seed derived from the phrase and `passphrase`.
+    pub fn to_seed(&self, passphrase: &str) -> Vec<u8> {
+        generate_seed_from_seed_phrase_and_passphrase(
+            &Zeroizing::new(self.phrase.nfkd().collect::<String>()),
+            &Zeroizing::new(passphrase.nfkd().collect::<String>()),
+        )
+    }
+}
+
+/// The first byte of the SHA-256 hash of `entropy`, which holds the checksum
+/// bits of every word count.
+fn checksum(entropy: &[u8]) -> u8 {
+    Sha256::digest(entropy)[0]
+}
+
+/// The bits of `byte`, most significant first.
+fn bits_of(byte: u8) -> impl Iterator<Item = bool> {
+    (0..8).rev().map(move |shift| byte >> shift & 1 == 1)
+}
+
+/// The bits of `bytes`, most significant first.
+fn bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes.iter().flat_map(|byte| bits_of(*byte))
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use super::*;
+
+    // test vectors from https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+    const VECTORS: [(&str, &str, &str); 3] = [
+        (
+            "00000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        ),
+        (
+            "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+        ),
+        (
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+            "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad",
+        ),
+    ];
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_vectors() {
+        for (entropy, phrase, seed) in VECTORS {
+            let entropy = decode_hex(entropy);
+            let mnemonic = Mnemonic::from_entropy(&entropy, Language::English).unwrap();
+            assert_eq!(mnemonic.phrase(), phrase);
+            assert_eq!(mnemonic.entropy(), entropy);
+            assert_eq!(
+                Mnemonic::from_phrase(phrase, Language::English).unwrap(),
+                mnemonic
+            );
+            assert_eq!(mnemonic.to_seed("TREZOR"), decode_hex(seed));
+        }
+    }
+
+    #[test]
+    fn test_languages() {
+        let languages = [
+            (Language::English, bip39::Language::English),
+            (
+                Language::ChineseSimplified,
+                bip39::Language::ChineseSimplified,
+            ),
+            (
+                Language::ChineseTraditional,
+                bip39::Language::ChineseTraditional,
+            ),
+            (Language::French, bip39::Language::French),
+            (Language::Italian, bip39::Language::Italian),
+            (Language::Japanese, bip39::Language::Japanese),
+            (Language::Korean, bip39::Language::Korean),
+            (Language::Spanish, bip39::Language::Spanish),
+        ];
+        for (language, bip39_language) in languages {
+            for entropy_len in [16, 20, 24, 28, 32] {
+                let entropy = (0..entropy_len)
+                    .map(|i| (i as u8).wrapping_mul(37))
+                    .collect::<Vec<_>>();
+                let mnemonic = Mnemonic::from_entropy(&entropy, language).unwrap();
+                let expected = bip39::Mnemonic::from_entropy(&entropy, bip39_language).unwrap();
+                assert!(mnemonic
+                    .phrase()
+                    .split_whitespace()
+                    .eq(expected.phrase().split_whitespace()));
+                assert_eq!(
+                    mnemonic.word_count().word_count(),
+                    mnemonic.phrase().split_whitespace().count()
+                );
+                assert_eq!(
+                    Mnemonic::from_phrase(expected.phrase(), language).unwrap(),
+                    mnemonic
+                );
+                assert_eq!(
+                    mnemonic.to_seed("パスワード"),
+                    bip39::Seed::new(&expected, "パスワード").as_bytes()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalization() {
+        // "académie" composed (NFC), while the wordlist stores it decomposed
+        let phrase = "acade\u{301}mie ".repeat(11) + "abeille";
+        let composed = "acad\u{e9}mie ".repeat(11) + "abeille";
+        let decomposed = Mnemonic::from_phrase(&phrase, Language::French);
+        assert_eq!(
+            Mnemonic::from_phrase(&composed, Language::French),
+            decomposed
+        );
+        assert!(!matches!(
+            decomposed,
+            Err(MnemonicError::UnknownWord { .. })
+        ));
+
+        let mnemonic = Mnemonic::from_entropy(&[0; 16], Language::Japanese).unwrap();
+        assert!(mnemonic.phrase().contains('\u{3000}'));
+        let spaced = mnemonic.phrase().replace('\u{3000}', "  \n");
+        assert_eq!(
+            Mnemonic::from_phrase(&spaced, Language::Japanese).unwrap(),
+            mnemonic
+        );
+        assert_eq!(
+            mnemonic.to_seed(""),
+            generate_seed_from_seed_phrase_and_passphrase(
+                &mnemonic.phrase().replace('\u{3000}', " "),
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let mnemonic = Mnemonic::from_entropy(&[0x7f; 16], Language::English).unwrap();
+        let debug = format!("{mnemonic:?}");
+        assert_eq!(
+            debug,
+            "Mnemonic { word_count: Words12, language: English, .. }"
+        );
+        assert!(!debug.contains("legal"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Mnemonic::from_entropy(&[0; 15], Language::English),
+            Err(MnemonicError::InvalidEntropyLength(15))
+        );
+        assert_eq!(
+            Mnemonic::from_phrase(&"abandon ".repeat(11), Language::English),
+            Err(MnemonicError::InvalidWordCount(11))
+        );
+        assert_eq!(
+            Mnemonic::from_phrase("", Language::English),
+            Err(MnemonicError::InvalidWordCount(0))
+        );
+
+        let typo = "abandon abandon abandon abandn abandon abandon abandon abandon abandon abandon abandon about";
+        let err = Mnemonic::from_phrase(typo, Language::English).unwrap_err();
+        assert_eq!(err, MnemonicError::UnknownWord { index: 3 });
+        assert_eq!(err.to_string(), "word 4 is not in the wordlist");
+        assert!(matches!(
+            Mnemonic::from_phrase(VECTORS[0].1, Language::French),
+            Err(MnemonicError::UnknownWord { index: 11, .. })
+        ));
+
+        assert_eq!(
+            Mnemonic::from_phrase(&"abandon ".repeat(12), Language::English),
+            Err(MnemonicError::InvalidChecksum)
+        );
+        assert_eq!(
+            Mnemonic::from_phrase(&("zoo ".repeat(23) + "zoo"), Language::English),
+            Err(MnemonicError::InvalidChecksum)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Program input buffers in the layout produced by the runtime.
+//!
+//! Some `AccountInfo` operations, such as [`AccountInfo::resize`], assume that
+//! the account data lives in a buffer serialized by the runtime. A
+//! [`ProgramInput`] serializes accounts in that layout, so that the account
+//! infos handed to a program under test behave as they would on-chain.
+
+use {
+    solana_account_info::{AccountInfo, MAX_PERMITTED_DATA_INCREASE},
+    solana_program_entrypoint::{deserialize, BPF_ALIGN_OF_U128, NON_DUP_MARKER},
+    solana_pubkey::Pubkey,
+    std::mem::size_of,
+};
+
+/// An account to be passed to a program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// A serialized program input buffer.
+///
+/// Accounts that appear more than once are serialized as duplicates of their
+/// first occurrence, taking the flags of that occurrence.
+pub struct ProgramInput {
+    // Backed by `u64`s to guarantee the alignment expected by the entrypoint
+    buffer: Vec<u64>,
+}
+
+impl ProgramInput {
+    /// Serializes the input for an instruction.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn new(program_id: &Pubkey, accounts: &[InputAccount], instruction_data: &[u8]) -> Self {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+        for (i, account) in accounts.iter().enumerate() {
+            if let Some(position) = accounts[..i].iter().position(|a| a.key == account.key) {
+                bytes.push(position as u8);
+                bytes.extend_from_slice(&[0; 7]); // padding
+                continue;
+            }
+            bytes.extend_from_slice(&[
+                NON_DUP_MARKER,
+                account.is_signer as u8,
+                account.is_writable as u8,
+                account.executable as u8,
+            ]);
+            bytes.extend_from_slice(&(account.data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(account.key.as_ref());
+            bytes.extend_from_slice(account.owner.as_ref());
+            bytes.extend_from_slice(&account.lamports.to_le_bytes());
+            bytes.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&account.data);
+            bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+            bytes.resize(bytes.len().next_multiple_of(BPF_ALIGN_OF_U128), 0);
+            bytes.extend_from_slice(&u64::MAX.to_le_bytes()); // rent epoch
+        }
+        bytes.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(instruction_data);
+        bytes.extend_from_slice(program_id.as_ref());
+
+        let mut buffer = vec![0u64; bytes.len().div_ceil(size_of::<u64>())];
+        // SAFETY: the buffer holds at least `bytes.len()` bytes.
+        unsafe {
+            std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, bytes.len())
+                .copy_from_slice(&bytes);
+        }
+        Self { buffer }
+    }
+
+    /// Deserializes the input as the entrypoint would.
+    pub fn deserialize(&mut self) -> (&Pubkey, Vec<AccountInfo<'_>>, &[u8]) {
+        // SAFETY: the buffer was serialized in the layout expected by the
+        // entrypoint, and stays borrowed for as long as the account infos live.
+        unsafe { deserialize(self.buffer.as_mut_ptr() as *mut u8) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_program_error::ProgramError};
+
+    #[test]
+    fn test_program_input() {
+        let program_id = Pubkey::new_unique();
+        let account = InputAccount {
+            key: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            lamports: 42,
+            data: vec![1, 2, 3],
+            executable: false,
+            is_signer: true,
+            is_writable: true,
+        };
+        let mut input = ProgramInput::new(
+            &program_id,
+            &[account.clone(), InputAccount::default(), account.clone()],
+            &[4, 5],
+        );
+
+        let (deserialized_program_id, accounts, instruction_data) = input.deserialize();
+        assert_eq!(deserialized_program_id, &program_id);
+        assert_eq!(instruction_data, &[4, 5]);
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[0].key, &account.key);
+        assert_eq!(accounts[0].owner, &account.owner);
+        assert_eq!(accounts[0].lamports(), 42);
+        assert_eq!(&**accounts[0].data.borrow(), &[1, 2, 3]);
+        assert!(accounts[0].is_signer && accounts[0].is_writable);
+        assert_eq!(accounts[1].key, &Pubkey::default());
+        assert!(!accounts[1].is_signer && !accounts[1].is_writable);
+
+        // duplicates share state, and data can be resized in place
+        accounts[2].resize(5).unwrap();
+        assert_eq!(&**accounts[0].data.borrow(), &[1, 2, 3, 0, 0]);
+
+        // growth is limited relative to the original data length
+        // SAFETY: the accounts were deserialized from a runtime-layout buffer.
+        assert_eq!(unsafe { accounts[0].original_data_len() }, 3);
+        assert_eq!(
+            accounts[0].resize(3 + MAX_PERMITTED_DATA_INCREASE + 1),
+            Err(ProgramError::InvalidRealloc)
+        );
+        assert_eq!(accounts[0].resize(3 + MAX_PERMITTED_DATA_INCREASE), Ok(()));
+    }
+}
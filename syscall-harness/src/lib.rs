@@ -0,0 +1,846 @@
+//! In-memory syscall stubs for testing Solana programs off-chain.
+//!
+//! A [`SyscallHarness`] implements [`SyscallStubs`] on top of configurable
+//! state: sysvars, epoch stakes, and the set of native programs that
+//! cross-program invocations are routed to. Everything a program emits through
+//! syscalls, such as logs, `sol_log_data` events and return data, is captured
+//! so that tests can assert on it.
+//!
+//! The harness is cheaply cloneable, and all clones share state, so a test can
+//! [install](SyscallHarness::install) one clone as the global syscall stubs and
+//! keep another to configure and inspect it:
+//!
+//! ```
+//! use {
+//!     solana_clock::Clock,
+//!     solana_pubkey::Pubkey,
+//!     solana_syscall_harness::SyscallHarness,
+//!     solana_sysvar::{program_stubs::sol_log, Sysvar},
+//! };
+//!
+//! let harness = SyscallHarness::new(Pubkey::new_unique());
+//! harness.set_clock(Clock {
+//!     slot: 42,
+//!     ..Clock::default()
+//! });
+//! harness.install();
+//!
+//! sol_log("hello");
+//! assert_eq!(Clock::get().unwrap().slot, 42);
+//! assert_eq!(harness.logs(), ["Program log: hello"]);
+//! ```
+//!
+//! The global syscall stubs are shared by every test in a process, so tests
+//! that install a harness should not run concurrently.
+
+#![cfg(not(target_os = "solana"))]
+
+pub mod input;
+pub mod system;
+
+use {
+    base64::{prelude::BASE64_STANDARD, Engine},
+    solana_account_info::AccountInfo,
+    solana_clock::Clock,
+    solana_epoch_rewards::EpochRewards,
+    solana_epoch_schedule::EpochSchedule,
+    solana_instruction::{error::InstructionError, Instruction},
+    solana_last_restart_slot::LastRestartSlot,
+    solana_program_entrypoint::{ProcessInstruction, SUCCESS},
+    solana_program_error::{ProgramError, ProgramResult},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{system_program, sysvar},
+    solana_sysvar::program_stubs::{set_syscall_stubs, SyscallStubs},
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, MutexGuard},
+    },
+};
+
+// Return codes of `sol_get_sysvar`, as defined by the runtime
+const OFFSET_LENGTH_EXCEEDS_SYSVAR: u64 = 1;
+const SYSVAR_NOT_FOUND: u64 = 2;
+
+/// The maximum height of the program stack, as defined by the runtime
+const MAX_INSTRUCTION_STACK_DEPTH: usize = 5;
+
+/// The state shared by all clones of a [`SyscallHarness`].
+struct State {
+    clock: Clock,
+    rent: Rent,
+    epoch_schedule: EpochSchedule,
+    epoch_rewards: EpochRewards,
+    last_restart_slot: LastRestartSlot,
+    epoch_stakes: HashMap<Pubkey, u64>,
+    processors: HashMap<Pubkey, ProcessInstruction>,
+    /// The programs currently executing, starting with the top-level program
+    program_stack: Vec<Pubkey>,
+    logs: Vec<String>,
+    data_events: Vec<Vec<Vec<u8>>>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+    invoked_instructions: Vec<Instruction>,
+}
+
+/// An in-memory implementation of [`SyscallStubs`].
+#[derive(Clone)]
+pub struct SyscallHarness {
+    state: Arc<Mutex<State>>,
+}
+
+impl SyscallHarness {
+    /// Creates a harness for a top-level invocation of `program_id`, with
+    /// default sysvars and the System program registered.
+    pub fn new(program_id: Pubkey) -> Self {
+        let mut processors = HashMap::new();
+        processors.insert(
+            system_program::id(),
+            system::process_instruction as ProcessInstruction,
+        );
+        Self {
+            state: Arc::new(Mutex::new(State {
+                clock: Clock::default(),
+                rent: Rent::default(),
+                epoch_schedule: EpochSchedule::default(),
+                epoch_rewards: EpochRewards::default(),
+                last_restart_slot: LastRestartSlot::default(),
+                epoch_stakes: HashMap::new(),
+                processors,
+                program_stack: vec![program_id],
+                logs: Vec::new(),
+                data_events: Vec::new(),
+                return_data: None,
+                invoked_instructions: Vec::new(),
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Installs a clone of the harness as the global syscall stubs, returning
+    /// the previously installed stubs.
+    pub fn install(&self) -> Box<dyn SyscallStubs> {
+        set_syscall_stubs(Box::new(self.clone()))
+    }
+
+    pub fn set_clock(&self, clock: Clock) {
+        self.state().clock = clock;
+    }
+
+    pub fn set_rent(&self, rent: Rent) {
+        self.state().rent = rent;
+    }
+
+    pub fn set_epoch_schedule(&self, epoch_schedule: EpochSchedule) {
+        self.state().epoch_schedule = epoch_schedule;
+    }
+
+    pub fn set_epoch_rewards(&self, epoch_rewards: EpochRewards) {
+        self.state().epoch_rewards = epoch_rewards;
+    }
+
+    pub fn set_last_restart_slot(&self, last_restart_slot: LastRestartSlot) {
+        self.state().last_restart_slot = last_restart_slot;
+    }
+
+    /// Sets the stake delegated to a vote account in the current epoch.
+    pub fn set_epoch_stake(&self, vote_address: Pubkey, stake: u64) {
+        self.state().epoch_stakes.insert(vote_address, stake);
+    }
+
+    /// Registers a native program that cross-program invocations of
+    /// `program_id` are dispatched to.
+    pub fn add_program(&self, program_id: Pubkey, process_instruction: ProcessInstruction) {
+        self.state()
+            .processors
+            .insert(program_id, process_instruction);
+    }
+
+    /// The messages logged so far, in the format used by the runtime.
+    pub fn logs(&self) -> Vec<String> {
+        self.state().logs.clone()
+    }
+
+    /// The fields of every `sol_log_data` event emitted so far.
+    pub fn data_events(&self) -> Vec<Vec<Vec<u8>>> {
+        self.state().data_events.clone()
+    }
+
+    /// The return data most recently set, along with the program that set it.
+    pub fn return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.state().return_data.clone()
+    }
+
+    /// The instructions invoked through cross-program invocations so far.
+    pub fn invoked_instructions(&self) -> Vec<Instruction> {
+        self.state().invoked_instructions.clone()
+    }
+
+    /// Clears the captured logs, events, return data and invoked instructions.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.logs.clear();
+        state.data_events.clear();
+        state.return_data = None;
+        state.invoked_instructions.clear();
+    }
+
+    /// Serializes the sysvar with the given id, in the layout returned by the
+    /// `sol_get_sysvar` syscall.
+    fn sysvar_data(&self, sysvar_id: &Pubkey) -> Option<Vec<u8>> {
+        let state = self.state();
+        let data = if sysvar::clock::check_id(sysvar_id) {
+            bincode::serialize(&state.clock)
+        } else if sysvar::rent::check_id(sysvar_id) {
+            bincode::serialize(&state.rent)
+        } else if sysvar::epoch_schedule::check_id(sysvar_id) {
+            bincode::serialize(&state.epoch_schedule)
+        } else if sysvar::epoch_rewards::check_id(sysvar_id) {
+            bincode::serialize(&state.epoch_rewards)
+        } else if sysvar::last_restart_slot::check_id(sysvar_id) {
+            bincode::serialize(&state.last_restart_slot)
+        } else {
+            return None;
+        };
+        Some(data.unwrap())
+    }
+
+    /// Checks that `program_id` can be pushed onto the program stack. As in the
+    /// runtime, a program may invoke itself but may not be reentered through
+    /// another program.
+    fn check_program_stack(
+        program_stack: &[Pubkey],
+        program_id: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        if program_stack.contains(program_id) && program_stack.last() != Some(program_id) {
+            return Err(InstructionError::ReentrancyNotAllowed);
+        }
+        if program_stack.len() >= MAX_INSTRUCTION_STACK_DEPTH {
+            return Err(InstructionError::CallDepth);
+        }
+        Ok(())
+    }
+
+    /// Checks the privileges requested by an instruction against those of the
+    /// caller, and returns the account infos to pass to the callee.
+    fn instruction_accounts<'a>(
+        caller: &Pubkey,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo<'a>],
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<Vec<AccountInfo<'a>>, ProgramError> {
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, caller))
+            .collect::<Result<Vec<_>, _>>()?;
+        instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let account_info = account_infos
+                    .iter()
+                    .find(|account_info| *account_info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                if meta.is_signer && !account_info.is_signer && !signers.contains(&meta.pubkey) {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                if meta.is_writable && !account_info.is_writable {
+                    return Err(ProgramError::InvalidArgument);
+                }
+                let mut account_info = account_info.clone();
+                account_info.is_signer = meta.is_signer;
+                account_info.is_writable = meta.is_writable;
+                Ok(account_info)
+            })
+            .collect()
+    }
+}
+
+impl SyscallStubs for SyscallHarness {
+    fn sol_log(&self, message: &str) {
+        self.state().logs.push(format!("Program log: {message}"));
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let (process_instruction, accounts) = {
+            let mut state = self.state();
+            let caller = *state.program_stack.last().unwrap();
+            let accounts =
+                Self::instruction_accounts(&caller, instruction, account_infos, signers_seeds)?;
+            let process_instruction = *state
+                .processors
+                .get(&instruction.program_id)
+                .ok_or(ProgramError::IncorrectProgramId)?;
+            if let Err(err) =
+                Self::check_program_stack(&state.program_stack, &instruction.program_id)
+            {
+                // these errors have no program error of their own
+                state.logs.push(format!("Program log: {err}"));
+                return Err(ProgramError::InvalidArgument);
+            }
+            state.invoked_instructions.push(instruction.clone());
+            state.program_stack.push(instruction.program_id);
+            let height = state.program_stack.len();
+            state.logs.push(format!(
+                "Program {} invoke [{height}]",
+                instruction.program_id
+            ));
+            (process_instruction, accounts)
+        };
+
+        // the lock must not be held while the callee issues syscalls
+        let result = process_instruction(&instruction.program_id, &accounts, &instruction.data);
+
+        let mut state = self.state();
+        state.program_stack.pop();
+        let log = match &result {
+            Ok(()) => format!("Program {} success", instruction.program_id),
+            Err(err) => format!("Program {} failed: {err}", instruction.program_id),
+        };
+        state.logs.push(log);
+        result
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn sol_get_sysvar(
+        &self,
+        sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64,
+    ) -> u64 {
+        // SAFETY: the caller passes a pointer to a sysvar id.
+        let sysvar_id = unsafe { &*(sysvar_id_addr as *const Pubkey) };
+        let Some(data) = self.sysvar_data(sysvar_id) else {
+            return SYSVAR_NOT_FOUND;
+        };
+        let Some(slice) = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(length).ok())
+            .and_then(|(offset, length)| data.get(offset..offset.checked_add(length)?))
+        else {
+            return OFFSET_LENGTH_EXCEEDS_SYSVAR;
+        };
+        // SAFETY: the caller passes a buffer of at least `length` bytes.
+        unsafe { std::ptr::copy_nonoverlapping(slice.as_ptr(), var_addr, slice.len()) };
+        SUCCESS
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to a `Clock`.
+        unsafe { *(var_addr as *mut Clock) = self.state().clock.clone() };
+        SUCCESS
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to an `EpochSchedule`.
+        unsafe { *(var_addr as *mut EpochSchedule) = self.state().epoch_schedule.clone() };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to a `Rent`.
+        unsafe { *(var_addr as *mut Rent) = self.state().rent.clone() };
+        SUCCESS
+    }
+
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to an `EpochRewards`.
+        unsafe { *(var_addr as *mut EpochRewards) = self.state().epoch_rewards.clone() };
+        SUCCESS
+    }
+
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to a `LastRestartSlot`.
+        unsafe { *(var_addr as *mut LastRestartSlot) = self.state().last_restart_slot.clone() };
+        SUCCESS
+    }
+
+    fn sol_get_epoch_stake(&self, vote_address: *const u8) -> u64 {
+        let state = self.state();
+        if vote_address.is_null() {
+            return state
+                .epoch_stakes
+                .values()
+                .fold(0, |total, stake| total.saturating_add(*stake));
+        }
+        // SAFETY: the caller passes a pointer to a vote address.
+        let vote_address = unsafe { &*(vote_address as *const Pubkey) };
+        state.epoch_stakes.get(vote_address).copied().unwrap_or(0)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.return_data()
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let mut state = self.state();
+        let program_id = *state.program_stack.last().unwrap();
+        state.return_data = (!data.is_empty()).then(|| (program_id, data.to_vec()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let mut state = self.state();
+        state.logs.push(format!(
+            "Program data: {}",
+            fields
+                .iter()
+                .map(|field| BASE64_STANDARD.encode(field))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ));
+        state
+            .data_events
+            .push(fields.iter().map(|field| field.to_vec()).collect());
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        self.state().program_stack.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::input::{InputAccount, ProgramInput},
+        serial_test::serial,
+        solana_account_info::MAX_PERMITTED_DATA_INCREASE,
+        solana_instruction::AccountMeta,
+        solana_system_interface::{error::SystemError, instruction as system_instruction},
+        solana_sysvar::{
+            program_stubs::{
+                sol_get_epoch_stake, sol_get_return_data, sol_get_stack_height, sol_invoke_signed,
+                sol_log, sol_log_data, sol_set_return_data,
+            },
+            Sysvar,
+        },
+    };
+
+    #[test]
+    #[serial]
+    fn test_sysvars() {
+        let harness = SyscallHarness::new(Pubkey::new_unique());
+        harness.set_clock(Clock {
+            slot: 1,
+            epoch_start_timestamp: 2,
+            epoch: 3,
+            leader_schedule_epoch: 4,
+            unix_timestamp: 5,
+        });
+        harness.set_rent(Rent::free());
+        harness.set_epoch_schedule(EpochSchedule::custom(64, 32, false));
+        harness.set_epoch_rewards(EpochRewards {
+            distribution_starting_block_height: 9,
+            total_rewards: 10,
+            active: true,
+            ..EpochRewards::default()
+        });
+        harness.set_last_restart_slot(LastRestartSlot {
+            last_restart_slot: 11,
+        });
+        harness.install();
+
+        assert_eq!(Clock::get().unwrap().unix_timestamp, 5);
+        assert_eq!(Rent::get().unwrap().minimum_balance(100), 0);
+        assert_eq!(
+            EpochSchedule::get().unwrap(),
+            EpochSchedule::custom(64, 32, false)
+        );
+        let epoch_rewards = EpochRewards::get().unwrap();
+        assert_eq!(epoch_rewards.total_rewards, 10);
+        assert!(epoch_rewards.active);
+        assert_eq!(LastRestartSlot::get().unwrap().last_restart_slot, 11);
+
+        let mut data = [0u8; 8];
+        assert_eq!(
+            solana_sysvar::get_sysvar(&mut data, &sysvar::clock::id(), 8, 8),
+            Ok(())
+        );
+        assert_eq!(u64::from_le_bytes(data), 2);
+        assert_eq!(
+            solana_sysvar::get_sysvar(&mut data, &sysvar::clock::id(), 36, 8),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_logs_and_return_data() {
+        let program_id = Pubkey::new_unique();
+        let harness = SyscallHarness::new(program_id);
+        let vote_addresses = [Pubkey::new_unique(), Pubkey::new_unique()];
+        harness.set_epoch_stake(vote_addresses[0], 100);
+        harness.set_epoch_stake(vote_addresses[1], 200);
+        harness.install();
+
+        sol_log("hello");
+        sol_log_data(&[b"a", b"bc"]);
+        assert_eq!(
+            harness.logs(),
+            ["Program log: hello", "Program data: YQ== YmM="]
+        );
+        assert_eq!(harness.data_events(), [vec![b"a".to_vec(), b"bc".to_vec()]]);
+
+        sol_set_return_data(&[1, 2, 3]);
+        assert_eq!(sol_get_return_data(), Some((program_id, vec![1, 2, 3])));
+        sol_set_return_data(&[]);
+        assert_eq!(sol_get_return_data(), None);
+
+        assert_eq!(sol_get_stack_height(), 1);
+        assert_eq!(
+            sol_get_epoch_stake(vote_addresses[1].as_ref().as_ptr()),
+            200
+        );
+        assert_eq!(
+            sol_get_epoch_stake(Pubkey::new_unique().as_ref().as_ptr()),
+            0
+        );
+        assert_eq!(sol_get_epoch_stake(std::ptr::null()), 300);
+
+        harness.clear();
+        assert!(harness.logs().is_empty());
+        assert!(harness.data_events().is_empty());
+    }
+
+    fn echo(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        assert_eq!(sol_get_stack_height(), 2);
+        assert!(accounts[0].is_signer);
+        sol_log("echo");
+        sol_set_return_data(instruction_data);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_invoke() {
+        let caller = Pubkey::new_unique();
+        let echo_id = Pubkey::new_unique();
+        let harness = SyscallHarness::new(caller);
+        harness.add_program(echo_id, echo);
+        harness.install();
+
+        let (pda, bump) = Pubkey::find_program_address(&[b"seed"], &caller);
+        let readonly = Pubkey::new_unique();
+        let mut input = ProgramInput::new(
+            &caller,
+            &[
+                InputAccount {
+                    key: pda,
+                    is_writable: true,
+                    ..InputAccount::default()
+                },
+                InputAccount {
+                    key: readonly,
+                    ..InputAccount::default()
+                },
+            ],
+            &[],
+        );
+        let (_, accounts, _) = input.deserialize();
+        let instruction =
+            Instruction::new_with_bytes(echo_id, &[7], vec![AccountMeta::new(pda, true)]);
+
+        // the PDA can only sign with the seeds of the caller
+        assert_eq!(
+            sol_invoke_signed(&instruction, &accounts, &[]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            sol_invoke_signed(&instruction, &accounts, &[&[b"seed", &[bump]]]),
+            Ok(())
+        );
+        assert_eq!(sol_get_return_data(), Some((echo_id, vec![7])));
+        assert_eq!(sol_get_stack_height(), 1);
+        assert_eq!(harness.invoked_instructions(), [instruction]);
+        assert_eq!(
+            harness.logs(),
+            [
+                format!("Program {echo_id} invoke [2]"),
+                "Program log: echo".to_string(),
+                format!("Program {echo_id} success"),
+            ]
+        );
+
+        let unknown = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]);
+        assert_eq!(
+            sol_invoke_signed(&unknown, &accounts, &[]),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        let missing_account = Instruction::new_with_bytes(
+            echo_id,
+            &[],
+            vec![AccountMeta::new(Pubkey::new_unique(), false)],
+        );
+        assert_eq!(
+            sol_invoke_signed(&missing_account, &accounts, &[]),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // the callee can not gain privileges the caller does not have
+        let writable_escalated =
+            Instruction::new_with_bytes(echo_id, &[], vec![AccountMeta::new(readonly, false)]);
+        assert_eq!(
+            sol_invoke_signed(&writable_escalated, &accounts, &[]),
+            Err(ProgramError::InvalidArgument)
+        );
+        let signer_escalated = Instruction::new_with_bytes(
+            echo_id,
+            &[],
+            vec![AccountMeta::new_readonly(readonly, true)],
+        );
+        assert_eq!(
+            sol_invoke_signed(&signer_escalated, &accounts, &[&[b"seed", &[bump]]]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    /// Invokes itself as many times as the first byte of the instruction data.
+    fn recurse(
+        program_id: &Pubkey,
+        _accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        match instruction_data.first() {
+            None | Some(0) => Ok(()),
+            Some(depth) => sol_invoke_signed(
+                &Instruction::new_with_bytes(*program_id, &[depth.saturating_sub(1)], vec![]),
+                &[],
+                &[],
+            ),
+        }
+    }
+
+    /// Invokes the program whose address is the instruction data.
+    fn forward(
+        _program_id: &Pubkey,
+        _accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let program_id =
+            Pubkey::try_from(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+        sol_invoke_signed(
+            &Instruction::new_with_bytes(program_id, &[], vec![]),
+            &[],
+            &[],
+        )
+    }
+
+    #[test]
+    #[serial]
+    fn test_invoke_program_stack() {
+        let caller = Pubkey::new_unique();
+        let recurse_id = Pubkey::new_unique();
+        let forward_id = Pubkey::new_unique();
+        let harness = SyscallHarness::new(caller);
+        harness.add_program(caller, recurse);
+        harness.add_program(recurse_id, recurse);
+        harness.add_program(forward_id, forward);
+        harness.install();
+
+        // programs may invoke themselves up to the maximum stack height
+        let instruction = Instruction::new_with_bytes(recurse_id, &[3], vec![]);
+        assert_eq!(sol_invoke_signed(&instruction, &[], &[]), Ok(()));
+        assert_eq!(sol_get_stack_height(), 1);
+        assert_eq!(harness.invoked_instructions().len(), 4);
+        assert!(harness
+            .logs()
+            .contains(&format!("Program {recurse_id} invoke [5]")));
+
+        harness.clear();
+        let instruction = Instruction::new_with_bytes(recurse_id, &[4], vec![]);
+        assert_eq!(
+            sol_invoke_signed(&instruction, &[], &[]),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(sol_get_stack_height(), 1);
+        assert_eq!(harness.invoked_instructions().len(), 4);
+        assert!(harness
+            .logs()
+            .contains(&format!("Program log: {}", InstructionError::CallDepth)));
+
+        // but may not be reentered through another program
+        harness.clear();
+        let instruction = Instruction::new_with_bytes(forward_id, caller.as_ref(), vec![]);
+        assert_eq!(
+            sol_invoke_signed(&instruction, &[], &[]),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(harness.invoked_instructions(), [instruction]);
+        assert_eq!(
+            harness.logs(),
+            [
+                format!("Program {forward_id} invoke [2]"),
+                format!("Program log: {}", InstructionError::ReentrancyNotAllowed),
+                format!(
+                    "Program {forward_id} failed: {}",
+                    ProgramError::InvalidArgument
+                ),
+            ]
+        );
+
+        harness.clear();
+        let instruction = Instruction::new_with_bytes(forward_id, recurse_id.as_ref(), vec![]);
+        assert_eq!(sol_invoke_signed(&instruction, &[], &[]), Ok(()));
+        assert_eq!(harness.invoked_instructions().len(), 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_invoke_system_program() {
+        let caller = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let harness = SyscallHarness::new(caller);
+        harness.set_rent(Rent::free());
+        harness.install();
+
+        let payer = Pubkey::new_unique();
+        let new_account = Pubkey::new_unique();
+        let mut input = ProgramInput::new(
+            &caller,
+            &[
+                InputAccount {
+                    key: payer,
+                    owner: system_program::id(),
+                    lamports: 1_000,
+                    is_signer: true,
+                    is_writable: true,
+                    ..InputAccount::default()
+                },
+                InputAccount {
+                    key: new_account,
+                    owner: system_program::id(),
+                    is_signer: true,
+                    is_writable: true,
+                    ..InputAccount::default()
+                },
+            ],
+            &[],
+        );
+        let (_, accounts, _) = input.deserialize();
+
+        // data can not grow by more than the runtime permits within a call,
+        // and the failed instruction has no effect
+        let instruction = system_instruction::create_account(
+            &payer,
+            &new_account,
+            400,
+            MAX_PERMITTED_DATA_INCREASE as u64 + 1,
+            &owner,
+        );
+        assert_eq!(
+            sol_invoke_signed(&instruction, &accounts, &[]),
+            Err(ProgramError::InvalidRealloc)
+        );
+        assert_eq!(accounts[0].lamports(), 1_000);
+        assert_eq!(accounts[1].lamports(), 0);
+        assert_eq!(accounts[1].data_len(), 0);
+        assert_eq!(accounts[1].owner, &system_program::id());
+
+        let instruction = system_instruction::create_account(&payer, &new_account, 400, 16, &owner);
+        assert_eq!(sol_invoke_signed(&instruction, &accounts, &[]), Ok(()));
+        assert_eq!(accounts[0].lamports(), 600);
+        assert_eq!(accounts[1].lamports(), 400);
+        assert_eq!(accounts[1].data_len(), 16);
+        assert_eq!(accounts[1].owner, &owner);
+
+        // the account is now in use
+        assert_eq!(
+            sol_invoke_signed(&instruction, &accounts, &[]),
+            Err(SystemError::AccountAlreadyInUse.into())
+        );
+        let transfer = system_instruction::transfer(&payer, &new_account, 601);
+        assert_eq!(
+            sol_invoke_signed(&transfer, &accounts, &[]),
+            Err(SystemError::ResultWithNegativeLamports.into())
+        );
+
+        // a rent-paying account can not receive lamports, and the failed
+        // instruction has no effect
+        harness.set_rent(Rent::default());
+        let transfer = system_instruction::transfer(&payer, &new_account, 600);
+        assert_eq!(
+            sol_invoke_signed(&transfer, &accounts, &[]),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(accounts[0].lamports(), 600);
+        assert_eq!(accounts[1].lamports(), 400);
+
+        harness.set_rent(Rent::free());
+        assert_eq!(sol_invoke_signed(&transfer, &accounts, &[]), Ok(()));
+        assert_eq!(accounts[0].lamports(), 0);
+        assert_eq!(accounts[1].lamports(), 1_000);
+    }
+
+    #[test]
+    #[serial]
+    fn test_invoke_system_program_nonce() {
+        let caller = Pubkey::new_unique();
+        let harness = SyscallHarness::new(caller);
+        harness.set_rent(Rent::free());
+        harness.install();
+
+        let payer = Pubkey::new_unique();
+        let nonce = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        #[allow(deprecated)]
+        let recent_blockhashes = sysvar::recent_blockhashes::id();
+        let mut input = ProgramInput::new(
+            &caller,
+            &[
+                InputAccount {
+                    key: payer,
+                    owner: system_program::id(),
+                    lamports: 1_000,
+                    is_signer: true,
+                    is_writable: true,
+                    ..InputAccount::default()
+                },
+                InputAccount {
+                    key: nonce,
+                    owner: system_program::id(),
+                    is_signer: true,
+                    is_writable: true,
+                    ..InputAccount::default()
+                },
+                InputAccount {
+                    key: recent_blockhashes,
+                    owner: sysvar::id(),
+                    ..InputAccount::default()
+                },
+                InputAccount {
+                    key: sysvar::rent::id(),
+                    owner: sysvar::id(),
+                    ..InputAccount::default()
+                },
+            ],
+            &[],
+        );
+        let (_, accounts, _) = input.deserialize();
+
+        let instructions =
+            system_instruction::create_nonce_account(&payer, &nonce, &authority, 400);
+        for instruction in &instructions {
+            assert_eq!(sol_invoke_signed(instruction, &accounts, &[]), Ok(()));
+        }
+        assert_eq!(accounts[1].lamports(), 400);
+        assert_eq!(accounts[1].data_len(), 80);
+
+        // the nonce account is now initialized
+        assert_eq!(
+            sol_invoke_signed(&instructions[1], &accounts, &[]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
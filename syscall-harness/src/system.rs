@@ -0,0 +1,127 @@
+//! The System program, for dispatching cross-program invocations.
+//!
+//! Instructions are executed by [`SystemProcessor`] over copies of the
+//! `AccountInfo`s, and the resulting accounts are written back if the
+//! instruction succeeds, so the checks, including those on rent states, are
+//! the same as those of the runtime. The rent is read from the installed
+//! syscall stubs. The harness has no blockhash, so nonces are derived from the
+//! default blockhash and can not be advanced.
+//!
+//! Allocating account data resizes the account in place, which is only
+//! possible for accounts deserialized from a runtime-layout buffer such as a
+//! [`ProgramInput`](crate::input::ProgramInput). As in the runtime, data can
+//! grow by at most `MAX_PERMITTED_DATA_INCREASE` bytes within a call, and an
+//! instruction that grows it further fails without modifying any account.
+
+use {
+    solana_account::{AccountSharedData, ReadableAccount, WritableAccount},
+    solana_account_info::{AccountInfo, MAX_PERMITTED_DATA_INCREASE},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_program_error::{ProgramError, ProgramResult},
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_system_processor::{ProcessError, SystemProcessor},
+    solana_sysvar::{program_stubs::sol_log, Sysvar},
+};
+
+/// Processes a System program instruction.
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = Instruction::new_with_bytes(
+        *program_id,
+        instruction_data,
+        accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+    );
+    let mut shared_accounts: Vec<(Pubkey, AccountSharedData)> = Vec::new();
+    for account in accounts {
+        if shared_accounts.iter().all(|(key, _)| key != account.key) {
+            shared_accounts.push((*account.key, to_account_shared_data(account)));
+        }
+    }
+
+    let processor = SystemProcessor {
+        rent: Rent::get()?,
+        ..SystemProcessor::default()
+    };
+    processor
+        .process_instruction(&instruction, &mut shared_accounts)
+        .map_err(|err| match err {
+            // the remaining errors are violations of account invariants,
+            // which have no program error of their own
+            ProcessError::Instruction(err) => {
+                ProgramError::try_from(err).unwrap_or(ProgramError::InvalidAccountData)
+            }
+            ProcessError::InsufficientFundsForRent { .. } => {
+                sol_log(&err.to_string());
+                ProgramError::InsufficientFunds
+            }
+        })?;
+
+    let writable_accounts = accounts
+        .iter()
+        .filter(|account| account.is_writable)
+        .map(|account| {
+            let (_, shared_account) = shared_accounts
+                .iter()
+                .find(|(key, _)| key == account.key)
+                .unwrap();
+            (account, shared_account)
+        })
+        .collect::<Vec<_>>();
+    // every account must be writable back before any is modified, so that a
+    // failed instruction has no effect
+    for (account, shared_account) in &writable_accounts {
+        check_write_back(account, shared_account)?;
+    }
+    for (account, shared_account) in writable_accounts {
+        write_back(account, shared_account)?;
+    }
+    Ok(())
+}
+
+fn to_account_shared_data(account: &AccountInfo) -> AccountSharedData {
+    let mut shared_account = AccountSharedData::new(account.lamports(), 0, account.owner);
+    shared_account.set_data_from_slice(&account.data.borrow());
+    shared_account.set_executable(account.executable);
+    shared_account
+}
+
+fn check_write_back(account: &AccountInfo, shared_account: &AccountSharedData) -> ProgramResult {
+    drop(account.try_borrow_mut_lamports()?);
+    drop(account.try_borrow_mut_data()?);
+    // SAFETY: resizing requires the same runtime layout
+    let original_data_len = unsafe { account.original_data_len() };
+    if shared_account
+        .data()
+        .len()
+        .saturating_sub(original_data_len)
+        > MAX_PERMITTED_DATA_INCREASE
+    {
+        return Err(ProgramError::InvalidRealloc);
+    }
+    Ok(())
+}
+
+fn write_back(account: &AccountInfo, shared_account: &AccountSharedData) -> ProgramResult {
+    **account.try_borrow_mut_lamports()? = shared_account.lamports();
+    if account.data_len() != shared_account.data().len() {
+        account.resize(shared_account.data().len())?;
+    }
+    account
+        .try_borrow_mut_data()?
+        .copy_from_slice(shared_account.data());
+    if account.owner != shared_account.owner() {
+        account.assign(shared_account.owner());
+    }
+    Ok(())
+}
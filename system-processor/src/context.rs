@@ -0,0 +1,204 @@
+//! Access to the accounts of an instruction, with the restrictions the runtime
+//! places on how a program may modify them.
+
+use {
+    solana_account::{state_traits::StateMut, AccountSharedData, ReadableAccount, WritableAccount},
+    solana_instruction::error::InstructionError,
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    solana_system_interface::MAX_PERMITTED_DATA_LENGTH,
+    std::collections::HashSet,
+};
+
+/// An account of an instruction, as an index into the accounts of the
+/// transaction along with the privileges the instruction grants it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct InstructionAccount {
+    pub(crate) index: usize,
+    pub(crate) is_signer: bool,
+    pub(crate) is_writable: bool,
+}
+
+pub(crate) struct InstructionContext<'a> {
+    pub(crate) accounts: &'a mut [(Pubkey, AccountSharedData)],
+    pub(crate) instruction_accounts: Vec<InstructionAccount>,
+}
+
+impl InstructionContext<'_> {
+    pub(crate) fn check_number_of_instruction_accounts(
+        &self,
+        expected: usize,
+    ) -> Result<(), InstructionError> {
+        if self.instruction_accounts.len() < expected {
+            return Err(InstructionError::MissingAccount);
+        }
+        Ok(())
+    }
+
+    fn instruction_account(&self, index: usize) -> Result<InstructionAccount, InstructionError> {
+        // the runtime still reports the deprecated error for lookups
+        #[allow(deprecated)]
+        self.instruction_accounts
+            .get(index)
+            .copied()
+            .ok_or(InstructionError::NotEnoughAccountKeys)
+    }
+
+    pub(crate) fn key(&self, index: usize) -> Result<&Pubkey, InstructionError> {
+        let instruction_account = self.instruction_account(index)?;
+        Ok(&self.accounts[instruction_account.index].0)
+    }
+
+    pub(crate) fn is_signer(&self, index: usize) -> Result<bool, InstructionError> {
+        Ok(self.instruction_account(index)?.is_signer)
+    }
+
+    /// The addresses of the accounts that signed the instruction.
+    pub(crate) fn signers(&self) -> HashSet<Pubkey> {
+        self.instruction_accounts
+            .iter()
+            .filter(|instruction_account| instruction_account.is_signer)
+            .map(|instruction_account| self.accounts[instruction_account.index].0)
+            .collect()
+    }
+
+    /// Checks that the account at `index` is the given sysvar.
+    pub(crate) fn check_sysvar(
+        &self,
+        index: usize,
+        sysvar_id: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        if self.key(index)? != sysvar_id {
+            return Err(InstructionError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn borrow(&mut self, index: usize) -> Result<BorrowedAccount<'_>, InstructionError> {
+        let instruction_account = self.instruction_account(index)?;
+        let (key, account) = &mut self.accounts[instruction_account.index];
+        Ok(BorrowedAccount {
+            key,
+            account,
+            is_writable: instruction_account.is_writable,
+        })
+    }
+}
+
+/// An account of the instruction that is being read or modified.
+///
+/// Every modification is checked as the runtime checks it when the System
+/// program modifies an account.
+pub(crate) struct BorrowedAccount<'a> {
+    key: &'a Pubkey,
+    account: &'a mut AccountSharedData,
+    is_writable: bool,
+}
+
+impl BorrowedAccount<'_> {
+    pub(crate) fn key(&self) -> &Pubkey {
+        self.key
+    }
+
+    pub(crate) fn lamports(&self) -> u64 {
+        self.account.lamports()
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        self.account.data()
+    }
+
+    pub(crate) fn owner(&self) -> &Pubkey {
+        self.account.owner()
+    }
+
+    pub(crate) fn is_writable(&self) -> bool {
+        self.is_writable
+    }
+
+    fn is_owned_by_system_program(&self) -> bool {
+        system_program::check_id(self.account.owner())
+    }
+
+    pub(crate) fn set_lamports(&mut self, lamports: u64) -> Result<(), InstructionError> {
+        if !self.is_owned_by_system_program() && lamports < self.lamports() {
+            return Err(InstructionError::ExternalAccountLamportSpend);
+        }
+        if !self.is_writable {
+            return Err(InstructionError::ReadonlyLamportChange);
+        }
+        if self.account.executable() {
+            return Err(InstructionError::ExecutableLamportChange);
+        }
+        self.account.set_lamports(lamports);
+        Ok(())
+    }
+
+    pub(crate) fn checked_add_lamports(&mut self, lamports: u64) -> Result<(), InstructionError> {
+        self.set_lamports(
+            self.lamports()
+                .checked_add(lamports)
+                .ok_or(InstructionError::ArithmeticOverflow)?,
+        )
+    }
+
+    pub(crate) fn checked_sub_lamports(&mut self, lamports: u64) -> Result<(), InstructionError> {
+        self.set_lamports(
+            self.lamports()
+                .checked_sub(lamports)
+                .ok_or(InstructionError::ArithmeticOverflow)?,
+        )
+    }
+
+    fn can_data_be_changed(&self) -> Result<(), InstructionError> {
+        if self.account.executable() {
+            return Err(InstructionError::ExecutableDataModified);
+        }
+        if !self.is_writable {
+            return Err(InstructionError::ReadonlyDataModified);
+        }
+        if !self.is_owned_by_system_program() {
+            return Err(InstructionError::ExternalAccountDataModified);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_data_length(&mut self, new_length: usize) -> Result<(), InstructionError> {
+        if new_length != self.data().len() && !self.is_owned_by_system_program() {
+            return Err(InstructionError::AccountDataSizeChanged);
+        }
+        if new_length as u64 > MAX_PERMITTED_DATA_LENGTH {
+            return Err(InstructionError::InvalidRealloc);
+        }
+        self.can_data_be_changed()?;
+        self.account.resize(new_length, 0);
+        Ok(())
+    }
+
+    pub(crate) fn set_owner(&mut self, owner: &Pubkey) -> Result<(), InstructionError> {
+        if !self.is_owned_by_system_program()
+            || !self.is_writable
+            || self.account.executable()
+            || self.data().iter().any(|byte| *byte != 0)
+        {
+            return Err(InstructionError::ModifiedProgramId);
+        }
+        self.account.set_owner(*owner);
+        Ok(())
+    }
+
+    pub(crate) fn get_state<T>(&self) -> Result<T, InstructionError>
+    where
+        AccountSharedData: StateMut<T>,
+    {
+        self.account.state()
+    }
+
+    pub(crate) fn set_state<T>(&mut self, state: &T) -> Result<(), InstructionError>
+    where
+        AccountSharedData: StateMut<T>,
+    {
+        self.can_data_be_changed()?;
+        self.account.set_state(state)
+    }
+}
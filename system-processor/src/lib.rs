@@ -0,0 +1,807 @@
+//! In-memory execution of System program instructions.
+//!
+//! [`SystemProcessor`] executes [`SystemInstruction`]s against
+//! [`AccountSharedData`] values the way the runtime does: it performs the same
+//! checks, fails with the same errors, including the custom [`SystemError`]s,
+//! and enforces the same restrictions on modifying accounts that are
+//! read-only, executable or owned by another program.
+//!
+//! As in the runtime, an instruction either succeeds or leaves the accounts
+//! untouched, and an instruction that leaves a writable account with a balance
+//! below the rent-exempt minimum for its data is rejected, unless the account
+//! was already rent-paying and only lost lamports.
+//!
+//! ```
+//! use {
+//!     solana_account::{AccountSharedData, ReadableAccount},
+//!     solana_pubkey::Pubkey,
+//!     solana_sdk_ids::system_program,
+//!     solana_system_interface::{error::SystemError, instruction as system_instruction},
+//!     solana_system_processor::SystemProcessor,
+//! };
+//!
+//! let from = Pubkey::new_unique();
+//! let to = Pubkey::new_unique();
+//! let mut accounts = vec![
+//!     (from, AccountSharedData::new(10_000_000, 0, &system_program::id())),
+//!     (to, AccountSharedData::new(0, 0, &system_program::id())),
+//! ];
+//! let processor = SystemProcessor::default();
+//!
+//! let instruction = system_instruction::transfer(&from, &to, 1_000_000);
+//! processor.process_instruction(&instruction, &mut accounts).unwrap();
+//! assert_eq!(accounts[1].1.lamports(), 1_000_000);
+//!
+//! let instruction = system_instruction::transfer(&from, &to, 10_000_000);
+//! let err = processor.process_instruction(&instruction, &mut accounts).unwrap_err();
+//! assert_eq!(err.system_error(), Some(SystemError::ResultWithNegativeLamports));
+//! ```
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+mod context;
+mod nonce;
+mod system;
+
+use {
+    crate::{
+        context::{InstructionAccount, InstructionContext},
+        system::Address,
+    },
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_bincode::limited_deserialize,
+    solana_hash::Hash,
+    solana_instruction::{error::InstructionError, Instruction},
+    solana_packet::PACKET_DATA_SIZE,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_sdk_ids::{incinerator, system_program, sysvar},
+    solana_system_interface::{error::SystemError, instruction::SystemInstruction},
+    std::fmt,
+};
+
+/// An error returned when processing an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessError {
+    /// The instruction failed.
+    Instruction(InstructionError),
+    /// The instruction left an account with insufficient funds for rent.
+    InsufficientFundsForRent { address: Pubkey },
+}
+
+impl ProcessError {
+    /// The System program error the instruction failed with, if any.
+    pub fn system_error(&self) -> Option<SystemError> {
+        match self {
+            Self::Instruction(InstructionError::Custom(code)) => SystemError::try_from(*code).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<InstructionError> for ProcessError {
+    fn from(err: InstructionError) -> Self {
+        Self::Instruction(err)
+    }
+}
+
+impl core::error::Error for ProcessError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Instruction(e) => Some(e),
+            Self::InsufficientFundsForRent { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Instruction(err) => write!(f, "instruction failed: {err}"),
+            Self::InsufficientFundsForRent { address } => {
+                write!(f, "account {address} has insufficient funds for rent")
+            }
+        }
+    }
+}
+
+/// The rent state of an account, as tracked by the runtime.
+#[derive(Debug, PartialEq, Eq)]
+enum RentState {
+    /// The account has no lamports.
+    Uninitialized,
+    /// The account has lamports, but fewer than the rent-exempt minimum.
+    RentPaying { lamports: u64, data_size: usize },
+    /// The account has at least the rent-exempt minimum.
+    RentExempt,
+}
+
+impl RentState {
+    fn of(account: &AccountSharedData, rent: &Rent) -> Self {
+        if account.lamports() == 0 {
+            Self::Uninitialized
+        } else if rent.is_exempt(account.lamports(), account.data().len()) {
+            Self::RentExempt
+        } else {
+            Self::RentPaying {
+                lamports: account.lamports(),
+                data_size: account.data().len(),
+            }
+        }
+    }
+
+    fn transition_allowed_from(&self, pre: &Self) -> bool {
+        match self {
+            Self::Uninitialized | Self::RentExempt => true,
+            Self::RentPaying {
+                lamports: post_lamports,
+                data_size: post_data_size,
+            } => match pre {
+                Self::Uninitialized | Self::RentExempt => false,
+                Self::RentPaying {
+                    lamports: pre_lamports,
+                    data_size: pre_data_size,
+                } => post_data_size == pre_data_size && post_lamports <= pre_lamports,
+            },
+        }
+    }
+}
+
+/// Executes System program instructions in memory.
+///
+/// The fields describe the block the instructions execute in.
+#[derive(Debug, Clone, Default)]
+pub struct SystemProcessor {
+    /// The rent parameters, which determine the minimum balance of nonce
+    /// accounts and of the accounts an instruction modifies.
+    pub rent: Rent,
+    /// The blockhash of the block, from which nonces are derived.
+    pub blockhash: Hash,
+    /// The fee per signature of the block, recorded in nonce accounts.
+    pub lamports_per_signature: u64,
+}
+
+impl SystemProcessor {
+    pub fn new(rent: Rent, blockhash: Hash, lamports_per_signature: u64) -> Self {
+        Self {
+            rent,
+            blockhash,
+            lamports_per_signature,
+        }
+    }
+
+    /// Processes a System program instruction.
+    ///
+    /// Every account the instruction references, including sysvars, must be
+    /// present in `accounts`; only their addresses are used for sysvars. The
+    /// privileges of each account are taken from the instruction. If the
+    /// instruction fails, `accounts` is left unchanged.
+    pub fn process_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &mut [(Pubkey, AccountSharedData)],
+    ) -> Result<(), ProcessError> {
+        if !system_program::check_id(&instruction.program_id) {
+            return Err(InstructionError::IncorrectProgramId.into());
+        }
+        let instruction_accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let index = accounts
+                    .iter()
+                    .position(|(address, _)| *address == meta.pubkey)
+                    .ok_or(InstructionError::MissingAccount)?;
+                // an account has the same privileges wherever it appears
+                let (is_signer, is_writable) = instruction
+                    .accounts
+                    .iter()
+                    .filter(|other| other.pubkey == meta.pubkey)
+                    .fold((false, false), |(is_signer, is_writable), other| {
+                        (
+                            is_signer || other.is_signer,
+                            is_writable || other.is_writable,
+                        )
+                    });
+                Ok(InstructionAccount {
+                    index,
+                    is_signer,
+                    is_writable,
+                })
+            })
+            .collect::<Result<Vec<_>, InstructionError>>()?;
+
+        let mut pre_accounts: Vec<(usize, AccountSharedData)> = Vec::new();
+        for instruction_account in instruction_accounts.iter().filter(|a| a.is_writable) {
+            if pre_accounts
+                .iter()
+                .all(|(index, _)| *index != instruction_account.index)
+            {
+                let account = accounts[instruction_account.index].1.clone();
+                pre_accounts.push((instruction_account.index, account));
+            }
+        }
+
+        let mut context = InstructionContext {
+            accounts: &mut *accounts,
+            instruction_accounts,
+        };
+        let result = self
+            .process(&mut context, &instruction.data)
+            .map_err(ProcessError::from)
+            .and_then(|()| self.check_rent_states(accounts, &pre_accounts));
+        if result.is_err() {
+            for (index, account) in pre_accounts {
+                accounts[index].1 = account;
+            }
+        }
+        result
+    }
+
+    fn check_rent_states(
+        &self,
+        accounts: &[(Pubkey, AccountSharedData)],
+        pre_accounts: &[(usize, AccountSharedData)],
+    ) -> Result<(), ProcessError> {
+        for (index, pre_account) in pre_accounts {
+            let (address, post_account) = &accounts[*index];
+            let pre = RentState::of(pre_account, &self.rent);
+            let post = RentState::of(post_account, &self.rent);
+            if !incinerator::check_id(address) && !post.transition_allowed_from(&pre) {
+                return Err(ProcessError::InsufficientFundsForRent { address: *address });
+            }
+        }
+        Ok(())
+    }
+
+    fn process(
+        &self,
+        context: &mut InstructionContext,
+        instruction_data: &[u8],
+    ) -> Result<(), InstructionError> {
+        let instruction: SystemInstruction =
+            limited_deserialize(instruction_data, PACKET_DATA_SIZE as u64)?;
+        let signers = context.signers();
+        match instruction {
+            SystemInstruction::CreateAccount {
+                lamports,
+                space,
+                owner,
+            } => {
+                context.check_number_of_instruction_accounts(2)?;
+                let to_address = Address::create(context.key(1)?, None)?;
+                system::create_account(
+                    context,
+                    0,
+                    1,
+                    &to_address,
+                    lamports,
+                    space,
+                    &owner,
+                    &signers,
+                )
+            }
+            SystemInstruction::CreateAccountWithSeed {
+                base,
+                seed,
+                lamports,
+                space,
+                owner,
+            } => {
+                context.check_number_of_instruction_accounts(2)?;
+                let to_address = Address::create(context.key(1)?, Some((&base, &seed, &owner)))?;
+                system::create_account(
+                    context,
+                    0,
+                    1,
+                    &to_address,
+                    lamports,
+                    space,
+                    &owner,
+                    &signers,
+                )
+            }
+            SystemInstruction::CreateAccountAllowPrefund {
+                lamports,
+                space,
+                owner,
+            } => {
+                context.check_number_of_instruction_accounts(1)?;
+                let to_address = Address::create(context.key(0)?, None)?;
+                system::allocate_and_assign(
+                    &mut context.borrow(0)?,
+                    &to_address,
+                    space,
+                    &owner,
+                    &signers,
+                )?;
+                if lamports > 0 {
+                    context.check_number_of_instruction_accounts(2)?;
+                    system::transfer(context, 1, 0, lamports)?;
+                }
+                Ok(())
+            }
+            SystemInstruction::Assign { owner } => {
+                context.check_number_of_instruction_accounts(1)?;
+                let address = Address::create(context.key(0)?, None)?;
+                system::assign(&mut context.borrow(0)?, &address, &owner, &signers)
+            }
+            SystemInstruction::AssignWithSeed { base, seed, owner } => {
+                context.check_number_of_instruction_accounts(1)?;
+                let address = Address::create(context.key(0)?, Some((&base, &seed, &owner)))?;
+                system::assign(&mut context.borrow(0)?, &address, &owner, &signers)
+            }
+            SystemInstruction::Transfer { lamports } => {
+                context.check_number_of_instruction_accounts(2)?;
+                system::transfer(context, 0, 1, lamports)
+            }
+            SystemInstruction::TransferWithSeed {
+                lamports,
+                from_seed,
+                from_owner,
+            } => {
+                context.check_number_of_instruction_accounts(3)?;
+                system::transfer_with_seed(context, 0, 1, &from_seed, &from_owner, 2, lamports)
+            }
+            SystemInstruction::Allocate { space } => {
+                context.check_number_of_instruction_accounts(1)?;
+                let address = Address::create(context.key(0)?, None)?;
+                system::allocate(&mut context.borrow(0)?, &address, space, &signers)
+            }
+            SystemInstruction::AllocateWithSeed {
+                base,
+                seed,
+                space,
+                owner,
+            } => {
+                context.check_number_of_instruction_accounts(1)?;
+                let address = Address::create(context.key(0)?, Some((&base, &seed, &owner)))?;
+                system::allocate_and_assign(
+                    &mut context.borrow(0)?,
+                    &address,
+                    space,
+                    &owner,
+                    &signers,
+                )
+            }
+            SystemInstruction::AdvanceNonceAccount => {
+                context.check_number_of_instruction_accounts(1)?;
+                #[allow(deprecated)]
+                context.check_sysvar(1, &sysvar::recent_blockhashes::id())?;
+                nonce::advance_nonce_account(&mut context.borrow(0)?, &signers, self)
+            }
+            SystemInstruction::WithdrawNonceAccount(lamports) => {
+                context.check_number_of_instruction_accounts(2)?;
+                #[allow(deprecated)]
+                context.check_sysvar(2, &sysvar::recent_blockhashes::id())?;
+                context.check_sysvar(3, &sysvar::rent::id())?;
+                nonce::withdraw_nonce_account(context, 0, lamports, 1, &signers, self)
+            }
+            SystemInstruction::InitializeNonceAccount(nonce_authority) => {
+                context.check_number_of_instruction_accounts(1)?;
+                #[allow(deprecated)]
+                context.check_sysvar(1, &sysvar::recent_blockhashes::id())?;
+                context.check_sysvar(2, &sysvar::rent::id())?;
+                nonce::initialize_nonce_account(&mut context.borrow(0)?, &nonce_authority, self)
+            }
+            SystemInstruction::AuthorizeNonceAccount(nonce_authority) => {
+                context.check_number_of_instruction_accounts(1)?;
+                nonce::authorize_nonce_account(&mut context.borrow(0)?, &nonce_authority, &signers)
+            }
+            SystemInstruction::UpgradeNonceAccount => {
+                context.check_number_of_instruction_accounts(1)?;
+                nonce::upgrade_nonce_account(&mut context.borrow(0)?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_account::WritableAccount,
+        solana_nonce::{
+            state::{DurableNonce, State},
+            versions::Versions,
+        },
+        solana_nonce_account::verify_nonce_account,
+        solana_system_interface::instruction as system_instruction,
+    };
+
+    fn system_account(lamports: u64) -> AccountSharedData {
+        AccountSharedData::new(lamports, 0, &system_program::id())
+    }
+
+    fn processor() -> SystemProcessor {
+        SystemProcessor::new(Rent::default(), Hash::new_from_array([1; 32]), 5_000)
+    }
+
+    #[test]
+    fn test_create_account() {
+        let processor = processor();
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let lamports = processor.rent.minimum_balance(16);
+        let mut accounts = vec![
+            (from, system_account(10 * lamports)),
+            (to, system_account(0)),
+        ];
+
+        let instruction = system_instruction::create_account(&from, &to, lamports, 16, &owner);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[0].1.lamports(), 9 * lamports);
+        assert_eq!(accounts[1].1.lamports(), lamports);
+        assert_eq!(accounts[1].1.data(), &[0; 16]);
+        assert_eq!(accounts[1].1.owner(), &owner);
+
+        // the account is now in use, and the failed instruction has no effect
+        let err = processor
+            .process_instruction(&instruction, &mut accounts)
+            .unwrap_err();
+        assert_eq!(err.system_error(), Some(SystemError::AccountAlreadyInUse));
+        assert_eq!(accounts[0].1.lamports(), 9 * lamports);
+
+        // the new account must sign
+        let other = Pubkey::new_unique();
+        accounts.push((other, system_account(0)));
+        let mut instruction =
+            system_instruction::create_account(&from, &other, lamports, 16, &owner);
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(InstructionError::MissingRequiredSignature.into())
+        );
+
+        // the new account must be rent exempt
+        let instruction =
+            system_instruction::create_account(&from, &other, lamports - 1, 16, &owner);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(ProcessError::InsufficientFundsForRent { address: other })
+        );
+        assert_eq!(accounts[2].1, system_account(0));
+    }
+
+    #[test]
+    fn test_create_account_with_seed() {
+        let processor = processor();
+        let from = Pubkey::new_unique();
+        let base = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let to = Pubkey::create_with_seed(&base, "seed", &owner).unwrap();
+        let lamports = processor.rent.minimum_balance(0);
+        let mut accounts = vec![
+            (from, system_account(10 * lamports)),
+            (to, system_account(0)),
+            (base, system_account(0)),
+        ];
+
+        let instruction = system_instruction::create_account_with_seed(
+            &from,
+            &to,
+            &base,
+            "other seed",
+            lamports,
+            0,
+            &owner,
+        );
+        let err = processor
+            .process_instruction(&instruction, &mut accounts)
+            .unwrap_err();
+        assert_eq!(
+            err.system_error(),
+            Some(SystemError::AddressWithSeedMismatch)
+        );
+
+        let instruction = system_instruction::create_account_with_seed(
+            &from, &to, &base, "seed", lamports, 0, &owner,
+        );
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[1].1.owner(), &owner);
+        assert_eq!(accounts[1].1.lamports(), lamports);
+    }
+
+    #[test]
+    fn test_allocate_and_assign() {
+        let processor = processor();
+        let address = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let lamports = processor.rent.minimum_balance(32);
+        let mut accounts = vec![(address, system_account(lamports))];
+
+        let instruction = system_instruction::allocate(&address, 32);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[0].1.data().len(), 32);
+
+        let err = processor
+            .process_instruction(&instruction, &mut accounts)
+            .unwrap_err();
+        assert_eq!(err.system_error(), Some(SystemError::AccountAlreadyInUse));
+
+        let instruction = system_instruction::allocate(&address, u64::MAX);
+        let mut fresh = vec![(address, system_account(lamports))];
+        let err = processor
+            .process_instruction(&instruction, &mut fresh)
+            .unwrap_err();
+        assert_eq!(
+            err.system_error(),
+            Some(SystemError::InvalidAccountDataLength)
+        );
+
+        // data of a new owner must be zero-initialized
+        accounts[0].1.data_as_mut_slice()[0] = 1;
+        let instruction = system_instruction::assign(&address, &owner);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(InstructionError::ModifiedProgramId.into())
+        );
+        accounts[0].1.data_as_mut_slice()[0] = 0;
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[0].1.owner(), &owner);
+
+        // accounts with data cannot send lamports
+        let instruction = system_instruction::transfer(&address, &Pubkey::new_unique(), 1);
+        accounts.push((instruction.accounts[1].pubkey, system_account(0)));
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(InstructionError::InvalidArgument.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer() {
+        let processor = processor();
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let lamports = processor.rent.minimum_balance(0);
+        let mut accounts = vec![
+            (from, system_account(3 * lamports)),
+            (to, system_account(0)),
+        ];
+
+        let instruction = system_instruction::transfer(&from, &to, 4 * lamports);
+        let err = processor
+            .process_instruction(&instruction, &mut accounts)
+            .unwrap_err();
+        assert_eq!(
+            err.system_error(),
+            Some(SystemError::ResultWithNegativeLamports)
+        );
+
+        // the recipient would be left rent paying
+        let instruction = system_instruction::transfer(&from, &to, 1);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(ProcessError::InsufficientFundsForRent { address: to })
+        );
+
+        // the whole balance can be transferred, leaving the sender uninitialized
+        let instruction = system_instruction::transfer(&from, &to, 3 * lamports);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[0].1.lamports(), 0);
+        assert_eq!(accounts[1].1.lamports(), 3 * lamports);
+
+        // the recipient must be writable
+        let mut instruction = system_instruction::transfer(&to, &from, lamports);
+        instruction.accounts[1].is_writable = false;
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(InstructionError::ReadonlyLamportChange.into())
+        );
+
+        let instruction = system_instruction::transfer(&to, &Pubkey::new_unique(), lamports);
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Err(InstructionError::MissingAccount.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer_with_seed() {
+        let processor = processor();
+        let base = Pubkey::new_unique();
+        let owner = system_program::id();
+        let from = Pubkey::create_with_seed(&base, "seed", &owner).unwrap();
+        let to = Pubkey::new_unique();
+        let lamports = processor.rent.minimum_balance(0);
+        let mut accounts = vec![
+            (from, system_account(2 * lamports)),
+            (base, system_account(0)),
+            (to, system_account(0)),
+        ];
+
+        let instruction = system_instruction::transfer_with_seed(
+            &from,
+            &base,
+            "seed".to_string(),
+            &owner,
+            &to,
+            lamports,
+        );
+        assert_eq!(
+            processor.process_instruction(&instruction, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[0].1.lamports(), lamports);
+        assert_eq!(accounts[2].1.lamports(), lamports);
+    }
+
+    fn nonce_state(account: &AccountSharedData) -> State {
+        let versions: Versions = account.deserialize_data().unwrap();
+        versions.state().clone()
+    }
+
+    #[test]
+    fn test_nonce_lifecycle() {
+        let mut processor = processor();
+        let payer = Pubkey::new_unique();
+        let nonce = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let lamports = processor.rent.minimum_balance(State::size());
+        #[allow(deprecated)]
+        let recent_blockhashes = sysvar::recent_blockhashes::id();
+        let mut accounts = vec![
+            (payer, system_account(10 * lamports)),
+            (nonce, system_account(0)),
+            (authority, system_account(0)),
+            (recent_blockhashes, AccountSharedData::default()),
+            (sysvar::rent::id(), AccountSharedData::default()),
+        ];
+
+        for instruction in
+            system_instruction::create_nonce_account(&payer, &nonce, &authority, 2 * lamports)
+        {
+            assert_eq!(
+                processor.process_instruction(&instruction, &mut accounts),
+                Ok(())
+            );
+        }
+        let durable_nonce = DurableNonce::from_blockhash(&processor.blockhash);
+        assert!(verify_nonce_account(&accounts[1].1, durable_nonce.as_hash()).is_some());
+
+        // the nonce cannot be advanced within the same block
+        let advance = system_instruction::advance_nonce_account(&nonce, &authority);
+        let err = processor
+            .process_instruction(&advance, &mut accounts)
+            .unwrap_err();
+        assert_eq!(
+            err.system_error(),
+            Some(SystemError::NonceBlockhashNotExpired)
+        );
+
+        // the sysvar must be passed
+        let mut truncated = advance.clone();
+        truncated.accounts.truncate(1);
+        #[allow(deprecated)]
+        let err = InstructionError::NotEnoughAccountKeys;
+        assert_eq!(
+            processor.process_instruction(&truncated, &mut accounts),
+            Err(err.into())
+        );
+
+        processor.blockhash = Hash::new_from_array([2; 32]);
+        processor.lamports_per_signature = 10_000;
+        assert_eq!(
+            processor.process_instruction(&advance, &mut accounts),
+            Ok(())
+        );
+        let State::Initialized(data) = nonce_state(&accounts[1].1) else {
+            panic!("nonce account is not initialized");
+        };
+        assert_eq!(
+            data.durable_nonce,
+            DurableNonce::from_blockhash(&processor.blockhash)
+        );
+        assert_eq!(data.get_lamports_per_signature(), 10_000);
+
+        // only the authority can advance the nonce, or hand it over
+        let new_authority = Pubkey::new_unique();
+        accounts.push((new_authority, system_account(0)));
+        let advance = system_instruction::advance_nonce_account(&nonce, &new_authority);
+        assert_eq!(
+            processor.process_instruction(&advance, &mut accounts),
+            Err(InstructionError::MissingRequiredSignature.into())
+        );
+        let authorize =
+            system_instruction::authorize_nonce_account(&nonce, &authority, &new_authority);
+        assert_eq!(
+            processor.process_instruction(&authorize, &mut accounts),
+            Ok(())
+        );
+
+        // the account must stay rent exempt unless it is closed
+        let withdraw = system_instruction::withdraw_nonce_account(
+            &nonce,
+            &new_authority,
+            &payer,
+            lamports + 1,
+        );
+        assert_eq!(
+            processor.process_instruction(&withdraw, &mut accounts),
+            Err(InstructionError::InsufficientFunds.into())
+        );
+        let withdraw =
+            system_instruction::withdraw_nonce_account(&nonce, &new_authority, &payer, lamports);
+        assert_eq!(
+            processor.process_instruction(&withdraw, &mut accounts),
+            Ok(())
+        );
+
+        // closing the account requires a new block
+        let withdraw =
+            system_instruction::withdraw_nonce_account(&nonce, &new_authority, &payer, lamports);
+        let err = processor
+            .process_instruction(&withdraw, &mut accounts)
+            .unwrap_err();
+        assert_eq!(
+            err.system_error(),
+            Some(SystemError::NonceBlockhashNotExpired)
+        );
+        processor.blockhash = Hash::new_from_array([3; 32]);
+        assert_eq!(
+            processor.process_instruction(&withdraw, &mut accounts),
+            Ok(())
+        );
+        assert_eq!(accounts[1].1.lamports(), 0);
+        assert_eq!(nonce_state(&accounts[1].1), State::Uninitialized);
+        assert_eq!(accounts[0].1.lamports(), 10 * lamports);
+    }
+
+    #[test]
+    fn test_initialize_nonce_account_insufficient_funds() {
+        let processor = processor();
+        let nonce = Pubkey::new_unique();
+        let lamports = processor.rent.minimum_balance(State::size());
+        #[allow(deprecated)]
+        let recent_blockhashes = sysvar::recent_blockhashes::id();
+        let nonce_account = AccountSharedData::new_data_with_space(
+            lamports - 1,
+            &Versions::new(State::Uninitialized),
+            State::size(),
+            &system_program::id(),
+        )
+        .unwrap();
+        let mut accounts = vec![
+            (nonce, nonce_account),
+            (recent_blockhashes, AccountSharedData::default()),
+            (sysvar::rent::id(), AccountSharedData::default()),
+        ];
+
+        let initialize = system_instruction::create_nonce_account(
+            &Pubkey::new_unique(),
+            &nonce,
+            &Pubkey::new_unique(),
+            lamports,
+        )
+        .pop()
+        .unwrap();
+        assert_eq!(
+            processor.process_instruction(&initialize, &mut accounts),
+            Err(InstructionError::InsufficientFunds.into())
+        );
+
+        // sysvar accounts are checked by address
+        let mut initialize = initialize;
+        initialize.accounts[2].pubkey = recent_blockhashes;
+        assert_eq!(
+            processor.process_instruction(&initialize, &mut accounts),
+            Err(InstructionError::InvalidArgument.into())
+        );
+    }
+}
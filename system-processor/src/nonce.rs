@@ -0,0 +1,154 @@
+//! Durable transaction nonce instructions.
+
+use {
+    crate::{
+        context::{BorrowedAccount, InstructionContext},
+        SystemProcessor,
+    },
+    solana_instruction::error::InstructionError,
+    solana_nonce::{
+        state::{Data, DurableNonce, State},
+        versions::{AuthorizeNonceError, Versions},
+    },
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::system_program,
+    solana_system_interface::error::SystemError,
+    std::collections::HashSet,
+};
+
+pub(crate) fn advance_nonce_account(
+    account: &mut BorrowedAccount,
+    signers: &HashSet<Pubkey>,
+    processor: &SystemProcessor,
+) -> Result<(), InstructionError> {
+    if !account.is_writable() {
+        return Err(InstructionError::InvalidArgument);
+    }
+    let state: Versions = account.get_state()?;
+    match state.state() {
+        State::Initialized(data) => {
+            if !signers.contains(&data.authority) {
+                return Err(InstructionError::MissingRequiredSignature);
+            }
+            let next_durable_nonce = DurableNonce::from_blockhash(&processor.blockhash);
+            if data.durable_nonce == next_durable_nonce {
+                return Err(SystemError::NonceBlockhashNotExpired.into());
+            }
+            let new_data = Data::new(
+                data.authority,
+                next_durable_nonce,
+                processor.lamports_per_signature,
+            );
+            account.set_state(&Versions::new(State::Initialized(new_data)))
+        }
+        State::Uninitialized => Err(InstructionError::InvalidAccountData),
+    }
+}
+
+pub(crate) fn withdraw_nonce_account(
+    context: &mut InstructionContext,
+    from_index: usize,
+    lamports: u64,
+    to_index: usize,
+    signers: &HashSet<Pubkey>,
+    processor: &SystemProcessor,
+) -> Result<(), InstructionError> {
+    {
+        let mut from = context.borrow(from_index)?;
+        if !from.is_writable() {
+            return Err(InstructionError::InvalidArgument);
+        }
+        let state: Versions = from.get_state()?;
+        let signer = match state.state() {
+            State::Uninitialized => {
+                if lamports > from.lamports() {
+                    return Err(InstructionError::InsufficientFunds);
+                }
+                *from.key()
+            }
+            State::Initialized(data) => {
+                if lamports == from.lamports() {
+                    // closing the account requires the nonce to have been used
+                    let durable_nonce = DurableNonce::from_blockhash(&processor.blockhash);
+                    if data.durable_nonce == durable_nonce {
+                        return Err(SystemError::NonceBlockhashNotExpired.into());
+                    }
+                    from.set_state(&Versions::new(State::Uninitialized))?;
+                } else {
+                    let min_balance = processor.rent.minimum_balance(from.data().len());
+                    let amount = lamports
+                        .checked_add(min_balance)
+                        .ok_or(InstructionError::InsufficientFunds)?;
+                    if amount > from.lamports() {
+                        return Err(InstructionError::InsufficientFunds);
+                    }
+                }
+                data.authority
+            }
+        };
+        if !signers.contains(&signer) {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        from.checked_sub_lamports(lamports)?;
+    }
+    context.borrow(to_index)?.checked_add_lamports(lamports)
+}
+
+pub(crate) fn initialize_nonce_account(
+    account: &mut BorrowedAccount,
+    nonce_authority: &Pubkey,
+    processor: &SystemProcessor,
+) -> Result<(), InstructionError> {
+    if !account.is_writable() {
+        return Err(InstructionError::InvalidArgument);
+    }
+    let state: Versions = account.get_state()?;
+    match state.state() {
+        State::Uninitialized => {
+            let min_balance = processor.rent.minimum_balance(account.data().len());
+            if account.lamports() < min_balance {
+                return Err(InstructionError::InsufficientFunds);
+            }
+            let durable_nonce = DurableNonce::from_blockhash(&processor.blockhash);
+            let data = Data::new(
+                *nonce_authority,
+                durable_nonce,
+                processor.lamports_per_signature,
+            );
+            account.set_state(&Versions::new(State::Initialized(data)))
+        }
+        State::Initialized(_) => Err(InstructionError::InvalidAccountData),
+    }
+}
+
+pub(crate) fn authorize_nonce_account(
+    account: &mut BorrowedAccount,
+    nonce_authority: &Pubkey,
+    signers: &HashSet<Pubkey>,
+) -> Result<(), InstructionError> {
+    if !account.is_writable() {
+        return Err(InstructionError::InvalidArgument);
+    }
+    let state: Versions = account.get_state()?;
+    match state.authorize(signers, *nonce_authority) {
+        Ok(state) => account.set_state(&state),
+        Err(AuthorizeNonceError::Uninitialized) => Err(InstructionError::InvalidAccountData),
+        Err(AuthorizeNonceError::MissingRequiredSignature(_)) => {
+            Err(InstructionError::MissingRequiredSignature)
+        }
+    }
+}
+
+pub(crate) fn upgrade_nonce_account(account: &mut BorrowedAccount) -> Result<(), InstructionError> {
+    if !system_program::check_id(account.owner()) {
+        return Err(InstructionError::InvalidAccountOwner);
+    }
+    if !account.is_writable() {
+        return Err(InstructionError::InvalidArgument);
+    }
+    let state: Versions = account.get_state()?;
+    match state.upgrade() {
+        Some(state) => account.set_state(&state),
+        None => Err(InstructionError::InvalidArgument),
+    }
+}
@@ -0,0 +1,168 @@
+//! Account creation, allocation, assignment and transfers.
+
+use {
+    crate::context::{BorrowedAccount, InstructionContext},
+    solana_instruction::error::InstructionError,
+    solana_pubkey::{Pubkey, PubkeyError},
+    solana_sdk_ids::system_program,
+    solana_system_interface::{error::SystemError, MAX_PERMITTED_DATA_LENGTH},
+    std::collections::HashSet,
+};
+
+/// The address of an account to allocate or assign, along with the base
+/// address that signs on its behalf if it is derived from a seed.
+pub(crate) struct Address {
+    address: Pubkey,
+    base: Option<Pubkey>,
+}
+
+impl Address {
+    fn is_signer(&self, signers: &HashSet<Pubkey>) -> bool {
+        signers.contains(self.base.as_ref().unwrap_or(&self.address))
+    }
+
+    pub(crate) fn create(
+        address: &Pubkey,
+        with_seed: Option<(&Pubkey, &str, &Pubkey)>,
+    ) -> Result<Self, InstructionError> {
+        let base = match with_seed {
+            Some((base, seed, owner)) => {
+                let address_with_seed = create_with_seed(base, seed, owner)?;
+                if *address != address_with_seed {
+                    return Err(SystemError::AddressWithSeedMismatch.into());
+                }
+                Some(*base)
+            }
+            None => None,
+        };
+        Ok(Self {
+            address: *address,
+            base,
+        })
+    }
+}
+
+fn create_with_seed(base: &Pubkey, seed: &str, owner: &Pubkey) -> Result<Pubkey, InstructionError> {
+    Pubkey::create_with_seed(base, seed, owner).map_err(|err| match err {
+        PubkeyError::MaxSeedLengthExceeded => InstructionError::MaxSeedLengthExceeded,
+        PubkeyError::InvalidSeeds => InstructionError::InvalidSeeds,
+        PubkeyError::IllegalOwner => InstructionError::IllegalOwner,
+    })
+}
+
+pub(crate) fn allocate(
+    account: &mut BorrowedAccount,
+    address: &Address,
+    space: u64,
+    signers: &HashSet<Pubkey>,
+) -> Result<(), InstructionError> {
+    if !address.is_signer(signers) {
+        return Err(InstructionError::MissingRequiredSignature);
+    }
+    // an account that has data or is owned by another program is in use
+    if !account.data().is_empty() || !system_program::check_id(account.owner()) {
+        return Err(SystemError::AccountAlreadyInUse.into());
+    }
+    if space > MAX_PERMITTED_DATA_LENGTH {
+        return Err(SystemError::InvalidAccountDataLength.into());
+    }
+    account.set_data_length(space as usize)
+}
+
+pub(crate) fn assign(
+    account: &mut BorrowedAccount,
+    address: &Address,
+    owner: &Pubkey,
+    signers: &HashSet<Pubkey>,
+) -> Result<(), InstructionError> {
+    if account.owner() == owner {
+        return Ok(());
+    }
+    if !address.is_signer(signers) {
+        return Err(InstructionError::MissingRequiredSignature);
+    }
+    account.set_owner(owner)
+}
+
+pub(crate) fn allocate_and_assign(
+    account: &mut BorrowedAccount,
+    address: &Address,
+    space: u64,
+    owner: &Pubkey,
+    signers: &HashSet<Pubkey>,
+) -> Result<(), InstructionError> {
+    allocate(account, address, space, signers)?;
+    assign(account, address, owner, signers)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_account(
+    context: &mut InstructionContext,
+    from_index: usize,
+    to_index: usize,
+    to_address: &Address,
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+    signers: &HashSet<Pubkey>,
+) -> Result<(), InstructionError> {
+    {
+        let mut to = context.borrow(to_index)?;
+        if to.lamports() > 0 {
+            return Err(SystemError::AccountAlreadyInUse.into());
+        }
+        allocate_and_assign(&mut to, to_address, space, owner, signers)?;
+    }
+    transfer(context, from_index, to_index, lamports)
+}
+
+/// Moves lamports between accounts whose signatures have been checked.
+pub(crate) fn transfer_verified(
+    context: &mut InstructionContext,
+    from_index: usize,
+    to_index: usize,
+    lamports: u64,
+) -> Result<(), InstructionError> {
+    {
+        let mut from = context.borrow(from_index)?;
+        if !from.data().is_empty() {
+            return Err(InstructionError::InvalidArgument);
+        }
+        if lamports > from.lamports() {
+            return Err(SystemError::ResultWithNegativeLamports.into());
+        }
+        from.checked_sub_lamports(lamports)?;
+    }
+    context.borrow(to_index)?.checked_add_lamports(lamports)
+}
+
+pub(crate) fn transfer(
+    context: &mut InstructionContext,
+    from_index: usize,
+    to_index: usize,
+    lamports: u64,
+) -> Result<(), InstructionError> {
+    if !context.is_signer(from_index)? {
+        return Err(InstructionError::MissingRequiredSignature);
+    }
+    transfer_verified(context, from_index, to_index, lamports)
+}
+
+pub(crate) fn transfer_with_seed(
+    context: &mut InstructionContext,
+    from_index: usize,
+    from_base_index: usize,
+    from_seed: &str,
+    from_owner: &Pubkey,
+    to_index: usize,
+    lamports: u64,
+) -> Result<(), InstructionError> {
+    if !context.is_signer(from_base_index)? {
+        return Err(InstructionError::MissingRequiredSignature);
+    }
+    let address_from_seed = create_with_seed(context.key(from_base_index)?, from_seed, from_owner)?;
+    if *context.key(from_index)? != address_from_seed {
+        return Err(SystemError::AddressWithSeedMismatch.into());
+    }
+    transfer_verified(context, from_index, to_index, lamports)
+}
@@ -1,6 +1,8 @@
 //! The `system_transaction` module provides functionality for creating system transactions.
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod nonce;
+
 use {
     solana_hash::Hash, solana_keypair::Keypair, solana_message::Message, solana_pubkey::Pubkey,
     solana_signer::Signer, solana_system_interface::instruction as system_instruction,
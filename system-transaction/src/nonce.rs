@@ -0,0 +1,394 @@
+//! Offline helpers for transactions that use a durable nonce.
+//!
+//! A [`NonceAccount`] is validated from the contents of a nonce account, which
+//! can be fetched ahead of time and carried to an offline signer. It builds and
+//! signs messages that use the stored nonce in place of a recent blockhash,
+//! checks that a transaction uses the nonce correctly, and simulates the
+//! advance of the nonce that executing the transaction performs.
+
+use {
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+    solana_message::Message,
+    solana_nonce::{
+        state::{Data, State},
+        versions::Versions,
+    },
+    solana_pubkey::Pubkey,
+    solana_sdk_ids::{system_program, sysvar},
+    solana_signer::{signers::Signers, SignerError},
+    solana_system_interface::instruction as system_instruction,
+    solana_system_processor::{ProcessError, SystemProcessor},
+    solana_transaction::{uses_durable_nonce, Transaction},
+    std::fmt,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NonceError {
+    /// The account is not owned by the System program.
+    InvalidOwner(Pubkey),
+    /// The account data is not a nonce state.
+    InvalidAccountData,
+    /// The nonce account has not been initialized.
+    Uninitialized,
+    /// The nonce account holds a legacy nonce, which must be upgraded before
+    /// it can be used.
+    LegacyNonce,
+    /// The transaction does not start with an advance nonce instruction.
+    NotNonced,
+    /// The transaction advances a different nonce account.
+    NonceAccountMismatch,
+    /// The nonce account is not writable in the transaction.
+    NonceAccountNotWritable,
+    /// The blockhash of the transaction is not the stored nonce.
+    BlockhashMismatch,
+    /// The nonce authority does not sign the advance nonce instruction.
+    MissingAuthoritySignature,
+    /// The transaction could not be signed.
+    Signer(SignerError),
+    /// The nonce could not be advanced.
+    Advance(ProcessError),
+}
+
+impl core::error::Error for NonceError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Signer(e) => Some(e),
+            Self::Advance(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NonceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidOwner(owner) => {
+                write!(
+                    f,
+                    "nonce account is owned by {owner}, not the System program"
+                )
+            }
+            Self::InvalidAccountData => f.write_str("account data is not a nonce state"),
+            Self::Uninitialized => f.write_str("nonce account is uninitialized"),
+            Self::LegacyNonce => f.write_str("nonce account holds a legacy nonce"),
+            Self::NotNonced => {
+                f.write_str("transaction does not start with an advance nonce instruction")
+            }
+            Self::NonceAccountMismatch => {
+                f.write_str("transaction advances a different nonce account")
+            }
+            Self::NonceAccountNotWritable => f.write_str("nonce account is not writable"),
+            Self::BlockhashMismatch => {
+                f.write_str("transaction blockhash does not match the stored nonce")
+            }
+            Self::MissingAuthoritySignature => f.write_str("nonce authority does not sign"),
+            Self::Signer(err) => write!(f, "failed to sign transaction: {err}"),
+            Self::Advance(err) => write!(f, "failed to advance nonce: {err}"),
+        }
+    }
+}
+
+/// An initialized nonce account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonceAccount {
+    address: Pubkey,
+    account: AccountSharedData,
+    data: Data,
+}
+
+impl NonceAccount {
+    /// Validates the contents of the nonce account at `address`.
+    pub fn new(address: Pubkey, account: AccountSharedData) -> Result<Self, NonceError> {
+        if !system_program::check_id(account.owner()) {
+            return Err(NonceError::InvalidOwner(*account.owner()));
+        }
+        let versions: Versions = account
+            .deserialize_data()
+            .map_err(|_| NonceError::InvalidAccountData)?;
+        let state = match versions {
+            Versions::Legacy(_) => return Err(NonceError::LegacyNonce),
+            Versions::Current(state) => *state,
+        };
+        let data = match state {
+            State::Uninitialized => return Err(NonceError::Uninitialized),
+            State::Initialized(data) => data,
+        };
+        Ok(Self {
+            address,
+            account,
+            data,
+        })
+    }
+
+    pub fn address(&self) -> &Pubkey {
+        &self.address
+    }
+
+    pub fn account(&self) -> &AccountSharedData {
+        &self.account
+    }
+
+    pub fn authority(&self) -> &Pubkey {
+        &self.data.authority
+    }
+
+    /// The stored nonce, used as the blockhash of transactions.
+    pub fn blockhash(&self) -> Hash {
+        self.data.blockhash()
+    }
+
+    /// The fee per signature of transactions that use the nonce.
+    pub fn lamports_per_signature(&self) -> u64 {
+        self.data.get_lamports_per_signature()
+    }
+
+    /// Creates a message that executes `instructions` using the nonce.
+    ///
+    /// The advance nonce instruction is placed first, and the stored nonce is
+    /// set as the blockhash of the message.
+    pub fn message(&self, instructions: &[Instruction], payer: Option<&Pubkey>) -> Message {
+        let mut message = Message::new_with_nonce(
+            instructions.to_vec(),
+            payer,
+            &self.address,
+            &self.data.authority,
+        );
+        message.recent_blockhash = self.blockhash();
+        message
+    }
+
+    /// Creates a transaction that executes `instructions` using the nonce,
+    /// and signs it with all of its required signers.
+    pub fn sign_transaction<T: Signers + ?Sized>(
+        &self,
+        instructions: &[Instruction],
+        payer: Option<&Pubkey>,
+        signers: &T,
+    ) -> Result<Transaction, NonceError> {
+        let mut transaction = Transaction::new_unsigned(self.message(instructions, payer));
+        transaction
+            .try_sign(signers, self.blockhash())
+            .map_err(NonceError::Signer)?;
+        Ok(transaction)
+    }
+
+    /// Checks that a transaction advances this nonce account and uses the
+    /// stored nonce as its blockhash.
+    ///
+    /// Signatures are not verified.
+    pub fn verify_transaction(&self, transaction: &Transaction) -> Result<(), NonceError> {
+        let message = transaction.message();
+        let instruction = uses_durable_nonce(transaction).ok_or(NonceError::NotNonced)?;
+        let nonce_index = instruction
+            .accounts
+            .first()
+            .map(|index| *index as usize)
+            .filter(|index| message.account_keys.get(*index) == Some(&self.address))
+            .ok_or(NonceError::NonceAccountMismatch)?;
+        if !message.is_maybe_writable(nonce_index, None) {
+            return Err(NonceError::NonceAccountNotWritable);
+        }
+        if message.recent_blockhash != self.blockhash() {
+            return Err(NonceError::BlockhashMismatch);
+        }
+        let authority_signs = instruction.accounts.iter().any(|index| {
+            let index = *index as usize;
+            message.account_keys.get(index) == Some(&self.data.authority)
+                && message.is_signer(index)
+        });
+        if !authority_signs {
+            return Err(NonceError::MissingAuthoritySignature);
+        }
+        Ok(())
+    }
+
+    /// Returns the nonce account after a transaction using it executes in a
+    /// block with the given blockhash and fee per signature.
+    ///
+    /// Fails if the nonce was stored in the same block, since it is only
+    /// replaced by a nonce derived from a newer blockhash.
+    pub fn advance(
+        &self,
+        blockhash: Hash,
+        lamports_per_signature: u64,
+    ) -> Result<AccountSharedData, NonceError> {
+        let processor = SystemProcessor {
+            blockhash,
+            lamports_per_signature,
+            ..SystemProcessor::default()
+        };
+        let instruction =
+            system_instruction::advance_nonce_account(&self.address, self.authority());
+        #[allow(deprecated)]
+        let mut accounts = vec![
+            (self.address, self.account.clone()),
+            (
+                sysvar::recent_blockhashes::id(),
+                AccountSharedData::default(),
+            ),
+        ];
+        if self.data.authority != self.address {
+            accounts.push((self.data.authority, AccountSharedData::default()));
+        }
+        processor
+            .process_instruction(&instruction, &mut accounts)
+            .map_err(NonceError::Advance)?;
+        Ok(accounts.swap_remove(0).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, solana_account::WritableAccount, solana_keypair::Keypair,
+        solana_nonce::state::DurableNonce, solana_signer::Signer,
+        solana_system_interface::error::SystemError,
+    };
+
+    fn nonce_account(authority: &Pubkey, blockhash: &Hash) -> AccountSharedData {
+        let state =
+            State::new_initialized(authority, DurableNonce::from_blockhash(blockhash), 5_000);
+        AccountSharedData::new_data(1_000_000, &Versions::new(state), &system_program::id())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_nonce_account() {
+        let authority = Pubkey::new_unique();
+        let blockhash = Hash::new_from_array([1; 32]);
+        let nonce =
+            NonceAccount::new(Pubkey::new_unique(), nonce_account(&authority, &blockhash)).unwrap();
+        assert_eq!(nonce.authority(), &authority);
+        assert_eq!(
+            nonce.blockhash(),
+            *DurableNonce::from_blockhash(&blockhash).as_hash()
+        );
+        assert_eq!(nonce.lamports_per_signature(), 5_000);
+
+        let owner = Pubkey::new_unique();
+        let mut account = nonce_account(&authority, &blockhash);
+        account.set_owner(owner);
+        assert_eq!(
+            NonceAccount::new(Pubkey::new_unique(), account),
+            Err(NonceError::InvalidOwner(owner))
+        );
+        let account = AccountSharedData::new_data(
+            1_000_000,
+            &Versions::new(State::Uninitialized),
+            &system_program::id(),
+        )
+        .unwrap();
+        assert_eq!(
+            NonceAccount::new(Pubkey::new_unique(), account),
+            Err(NonceError::Uninitialized)
+        );
+        let account = AccountSharedData::new_data(
+            1_000_000,
+            &Versions::Legacy(Box::new(State::Initialized(Data::default()))),
+            &system_program::id(),
+        )
+        .unwrap();
+        assert_eq!(
+            NonceAccount::new(Pubkey::new_unique(), account),
+            Err(NonceError::LegacyNonce)
+        );
+        assert_eq!(
+            NonceAccount::new(Pubkey::new_unique(), AccountSharedData::default()),
+            Err(NonceError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_transaction() {
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let blockhash = Hash::new_from_array([1; 32]);
+        let nonce = NonceAccount::new(
+            Pubkey::new_unique(),
+            nonce_account(&authority.pubkey(), &blockhash),
+        )
+        .unwrap();
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 42);
+
+        let transaction = nonce
+            .sign_transaction(
+                std::slice::from_ref(&transfer),
+                Some(&payer.pubkey()),
+                &[&payer, &authority],
+            )
+            .unwrap();
+        assert_eq!(transaction.message.recent_blockhash, nonce.blockhash());
+        assert!(transaction.is_signed());
+        assert_eq!(nonce.verify_transaction(&transaction), Ok(()));
+
+        assert_eq!(
+            nonce.sign_transaction(
+                std::slice::from_ref(&transfer),
+                Some(&payer.pubkey()),
+                &[&payer]
+            ),
+            Err(NonceError::Signer(SignerError::NotEnoughSigners))
+        );
+
+        let mut stale = transaction.clone();
+        stale.message.recent_blockhash = blockhash;
+        assert_eq!(
+            nonce.verify_transaction(&stale),
+            Err(NonceError::BlockhashMismatch)
+        );
+
+        let other = NonceAccount::new(
+            Pubkey::new_unique(),
+            nonce_account(&authority.pubkey(), &blockhash),
+        )
+        .unwrap();
+        assert_eq!(
+            other.verify_transaction(&transaction),
+            Err(NonceError::NonceAccountMismatch)
+        );
+
+        let message =
+            Message::new_with_blockhash(&[transfer], Some(&payer.pubkey()), &nonce.blockhash());
+        let transaction = Transaction::new(&[&payer], message, nonce.blockhash());
+        assert_eq!(
+            nonce.verify_transaction(&transaction),
+            Err(NonceError::NotNonced)
+        );
+    }
+
+    #[test]
+    fn test_advance() {
+        let authority = Pubkey::new_unique();
+        let blockhash = Hash::new_from_array([1; 32]);
+        let nonce =
+            NonceAccount::new(Pubkey::new_unique(), nonce_account(&authority, &blockhash)).unwrap();
+
+        let next_blockhash = Hash::new_from_array([2; 32]);
+        let advanced = NonceAccount::new(
+            *nonce.address(),
+            nonce.advance(next_blockhash, 10_000).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            advanced.blockhash(),
+            *DurableNonce::from_blockhash(&next_blockhash).as_hash()
+        );
+        assert_eq!(advanced.lamports_per_signature(), 10_000);
+        assert_eq!(advanced.authority(), &authority);
+
+        let err = nonce.advance(blockhash, 10_000).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to advance nonce: instruction failed: custom program error: 0x7"
+        );
+        let NonceError::Advance(err) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(
+            err.system_error(),
+            Some(SystemError::NonceBlockhashNotExpired)
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Names of the accounts each instruction expects, following the account
+//! references documented on the instruction enums.
+
+use {
+    crate::{DecodedInstruction, FeatureGateInstruction},
+    solana_address_lookup_table_interface::instruction::ProgramInstruction,
+    solana_loader_v3_interface::instruction::UpgradeableLoaderInstruction,
+    solana_loader_v4_interface::instruction::LoaderV4Instruction,
+    solana_system_interface::instruction::SystemInstruction,
+    solana_vote_interface::instruction::VoteInstruction,
+};
+
+pub(crate) fn account_names(instruction: &DecodedInstruction) -> &'static [&'static str] {
+    match instruction {
+        DecodedInstruction::System(instruction) => system(instruction),
+        DecodedInstruction::Vote(instruction) => vote(instruction),
+        DecodedInstruction::BpfLoaderUpgradeable(instruction) => loader_v3(instruction),
+        DecodedInstruction::LoaderV4(instruction) => loader_v4(instruction),
+        DecodedInstruction::AddressLookupTable(instruction) => address_lookup_table(instruction),
+        DecodedInstruction::FeatureGate(FeatureGateInstruction::RevokePendingActivation) => {
+            &["feature account", "incinerator", "system program"]
+        }
+        DecodedInstruction::ComputeBudget(_)
+        | DecodedInstruction::Unknown { .. }
+        | DecodedInstruction::Invalid { .. } => &[],
+    }
+}
+
+fn system(instruction: &SystemInstruction) -> &'static [&'static str] {
+    match instruction {
+        SystemInstruction::CreateAccount { .. } => &["funding account", "new account"],
+        SystemInstruction::Assign { .. } => &["assigned account"],
+        SystemInstruction::Transfer { .. } => &["funding account", "recipient account"],
+        SystemInstruction::CreateAccountWithSeed { .. } => {
+            &["funding account", "created account", "base account"]
+        }
+        SystemInstruction::AdvanceNonceAccount => &[
+            "nonce account",
+            "recent blockhashes sysvar",
+            "nonce authority",
+        ],
+        SystemInstruction::WithdrawNonceAccount(_) => &[
+            "nonce account",
+            "recipient account",
+            "recent blockhashes sysvar",
+            "rent sysvar",
+            "nonce authority",
+        ],
+        SystemInstruction::InitializeNonceAccount(_) => {
+            &["nonce account", "recent blockhashes sysvar", "rent sysvar"]
+        }
+        SystemInstruction::AuthorizeNonceAccount(_) => &["nonce account", "nonce authority"],
+        SystemInstruction::Allocate { .. } => &["new account"],
+        SystemInstruction::AllocateWithSeed { .. } => &["allocated account", "base account"],
+        SystemInstruction::AssignWithSeed { .. } => &["assigned account", "base account"],
+        SystemInstruction::TransferWithSeed { .. } => &[
+            "funding account",
+            "base for funding account",
+            "recipient account",
+        ],
+        SystemInstruction::UpgradeNonceAccount => &["nonce account"],
+        SystemInstruction::CreateAccountAllowPrefund { .. } => &["new account", "funding account"],
+    }
+}
+
+fn vote(instruction: &VoteInstruction) -> &'static [&'static str] {
+    match instruction {
+        VoteInstruction::InitializeAccount(_) => &[
+            "vote account",
+            "rent sysvar",
+            "clock sysvar",
+            "validator identity",
+        ],
+        VoteInstruction::Authorize(..) => &["vote account", "clock sysvar", "authority"],
+        VoteInstruction::Vote(_) | VoteInstruction::VoteSwitch(..) => &[
+            "vote account",
+            "slot hashes sysvar",
+            "clock sysvar",
+            "vote authority",
+        ],
+        VoteInstruction::Withdraw(_) => {
+            &["vote account", "recipient account", "withdraw authority"]
+        }
+        VoteInstruction::UpdateValidatorIdentity => {
+            &["vote account", "validator identity", "withdraw authority"]
+        }
+        VoteInstruction::UpdateCommission(_) | VoteInstruction::UpdateCommissionBps { .. } => {
+            &["vote account", "withdraw authority"]
+        }
+        VoteInstruction::AuthorizeChecked(_) => {
+            &["vote account", "clock sysvar", "authority", "new authority"]
+        }
+        VoteInstruction::UpdateVoteState(_)
+        | VoteInstruction::UpdateVoteStateSwitch(..)
+        | VoteInstruction::CompactUpdateVoteState(_)
+        | VoteInstruction::CompactUpdateVoteStateSwitch(..)
+        | VoteInstruction::TowerSync(_)
+        | VoteInstruction::TowerSyncSwitch(..) => &["vote account", "vote authority"],
+        VoteInstruction::AuthorizeWithSeed(_) => {
+            &["vote account", "clock sysvar", "authority base"]
+        }
+        VoteInstruction::AuthorizeCheckedWithSeed(_) => &[
+            "vote account",
+            "clock sysvar",
+            "authority base",
+            "new authority",
+        ],
+        VoteInstruction::InitializeAccountV2(_) => &["vote account", "validator identity"],
+        VoteInstruction::UpdateCommissionCollector(_) => {
+            &["vote account", "collector account", "withdraw authority"]
+        }
+        VoteInstruction::DepositDelegatorRewards { .. } => &["vote account", "source account"],
+    }
+}
+
+fn loader_v3(instruction: &UpgradeableLoaderInstruction) -> &'static [&'static str] {
+    match instruction {
+        UpgradeableLoaderInstruction::InitializeBuffer => &["buffer account", "buffer authority"],
+        UpgradeableLoaderInstruction::Write { .. } => &["buffer account", "buffer authority"],
+        UpgradeableLoaderInstruction::DeployWithMaxDataLen { .. } => &[
+            "payer",
+            "program data account",
+            "program account",
+            "buffer account",
+            "rent sysvar",
+            "clock sysvar",
+            "system program",
+            "program authority",
+        ],
+        UpgradeableLoaderInstruction::Upgrade => &[
+            "program data account",
+            "program account",
+            "buffer account",
+            "spill account",
+            "rent sysvar",
+            "clock sysvar",
+            "program authority",
+        ],
+        UpgradeableLoaderInstruction::SetAuthority => {
+            &["account", "current authority", "new authority"]
+        }
+        UpgradeableLoaderInstruction::Close => &[
+            "closed account",
+            "recipient account",
+            "authority",
+            "program account",
+        ],
+        UpgradeableLoaderInstruction::ExtendProgram { .. } => &[
+            "program data account",
+            "program account",
+            "system program",
+            "payer",
+        ],
+        UpgradeableLoaderInstruction::SetAuthorityChecked => {
+            &["account", "current authority", "new authority"]
+        }
+        UpgradeableLoaderInstruction::Migrate => {
+            &["program data account", "program account", "authority"]
+        }
+        UpgradeableLoaderInstruction::ExtendProgramChecked { .. } => &[
+            "program data account",
+            "program account",
+            "authority",
+            "system program",
+            "payer",
+        ],
+    }
+}
+
+fn loader_v4(instruction: &LoaderV4Instruction) -> &'static [&'static str] {
+    match instruction {
+        LoaderV4Instruction::Write { .. } | LoaderV4Instruction::Retract => {
+            &["program account", "authority"]
+        }
+        LoaderV4Instruction::Copy { .. } => &["program account", "authority", "source program"],
+        LoaderV4Instruction::SetProgramLength { .. } => {
+            &["program account", "authority", "recipient account"]
+        }
+        LoaderV4Instruction::Deploy => &["program account", "authority", "source program"],
+        LoaderV4Instruction::TransferAuthority => {
+            &["program account", "authority", "new authority"]
+        }
+        LoaderV4Instruction::Finalize => &["program account", "authority", "next version"],
+    }
+}
+
+fn address_lookup_table(instruction: &ProgramInstruction) -> &'static [&'static str] {
+    match instruction {
+        ProgramInstruction::CreateLookupTable { .. } => {
+            &["lookup table", "authority", "payer", "system program"]
+        }
+        ProgramInstruction::FreezeLookupTable | ProgramInstruction::DeactivateLookupTable => {
+            &["lookup table", "authority"]
+        }
+        ProgramInstruction::ExtendLookupTable { .. } => {
+            &["lookup table", "authority", "payer", "system program"]
+        }
+        ProgramInstruction::CloseLookupTable => &["lookup table", "authority", "recipient"],
+    }
+}
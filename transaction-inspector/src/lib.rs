@@ -0,0 +1,580 @@
+//! Decode transactions offline into reports describing what they do.
+//!
+//! [`inspect_transaction`] decodes the instructions of every program the SDK
+//! has an interface for using that interface's own instruction enum, so a
+//! report always agrees with the instructions the SDK builds. Reports
+//! serialize with serde, for display in "what am I signing" screens.
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use {
+    serde_derive::Serialize,
+    solana_address::Address,
+    solana_address_lookup_table_interface::instruction::ProgramInstruction,
+    solana_compute_budget_interface::ComputeBudgetInstruction,
+    solana_hash::Hash,
+    solana_loader_v3_interface::instruction::UpgradeableLoaderInstruction,
+    solana_loader_v4_interface::instruction::LoaderV4Instruction,
+    solana_message::{compiled_instruction::CompiledInstruction, VersionedMessage},
+    solana_sanitize::SanitizeError,
+    solana_sdk_ids::{
+        address_lookup_table, bpf_loader_upgradeable, compute_budget, feature, loader_v4,
+        system_program, vote,
+    },
+    solana_signature::Signature,
+    solana_system_interface::instruction::SystemInstruction,
+    solana_transaction::versioned::{TransactionVersion, VersionedTransaction},
+    solana_vote_interface::instruction::VoteInstruction,
+    std::fmt,
+};
+
+mod account_names;
+
+/// Addresses, hashes and signatures appear in reports in their string form.
+mod display {
+    use {
+        serde::{ser::SerializeSeq, Serializer},
+        std::fmt::Display,
+    };
+
+    pub(crate) fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub(crate) fn serialize_seq<T: Display, S: Serializer>(
+        values: &[T],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+            seq.serialize_element(&value.to_string())?;
+        }
+        seq.end()
+    }
+}
+
+// inlined to avoid solana_packet dep
+#[cfg(test)]
+static_assertions::const_assert_eq!(PACKET_DATA_SIZE, solana_packet::PACKET_DATA_SIZE);
+const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
+
+#[derive(Debug)]
+pub enum InspectError {
+    /// The bytes are not a serialized transaction.
+    Deserialize(bincode::Error),
+    /// The transaction is malformed.
+    Sanitize(SanitizeError),
+}
+
+impl core::error::Error for InspectError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Deserialize(e) => Some(e),
+            Self::Sanitize(e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for InspectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Deserialize(_) => f.write_str("failed to deserialize transaction"),
+            Self::Sanitize(_) => f.write_str("transaction failed to sanitize"),
+        }
+    }
+}
+
+/// A decoded transaction.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReport {
+    pub version: TransactionVersion,
+    #[serde(serialize_with = "display::serialize_seq")]
+    pub signatures: Vec<Signature>,
+    #[serde(serialize_with = "display::serialize")]
+    pub fee_payer: Address,
+    #[serde(serialize_with = "display::serialize")]
+    pub recent_blockhash: Hash,
+    pub address_table_lookups: Vec<AddressTableLookupReport>,
+    pub instructions: Vec<InstructionReport>,
+}
+
+/// The accounts a transaction loads from an address lookup table.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTableLookupReport {
+    #[serde(serialize_with = "display::serialize")]
+    pub table: Address,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+/// A decoded instruction.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstructionReport {
+    #[serde(serialize_with = "display::serialize")]
+    pub program_id: Address,
+    pub accounts: Vec<AccountReport>,
+    #[serde(flatten)]
+    pub decoded: DecodedInstruction,
+}
+
+/// An account passed to an instruction, along with the role it plays in the
+/// transaction.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountReport {
+    pub address: AccountAddress,
+    /// What the instruction uses the account for, if known.
+    pub name: Option<&'static str>,
+    pub is_signer: bool,
+    /// Whether the transaction write locks the account. Accounts may be
+    /// demoted to read-only at runtime if they are reserved.
+    pub is_writable: bool,
+    pub is_fee_payer: bool,
+}
+
+/// Where the address of an account comes from.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountAddress {
+    /// The address is included in the message.
+    Static(#[serde(serialize_with = "display::serialize")] Address),
+    /// The address is loaded from an address lookup table, and is only known
+    /// once the table is read.
+    Lookup {
+        #[serde(serialize_with = "display::serialize")]
+        table: Address,
+        index: u8,
+    },
+}
+
+/// The instructions of the feature gate program.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureGateInstruction {
+    RevokePendingActivation,
+}
+
+/// An instruction decoded with the interface of its program.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "program", content = "instruction", rename_all = "camelCase")]
+pub enum DecodedInstruction {
+    System(SystemInstruction),
+    Vote(Box<VoteInstruction>),
+    BpfLoaderUpgradeable(UpgradeableLoaderInstruction),
+    LoaderV4(LoaderV4Instruction),
+    AddressLookupTable(ProgramInstruction),
+    ComputeBudget(ComputeBudgetInstruction),
+    FeatureGate(FeatureGateInstruction),
+    /// An instruction of a program the SDK has no interface for.
+    Unknown {
+        data: Vec<u8>,
+    },
+    /// Instruction data the program's interface can't decode.
+    Invalid {
+        data: Vec<u8>,
+    },
+}
+
+impl DecodedInstruction {
+    /// Decode the data of an instruction for `program_id`.
+    pub fn decode(program_id: &Address, data: &[u8]) -> Self {
+        let decoded = if system_program::check_id(program_id) {
+            limited_deserialize(data).map(Self::System)
+        } else if vote::check_id(program_id) {
+            limited_deserialize(data).map(|instruction| Self::Vote(Box::new(instruction)))
+        } else if bpf_loader_upgradeable::check_id(program_id) {
+            limited_deserialize(data).map(Self::BpfLoaderUpgradeable)
+        } else if loader_v4::check_id(program_id) {
+            limited_deserialize(data).map(Self::LoaderV4)
+        } else if address_lookup_table::check_id(program_id) {
+            limited_deserialize(data).map(Self::AddressLookupTable)
+        } else if compute_budget::check_id(program_id) {
+            ComputeBudgetInstruction::from_instruction_data(data).map(Self::ComputeBudget)
+        } else if feature::check_id(program_id) {
+            (data == [0]).then_some(Self::FeatureGate(
+                FeatureGateInstruction::RevokePendingActivation,
+            ))
+        } else {
+            return Self::Unknown {
+                data: data.to_vec(),
+            };
+        };
+        decoded.unwrap_or_else(|| Self::Invalid {
+            data: data.to_vec(),
+        })
+    }
+
+    /// Names of the accounts the instruction expects, in order.
+    pub fn account_names(&self) -> &'static [&'static str] {
+        account_names::account_names(self)
+    }
+}
+
+fn limited_deserialize<T: serde::de::DeserializeOwned>(data: &[u8]) -> Option<T> {
+    solana_bincode::limited_deserialize(data, PACKET_DATA_SIZE as u64).ok()
+}
+
+/// Decode a serialized [`VersionedTransaction`].
+pub fn inspect_transaction(bytes: &[u8]) -> Result<TransactionReport, InspectError> {
+    let transaction: VersionedTransaction =
+        bincode::deserialize(bytes).map_err(InspectError::Deserialize)?;
+    inspect_versioned_transaction(&transaction)
+}
+
+/// Decode a [`VersionedTransaction`].
+pub fn inspect_versioned_transaction(
+    transaction: &VersionedTransaction,
+) -> Result<TransactionReport, InspectError> {
+    transaction.sanitize().map_err(InspectError::Sanitize)?;
+    let message = &transaction.message;
+    let addresses = account_addresses(message);
+    let instructions = message
+        .instructions()
+        .iter()
+        .map(|instruction| inspect_instruction(message, &addresses, instruction))
+        .collect();
+
+    Ok(TransactionReport {
+        version: transaction.version(),
+        signatures: transaction.signatures.clone(),
+        fee_payer: message.static_account_keys()[0],
+        recent_blockhash: *message.recent_blockhash(),
+        address_table_lookups: message
+            .address_table_lookups()
+            .unwrap_or_default()
+            .iter()
+            .map(|lookup| AddressTableLookupReport {
+                table: lookup.account_key,
+                writable_indexes: lookup.writable_indexes.clone(),
+                readonly_indexes: lookup.readonly_indexes.clone(),
+            })
+            .collect(),
+        instructions,
+    })
+}
+
+/// The address of each account of `message`, in account index order: static
+/// keys, then writable and then readonly keys loaded from lookup tables.
+fn account_addresses(message: &VersionedMessage) -> Vec<AccountAddress> {
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let writable = lookups.iter().flat_map(|lookup| {
+        lookup
+            .writable_indexes
+            .iter()
+            .map(|index| AccountAddress::Lookup {
+                table: lookup.account_key,
+                index: *index,
+            })
+    });
+    let readonly = lookups.iter().flat_map(|lookup| {
+        lookup
+            .readonly_indexes
+            .iter()
+            .map(|index| AccountAddress::Lookup {
+                table: lookup.account_key,
+                index: *index,
+            })
+    });
+    message
+        .static_account_keys()
+        .iter()
+        .copied()
+        .map(AccountAddress::Static)
+        .chain(writable)
+        .chain(readonly)
+        .collect()
+}
+
+fn inspect_instruction(
+    message: &VersionedMessage,
+    addresses: &[AccountAddress],
+    instruction: &CompiledInstruction,
+) -> InstructionReport {
+    let program_id = message.static_account_keys()[usize::from(instruction.program_id_index)];
+    let decoded = DecodedInstruction::decode(&program_id, &instruction.data);
+    let names = decoded.account_names();
+    let accounts = instruction
+        .accounts
+        .iter()
+        .enumerate()
+        .map(|(position, index)| {
+            let index = usize::from(*index);
+            AccountReport {
+                address: addresses[index],
+                name: names.get(position).copied(),
+                is_signer: message.is_signer(index),
+                is_writable: message.is_maybe_writable(index, None),
+                is_fee_payer: index == 0,
+            }
+        })
+        .collect();
+
+    InstructionReport {
+        program_id,
+        accounts,
+        decoded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_instruction::{AccountMeta, Instruction},
+        solana_keypair::Keypair,
+        solana_message::{v0, AddressLookupTableAccount, Message},
+        solana_signer::Signer,
+        solana_system_interface::instruction as system_instruction,
+        solana_transaction::Transaction,
+    };
+
+    #[test]
+    fn test_inspect_legacy_transaction() {
+        let payer = Keypair::new();
+        let recipient = Address::new_unique();
+        let recent_blockhash = Hash::new_unique();
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_price(10),
+                system_instruction::transfer(&payer.pubkey(), &recipient, 42),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        let report = inspect_transaction(&bincode::serialize(&transaction).unwrap()).unwrap();
+        assert_eq!(report.version, TransactionVersion::LEGACY);
+        assert_eq!(report.signatures, transaction.signatures);
+        assert_eq!(report.fee_payer, payer.pubkey());
+        assert_eq!(report.recent_blockhash, recent_blockhash);
+        assert!(report.address_table_lookups.is_empty());
+        assert_eq!(
+            report.instructions,
+            vec![
+                InstructionReport {
+                    program_id: compute_budget::id(),
+                    accounts: vec![],
+                    decoded: DecodedInstruction::ComputeBudget(
+                        ComputeBudgetInstruction::SetComputeUnitPrice(10)
+                    ),
+                },
+                InstructionReport {
+                    program_id: system_program::id(),
+                    accounts: vec![
+                        AccountReport {
+                            address: AccountAddress::Static(payer.pubkey()),
+                            name: Some("funding account"),
+                            is_signer: true,
+                            is_writable: true,
+                            is_fee_payer: true,
+                        },
+                        AccountReport {
+                            address: AccountAddress::Static(recipient),
+                            name: Some("recipient account"),
+                            is_signer: false,
+                            is_writable: true,
+                            is_fee_payer: false,
+                        },
+                    ],
+                    decoded: DecodedInstruction::System(SystemInstruction::Transfer {
+                        lamports: 42
+                    }),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inspect_v0_transaction() {
+        let payer = Keypair::new();
+        let nonce_account = Address::new_unique();
+        let table_key = Address::new_unique();
+        let loaded = Address::new_unique();
+        let message = v0::Message::try_compile(
+            &payer.pubkey(),
+            &[
+                system_instruction::advance_nonce_account(&nonce_account, &payer.pubkey()),
+                Instruction::new_with_bytes(
+                    Address::new_unique(),
+                    &[1, 2, 3],
+                    vec![AccountMeta::new_readonly(loaded, false)],
+                ),
+            ],
+            &[AddressLookupTableAccount {
+                key: table_key,
+                addresses: vec![Address::new_unique(), loaded],
+            }],
+            Hash::new_unique(),
+        )
+        .unwrap();
+        let transaction =
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap();
+
+        let report = inspect_versioned_transaction(&transaction).unwrap();
+        assert_eq!(report.version, TransactionVersion::Number(0));
+        assert_eq!(
+            report.address_table_lookups,
+            vec![AddressTableLookupReport {
+                table: table_key,
+                writable_indexes: vec![],
+                readonly_indexes: vec![1],
+            }]
+        );
+
+        let advance_nonce = &report.instructions[0];
+        assert_eq!(
+            advance_nonce.decoded,
+            DecodedInstruction::System(SystemInstruction::AdvanceNonceAccount)
+        );
+        assert_eq!(
+            advance_nonce
+                .accounts
+                .iter()
+                .map(|account| account.name)
+                .collect::<Vec<_>>(),
+            vec![
+                Some("nonce account"),
+                Some("recent blockhashes sysvar"),
+                Some("nonce authority"),
+            ]
+        );
+        assert!(advance_nonce.accounts[0].is_writable);
+        assert!(advance_nonce.accounts[2].is_signer);
+
+        let unknown = &report.instructions[1];
+        assert_eq!(
+            unknown.decoded,
+            DecodedInstruction::Unknown {
+                data: vec![1, 2, 3]
+            }
+        );
+        assert_eq!(
+            unknown.accounts,
+            vec![AccountReport {
+                address: AccountAddress::Lookup {
+                    table: table_key,
+                    index: 1
+                },
+                name: None,
+                is_signer: false,
+                is_writable: false,
+                is_fee_payer: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let authority = Address::new_unique();
+        let payer = Address::new_unique();
+        let program = Address::new_unique();
+
+        let (create_lookup_table, _) =
+            solana_address_lookup_table_interface::instruction::create_lookup_table(
+                authority, payer, 0,
+            );
+        let instructions = [
+            create_lookup_table,
+            solana_vote_interface::instruction::withdraw(&program, &authority, 1, &payer),
+            solana_loader_v3_interface::instruction::set_upgrade_authority(
+                &program, &authority, None,
+            ),
+            solana_loader_v4_interface::instruction::retract(&program, &authority),
+            solana_feature_gate_interface::instruction::revoke_pending_activation(&program),
+        ];
+        let decoded = instructions
+            .iter()
+            .map(|instruction| {
+                DecodedInstruction::decode(&instruction.program_id, &instruction.data)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decoded,
+            vec![
+                DecodedInstruction::AddressLookupTable(ProgramInstruction::CreateLookupTable {
+                    recent_slot: 0,
+                    bump_seed: match &decoded[0] {
+                        DecodedInstruction::AddressLookupTable(
+                            ProgramInstruction::CreateLookupTable { bump_seed, .. },
+                        ) => *bump_seed,
+                        _ => panic!("not a create lookup table instruction"),
+                    },
+                }),
+                DecodedInstruction::Vote(Box::new(VoteInstruction::Withdraw(1))),
+                DecodedInstruction::BpfLoaderUpgradeable(
+                    UpgradeableLoaderInstruction::SetAuthority
+                ),
+                DecodedInstruction::LoaderV4(LoaderV4Instruction::Retract),
+                DecodedInstruction::FeatureGate(FeatureGateInstruction::RevokePendingActivation),
+            ]
+        );
+        for (instruction, decoded) in instructions.iter().zip(&decoded) {
+            assert!(instruction.accounts.len() <= decoded.account_names().len());
+        }
+
+        assert_eq!(
+            DecodedInstruction::decode(&system_program::id(), &[42]),
+            DecodedInstruction::Invalid { data: vec![42] }
+        );
+        assert_eq!(
+            DecodedInstruction::decode(&compute_budget::id(), &[2, 0]),
+            DecodedInstruction::Invalid { data: vec![2, 0] }
+        );
+    }
+
+    #[test]
+    fn test_serialize_report() {
+        let payer = Keypair::new();
+        let message = Message::new(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Address::new_unique(),
+                7,
+            )],
+            Some(&payer.pubkey()),
+        );
+        let transaction = Transaction::new(&[&payer], message, Hash::new_unique());
+        let report =
+            inspect_versioned_transaction(&VersionedTransaction::from(transaction)).unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["version"], "legacy");
+        assert_eq!(json["signatures"][0], report.signatures[0].to_string());
+        assert_eq!(json["feePayer"], payer.pubkey().to_string());
+        assert_eq!(json["instructions"][0]["program"], "system");
+        assert_eq!(
+            json["instructions"][0]["instruction"]["Transfer"]["lamports"],
+            7
+        );
+        assert_eq!(
+            json["instructions"][0]["accounts"][0]["address"]["static"],
+            payer.pubkey().to_string()
+        );
+        assert_eq!(
+            json["instructions"][0]["accounts"][0]["name"],
+            "funding account"
+        );
+    }
+
+    #[test]
+    fn test_inspect_errors() {
+        assert!(matches!(
+            inspect_transaction(&[1, 2, 3]),
+            Err(InspectError::Deserialize(_))
+        ));
+
+        let mut transaction = VersionedTransaction::from(Transaction::new_with_payer(
+            &[],
+            Some(&Address::new_unique()),
+        ));
+        transaction.signatures.clear();
+        assert!(matches!(
+            inspect_versioned_transaction(&transaction),
+            Err(InspectError::Sanitize(_))
+        ));
+    }
+}
@@ -119,7 +119,10 @@ pub use {
     solana_address::Address,
     solana_instruction::{AccountMeta, Instruction},
     solana_instruction_error::InstructionError,
-    solana_message::{compiled_instruction::CompiledInstruction, Message, VersionedMessage},
+    solana_message::{
+        compiled_instruction::CompiledInstruction, transaction_size::TransactionSize, Message,
+        VersionedMessage,
+    },
     solana_signature::Signature,
     solana_transaction_error::{TransactionError, TransactionResult},
 };
@@ -135,7 +138,10 @@ use {
     std::result,
 };
 
+pub mod packer;
 pub mod sanitized;
+#[cfg(feature = "verify")]
+pub mod signing_session;
 pub mod simple_vote_transaction_checker;
 pub mod versioned;
 
@@ -1084,6 +1090,14 @@ impl Transaction {
             .iter()
             .all(|signature| *signature != Signature::default())
     }
+
+    /// Compute the size of this transaction once serialized, broken down by
+    /// component, without serializing it.
+    pub fn transaction_size(&self) -> TransactionSize {
+        self.message
+            .transaction_size()
+            .with_num_signatures(self.signatures.len())
+    }
 }
 
 /// Returns true if transaction begins with an advance nonce instruction.
@@ -1630,6 +1644,27 @@ mod tests {
         assert!(tx.is_signed());
     }
 
+    #[test]
+    fn test_transaction_size() {
+        let keypair = Keypair::new();
+        let ix = Instruction::new_with_bytes(
+            Address::new_unique(),
+            &[0; 300],
+            vec![AccountMeta::new(Address::new_unique(), false)],
+        );
+        let mut tx = Transaction::new_with_payer(&[ix], Some(&keypair.pubkey()));
+        let size = tx.transaction_size();
+        assert_eq!(size.total(), serialize(&tx).unwrap().len());
+        assert_eq!(size, tx.message.transaction_size());
+        assert!(size.fits_in_packet());
+
+        tx.sign(&[&keypair], Hash::default());
+        assert_eq!(tx.transaction_size(), size);
+
+        tx.signatures.clear();
+        assert_eq!(tx.transaction_size().total(), serialize(&tx).unwrap().len());
+    }
+
     #[test]
     fn test_replace_signatures() {
         let program_id = Address::default();
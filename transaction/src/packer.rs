@@ -0,0 +1,602 @@
+//! Packing long lists of instructions into as few transactions as possible.
+//!
+//! Jobs such as airdrops or account migrations produce more instructions than
+//! fit in a single transaction. [`InstructionPacker`] splits them into
+//! consecutive batches, each of which compiles to a message that fits in a
+//! single packet and stays within the transaction account lock limit.
+//! Instructions keep their relative order, and groups of instructions which
+//! must execute atomically are never split across transactions.
+//!
+//! Since a batch which fits keeps fitting when instructions are removed from
+//! it, filling each batch before starting the next one yields the minimum
+//! number of transactions for the given order.
+
+use {
+    crate::sanitized::MAX_TX_ACCOUNT_LOCKS,
+    core::fmt,
+    solana_address::Address,
+    solana_hash::Hash,
+    solana_instruction::Instruction,
+    solana_message::{
+        legacy, transaction_size::TransactionSize, v0, AddressLookupTableAccount, CompileError,
+        VersionedMessage,
+    },
+};
+
+/// Reasons why instructions could not be packed into transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// A group of instructions does not fit in a single packet on its own.
+    GroupTooLarge {
+        group_index: usize,
+        size: TransactionSize,
+    },
+    /// A group of instructions references more accounts than a transaction
+    /// may lock on its own.
+    TooManyAccountLocks {
+        group_index: usize,
+        num_accounts: usize,
+    },
+    /// A group of instructions references more accounts than a message can
+    /// index on its own.
+    TooManyAccountKeys { group_index: usize },
+    /// Compiling a message failed.
+    Compile(CompileError),
+}
+
+impl core::error::Error for PackError {}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GroupTooLarge { group_index, size } => write!(
+                f,
+                "instruction group {group_index} needs a {} byte transaction, which does not fit \
+                 in a packet",
+                size.total()
+            ),
+            Self::TooManyAccountLocks {
+                group_index,
+                num_accounts,
+            } => write!(
+                f,
+                "instruction group {group_index} locks {num_accounts} accounts, which exceeds \
+                 the account lock limit"
+            ),
+            Self::TooManyAccountKeys { group_index } => write!(
+                f,
+                "instruction group {group_index} references too many accounts to be compiled"
+            ),
+            Self::Compile(err) => write!(f, "failed to compile message: {err}"),
+        }
+    }
+}
+
+impl From<CompileError> for PackError {
+    fn from(err: CompileError) -> Self {
+        Self::Compile(err)
+    }
+}
+
+/// Splits instructions into the fewest messages which each fit in a
+/// transaction.
+///
+/// Legacy messages are produced unless address lookup tables are provided,
+/// in which case v0 messages are compiled with the subset of tables which
+/// makes each of them smallest.
+#[derive(Debug, Clone)]
+pub struct InstructionPacker<'a> {
+    payer: Address,
+    recent_blockhash: Hash,
+    address_lookup_table_accounts: Option<&'a [AddressLookupTableAccount]>,
+    account_lock_limit: usize,
+}
+
+/// Outcome of compiling a candidate batch of instructions.
+enum Compiled {
+    Fits(VersionedMessage),
+    TooLarge(TransactionSize),
+    TooManyAccountLocks(usize),
+    TooManyAccountKeys,
+}
+
+/// The message compiled from the instructions of the current batch.
+struct BatchMessage {
+    message: VersionedMessage,
+    /// Whether the message was compiled with the subset of lookup tables
+    /// which makes it smallest, rather than with the tables of the previous
+    /// message of the batch.
+    is_optimal: bool,
+}
+
+impl<'a> InstructionPacker<'a> {
+    /// Create a packer which produces legacy messages.
+    pub fn new(payer: Address, recent_blockhash: Hash) -> Self {
+        Self {
+            payer,
+            recent_blockhash,
+            address_lookup_table_accounts: None,
+            account_lock_limit: MAX_TX_ACCOUNT_LOCKS,
+        }
+    }
+
+    /// Create a packer which produces v0 messages loading accounts from
+    /// `address_lookup_table_accounts`.
+    pub fn new_with_address_lookup_tables(
+        payer: Address,
+        recent_blockhash: Hash,
+        address_lookup_table_accounts: &'a [AddressLookupTableAccount],
+    ) -> Self {
+        Self {
+            address_lookup_table_accounts: Some(address_lookup_table_accounts),
+            ..Self::new(payer, recent_blockhash)
+        }
+    }
+
+    /// Limit the number of accounts each transaction may lock, which
+    /// defaults to [`MAX_TX_ACCOUNT_LOCKS`].
+    pub fn with_account_lock_limit(mut self, account_lock_limit: usize) -> Self {
+        self.account_lock_limit = account_lock_limit;
+        self
+    }
+
+    /// Pack instructions which may each be executed in any transaction.
+    pub fn pack(&self, instructions: &[Instruction]) -> Result<Vec<VersionedMessage>, PackError> {
+        self.pack_groups(
+            &instructions
+                .iter()
+                .map(core::slice::from_ref)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Pack groups of instructions, keeping every group within a single
+    /// transaction.
+    ///
+    /// While a batch grows, it is compiled with the lookup tables already
+    /// selected for it, and the optimal subset of tables is only searched for
+    /// once those no longer make it fit, and again when the batch is closed.
+    pub fn pack_groups<G: AsRef<[Instruction]>>(
+        &self,
+        groups: &[G],
+    ) -> Result<Vec<VersionedMessage>, PackError> {
+        let mut messages = Vec::new();
+        let mut batch: Vec<Instruction> = Vec::new();
+        let mut batch_message: Option<BatchMessage> = None;
+
+        for (group_index, group) in groups.iter().enumerate() {
+            let group = group.as_ref();
+            if group.is_empty() {
+                continue;
+            }
+
+            let batch_len = batch.len();
+            batch.extend_from_slice(group);
+            if let Some(message) = self.compile_appended(&batch, batch_message.as_ref())? {
+                batch_message = Some(message);
+                continue;
+            }
+
+            // the group doesn't fit in the current batch, start a new one
+            batch.truncate(batch_len);
+            messages.extend(self.finish(&batch, batch_message.take())?);
+            batch.clear();
+            batch.extend_from_slice(group);
+            match self.compile(&batch, None)? {
+                Compiled::Fits(message) => {
+                    batch_message = Some(BatchMessage {
+                        message,
+                        is_optimal: true,
+                    })
+                }
+                Compiled::TooLarge(size) => {
+                    return Err(PackError::GroupTooLarge { group_index, size })
+                }
+                Compiled::TooManyAccountLocks(num_accounts) => {
+                    return Err(PackError::TooManyAccountLocks {
+                        group_index,
+                        num_accounts,
+                    })
+                }
+                Compiled::TooManyAccountKeys => {
+                    return Err(PackError::TooManyAccountKeys { group_index })
+                }
+            }
+        }
+        messages.extend(self.finish(&batch, batch_message)?);
+
+        Ok(messages)
+    }
+
+    /// Compile a batch which a group was just appended to, returning `None`
+    /// if it no longer fits in a transaction.
+    fn compile_appended(
+        &self,
+        batch: &[Instruction],
+        previous: Option<&BatchMessage>,
+    ) -> Result<Option<BatchMessage>, PackError> {
+        if let (Some(previous), Some(tables)) = (previous, self.address_lookup_table_accounts) {
+            let selected_tables = selected_tables(&previous.message, tables);
+            // the selected tables may not be usable for the new accounts, for
+            // example if they are stored past the last index a lookup can
+            // address, in which case the optimal selection decides
+            if let Ok(Compiled::Fits(message)) = self.compile(batch, Some(&selected_tables)) {
+                return Ok(Some(BatchMessage {
+                    message,
+                    is_optimal: false,
+                }));
+            }
+        }
+        match self.compile(batch, None)? {
+            Compiled::Fits(message) => Ok(Some(BatchMessage {
+                message,
+                is_optimal: true,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Produce the message of a closed batch, compiled with the optimal
+    /// subset of lookup tables.
+    fn finish(
+        &self,
+        batch: &[Instruction],
+        batch_message: Option<BatchMessage>,
+    ) -> Result<Option<VersionedMessage>, PackError> {
+        let Some(batch_message) = batch_message else {
+            return Ok(None);
+        };
+        if batch_message.is_optimal {
+            return Ok(Some(batch_message.message));
+        }
+        match self.compile(batch, None)? {
+            Compiled::Fits(message) => Ok(Some(message)),
+            _ => Ok(Some(batch_message.message)),
+        }
+    }
+
+    /// Compile instructions with `selected_tables`, or with the subset of
+    /// the packer's lookup tables which makes the message smallest.
+    fn compile(
+        &self,
+        instructions: &[Instruction],
+        selected_tables: Option<&[AddressLookupTableAccount]>,
+    ) -> Result<Compiled, PackError> {
+        let compiled = match selected_tables {
+            Some(selected_tables) => v0::Message::try_compile(
+                &self.payer,
+                instructions,
+                selected_tables,
+                self.recent_blockhash,
+            ),
+            None => v0::Message::try_compile_with_optimal_lookup_tables(
+                &self.payer,
+                instructions,
+                self.address_lookup_table_accounts.unwrap_or_default(),
+                self.recent_blockhash,
+            )
+            .map(|optimized| optimized.message),
+        };
+        let message = match compiled {
+            Ok(message) => message,
+            Err(CompileError::AccountIndexOverflow) => return Ok(Compiled::TooManyAccountKeys),
+            Err(err) => return Err(err.into()),
+        };
+        let num_accounts = message.account_keys.len().saturating_add(
+            message
+                .address_table_lookups
+                .iter()
+                .map(|lookup| {
+                    lookup
+                        .writable_indexes
+                        .len()
+                        .saturating_add(lookup.readonly_indexes.len())
+                })
+                .sum(),
+        );
+        if num_accounts > self.account_lock_limit {
+            return Ok(Compiled::TooManyAccountLocks(num_accounts));
+        }
+
+        let message = if self.address_lookup_table_accounts.is_some() {
+            VersionedMessage::V0(message)
+        } else {
+            VersionedMessage::Legacy(legacy::Message {
+                header: message.header,
+                account_keys: message.account_keys,
+                recent_blockhash: message.recent_blockhash,
+                instructions: message.instructions,
+            })
+        };
+        let size = message.transaction_size();
+        if size.fits_in_packet() {
+            Ok(Compiled::Fits(message))
+        } else {
+            Ok(Compiled::TooLarge(size))
+        }
+    }
+}
+
+/// The lookup tables `message` loads accounts from, in the order it uses them.
+fn selected_tables(
+    message: &VersionedMessage,
+    tables: &[AddressLookupTableAccount],
+) -> Vec<AddressLookupTableAccount> {
+    message
+        .address_table_lookups()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|lookup| {
+            tables
+                .iter()
+                .find(|table| table.key == lookup.account_key)
+                .cloned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_instruction::AccountMeta};
+
+    fn instruction(program_id: Address, data_len: usize) -> Instruction {
+        Instruction::new_with_bytes(
+            program_id,
+            &vec![0; data_len],
+            vec![AccountMeta::new(Address::new_unique(), false)],
+        )
+    }
+
+    fn decompile(message: &VersionedMessage) -> Vec<(Address, usize)> {
+        message
+            .instructions()
+            .iter()
+            .map(|ix| {
+                (
+                    message.static_account_keys()[usize::from(ix.program_id_index)],
+                    ix.data.len(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pack_preserves_order_and_fills_batches() {
+        let payer = Address::new_unique();
+        let program_ids: Vec<Address> = (0..3).map(|_| Address::new_unique()).collect();
+        let instructions: Vec<Instruction> = (0..50)
+            .map(|i| instruction(program_ids[i % 3], 20 + i))
+            .collect();
+        let packer = InstructionPacker::new(payer, Hash::new_unique());
+
+        let messages = packer.pack(&instructions).unwrap();
+        assert!(messages.len() > 1);
+        assert!(messages
+            .iter()
+            .all(|message| matches!(message, VersionedMessage::Legacy(_))));
+        assert!(messages
+            .iter()
+            .all(|message| message.transaction_size().fits_in_packet()));
+        assert_eq!(
+            messages.iter().flat_map(decompile).collect::<Vec<_>>(),
+            instructions
+                .iter()
+                .map(|ix| (ix.program_id, ix.data.len()))
+                .collect::<Vec<_>>()
+        );
+
+        // each batch is full, adding the first instruction of the next batch
+        // would make it too large
+        let mut start = 0;
+        for message in &messages[..messages.len() - 1] {
+            let end = start + message.instructions().len();
+            assert!(matches!(
+                packer.compile(&instructions[start..=end], None).unwrap(),
+                Compiled::TooLarge(_)
+            ));
+            start = end;
+        }
+    }
+
+    #[test]
+    fn test_pack_groups_keeps_groups_together() {
+        let payer = Address::new_unique();
+        let program_id = Address::new_unique();
+        let groups: Vec<Vec<Instruction>> = (0..10)
+            .map(|_| (0..3).map(|_| instruction(program_id, 100)).collect())
+            .collect();
+        let packer = InstructionPacker::new(payer, Hash::new_unique());
+
+        let messages = packer.pack_groups(&groups).unwrap();
+        assert!(messages.len() > 1);
+        assert!(messages
+            .iter()
+            .all(|message| message.instructions().len() % 3 == 0));
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| message.instructions().len())
+                .sum::<usize>(),
+            30
+        );
+    }
+
+    #[test]
+    fn test_pack_group_too_large() {
+        let payer = Address::new_unique();
+        let program_id = Address::new_unique();
+        let groups = vec![
+            vec![instruction(program_id, 10)],
+            vec![instruction(program_id, 600), instruction(program_id, 600)],
+        ];
+        let packer = InstructionPacker::new(payer, Hash::new_unique());
+
+        assert!(matches!(
+            packer.pack_groups(&groups),
+            Err(PackError::GroupTooLarge { group_index: 1, .. })
+        ));
+        assert_eq!(packer.pack_groups(&groups[..1]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_pack_account_lock_limit() {
+        let payer = Address::new_unique();
+        let program_id = Address::new_unique();
+        let instructions: Vec<Instruction> = (0..6).map(|_| instruction(program_id, 0)).collect();
+        // payer, program id and two instruction accounts
+        let packer = InstructionPacker::new(payer, Hash::new_unique()).with_account_lock_limit(4);
+
+        let messages = packer.pack(&instructions).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert!(messages
+            .iter()
+            .all(|message| message.static_account_keys().len() == 4));
+
+        let packer = InstructionPacker::new(payer, Hash::new_unique()).with_account_lock_limit(2);
+        assert_eq!(
+            packer.pack(&instructions),
+            Err(PackError::TooManyAccountLocks {
+                group_index: 0,
+                num_accounts: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_pack_with_address_lookup_tables() {
+        let payer = Address::new_unique();
+        let program_id = Address::new_unique();
+        let instructions: Vec<Instruction> = (0..60).map(|_| instruction(program_id, 8)).collect();
+        let address_lookup_table_accounts = [AddressLookupTableAccount {
+            key: Address::new_unique(),
+            addresses: instructions
+                .iter()
+                .map(|ix| ix.accounts[0].pubkey)
+                .collect(),
+        }];
+
+        let legacy_messages = InstructionPacker::new(payer, Hash::new_unique())
+            .pack(&instructions)
+            .unwrap();
+        let v0_messages = InstructionPacker::new_with_address_lookup_tables(
+            payer,
+            Hash::new_unique(),
+            &address_lookup_table_accounts,
+        )
+        .pack(&instructions)
+        .unwrap();
+
+        assert!(v0_messages.len() < legacy_messages.len());
+        assert!(v0_messages.iter().all(|message| matches!(
+            message,
+            VersionedMessage::V0(message) if message.address_table_lookups.len() == 1
+        )));
+        assert!(v0_messages
+            .iter()
+            .all(|message| message.transaction_size().fits_in_packet()));
+    }
+
+    #[test]
+    fn test_pack_empty() {
+        let packer = InstructionPacker::new(Address::new_unique(), Hash::new_unique());
+        assert_eq!(packer.pack(&[]), Ok(vec![]));
+        assert_eq!(
+            packer.pack_groups::<Vec<Instruction>>(&[vec![]]),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn test_pack_with_address_lookup_tables_compiles_optimal_messages() {
+        let payer = Address::new_unique();
+        let program_id = Address::new_unique();
+        let instructions: Vec<Instruction> = (0..60).map(|_| instruction(program_id, 8)).collect();
+        let accounts: Vec<Address> = instructions
+            .iter()
+            .map(|ix| ix.accounts[0].pubkey)
+            .collect();
+        // the small tables are selected for the first instructions of each
+        // batch, the covering table once they no longer make the batch fit
+        let address_lookup_table_accounts: Vec<AddressLookupTableAccount> = accounts
+            .chunks(2)
+            .map(|chunk| AddressLookupTableAccount {
+                key: Address::new_unique(),
+                addresses: chunk.to_vec(),
+            })
+            .take(4)
+            .chain([AddressLookupTableAccount {
+                key: Address::new_unique(),
+                addresses: accounts.clone(),
+            }])
+            .collect();
+        let recent_blockhash = Hash::new_unique();
+        let packer = InstructionPacker::new_with_address_lookup_tables(
+            payer,
+            recent_blockhash,
+            &address_lookup_table_accounts,
+        );
+
+        let messages = packer.pack(&instructions).unwrap();
+        let mut start = 0;
+        for message in &messages {
+            let end = start + message.instructions().len();
+            let optimized = v0::Message::try_compile_with_optimal_lookup_tables(
+                &payer,
+                &instructions[start..end],
+                &address_lookup_table_accounts,
+                recent_blockhash,
+            )
+            .unwrap();
+            assert_eq!(message, &VersionedMessage::V0(optimized.message));
+            start = end;
+        }
+        assert_eq!(start, instructions.len());
+    }
+
+    #[test]
+    fn test_pack_with_address_lookup_tables_past_index_limit() {
+        let payer = Address::new_unique();
+        let program_id = Address::new_unique();
+        let addresses: Vec<Address> = (0..300).map(|_| Address::new_unique()).collect();
+        let address_lookup_table_accounts = [AddressLookupTableAccount {
+            key: Address::new_unique(),
+            addresses: addresses.clone(),
+        }];
+        // the table is selected for the accounts of the first instruction,
+        // but can not be reused for the second, whose account is past the
+        // last index
+        let instructions = [
+            Instruction::new_with_bytes(
+                program_id,
+                &[],
+                addresses[..8]
+                    .iter()
+                    .map(|address| AccountMeta::new(*address, false))
+                    .collect(),
+            ),
+            Instruction::new_with_bytes(
+                program_id,
+                &[],
+                vec![AccountMeta::new(addresses[299], false)],
+            ),
+        ];
+        let recent_blockhash = Hash::new_unique();
+        let packer = InstructionPacker::new_with_address_lookup_tables(
+            payer,
+            recent_blockhash,
+            &address_lookup_table_accounts,
+        );
+
+        let optimized = v0::Message::try_compile_with_optimal_lookup_tables(
+            &payer,
+            &instructions,
+            &address_lookup_table_accounts,
+            recent_blockhash,
+        )
+        .unwrap();
+        assert_eq!(
+            packer.pack(&instructions),
+            Ok(vec![VersionedMessage::V0(optimized.message)])
+        );
+    }
+}
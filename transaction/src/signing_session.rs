@@ -0,0 +1,526 @@
+//! Collecting the signatures of a transaction from several parties.
+//!
+//! A [`SigningSession`] is a message together with the signatures collected
+//! for it so far. Sessions serialize with serde, so each party can sign its
+//! own copy of a session, possibly on an air-gapped machine, and pass it back
+//! to be [merged](SigningSession::merge) with the others. Every signature is
+//! verified before it is added to a session.
+
+use {
+    crate::{versioned::VersionedTransaction, Transaction},
+    serde_derive::{Deserialize, Serialize},
+    solana_address::Address,
+    solana_message::VersionedMessage,
+    solana_signature::Signature,
+    solana_signer::{signers::Signers, SignerError},
+    std::fmt,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SigningSessionError {
+    /// The message requires more signatures than it has static account keys.
+    InvalidMessage,
+    /// The sessions being merged are for different messages.
+    MessageMismatch,
+    /// The session's signers or signature slots don't match its message.
+    InconsistentSession,
+    /// The address is not a required signer of the message.
+    UnexpectedSigner(Address),
+    /// The signature of the address doesn't verify against the message.
+    InvalidSignature(Address),
+    /// These signers haven't signed yet.
+    MissingSignatures(Vec<Address>),
+    /// Signing failed.
+    Signer(SignerError),
+}
+
+impl core::error::Error for SigningSessionError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Signer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SigningSessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidMessage => f.write_str("message has fewer account keys than signers"),
+            Self::MessageMismatch => f.write_str("signing sessions are for different messages"),
+            Self::InconsistentSession => {
+                f.write_str("signing session signers don't match its message")
+            }
+            Self::UnexpectedSigner(address) => {
+                write!(f, "`{address}` is not a required signer of the message")
+            }
+            Self::InvalidSignature(address) => write!(f, "invalid signature for `{address}`"),
+            Self::MissingSignatures(addresses) => {
+                write!(f, "missing {} signature(s)", addresses.len())
+            }
+            Self::Signer(err) => write!(f, "signer error: {err}"),
+        }
+    }
+}
+
+impl From<SignerError> for SigningSessionError {
+    fn from(error: SignerError) -> Self {
+        Self::Signer(error)
+    }
+}
+
+/// A message waiting for signatures from its required signers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SigningSession {
+    message: VersionedMessage,
+    /// The required signers of the message, in signature order.
+    signers: Vec<Address>,
+    /// The signature of each signer, if collected.
+    signatures: Vec<Option<Signature>>,
+}
+
+impl SigningSession {
+    /// Start a session with no signatures.
+    pub fn new(message: VersionedMessage) -> Result<Self, SigningSessionError> {
+        let num_required_signatures = usize::from(message.header().num_required_signatures);
+        let signers = message
+            .static_account_keys()
+            .get(..num_required_signatures)
+            .ok_or(SigningSessionError::InvalidMessage)?
+            .to_vec();
+        Ok(Self {
+            message,
+            signers,
+            signatures: vec![None; num_required_signatures],
+        })
+    }
+
+    /// Start a session from a partially signed transaction, keeping its
+    /// signatures.
+    ///
+    /// Default signatures mark signers which haven't signed yet. Any other
+    /// signature must be valid.
+    pub fn from_versioned_transaction(
+        transaction: &VersionedTransaction,
+    ) -> Result<Self, SigningSessionError> {
+        let mut session = Self::new(transaction.message.clone())?;
+        if transaction.signatures.len() != session.signers.len() {
+            return Err(SigningSessionError::InconsistentSession);
+        }
+        let message_data = session.message.serialize();
+        for (index, signature) in transaction.signatures.iter().enumerate() {
+            if *signature != Signature::default() {
+                session.try_add_signature_at(index, *signature, &message_data)?;
+            }
+        }
+        Ok(session)
+    }
+
+    /// Start a session from a partially signed legacy transaction; see
+    /// [`from_versioned_transaction`](Self::from_versioned_transaction).
+    pub fn from_transaction(transaction: &Transaction) -> Result<Self, SigningSessionError> {
+        Self::from_versioned_transaction(&VersionedTransaction::from(transaction.clone()))
+    }
+
+    pub fn message(&self) -> &VersionedMessage {
+        &self.message
+    }
+
+    /// The required signers of the message, in signature order.
+    pub fn signers(&self) -> &[Address] {
+        &self.signers
+    }
+
+    /// The signature collected from `signer`, if any.
+    pub fn signature(&self, signer: &Address) -> Option<&Signature> {
+        self.signers
+            .iter()
+            .position(|address| address == signer)
+            .and_then(|index| self.signatures.get(index))
+            .and_then(Option::as_ref)
+    }
+
+    /// Signers which haven't signed yet.
+    pub fn missing_signers(&self) -> impl Iterator<Item = &Address> {
+        self.signers
+            .iter()
+            .zip(&self.signatures)
+            .filter(|(_, signature)| signature.is_none())
+            .map(|(signer, _)| signer)
+    }
+
+    /// Returns true once every required signer has signed.
+    pub fn is_complete(&self) -> bool {
+        self.signatures.iter().all(Option::is_some)
+    }
+
+    /// Sign the message with `keypairs`, each of which must be a required
+    /// signer.
+    pub fn sign<T: Signers + ?Sized>(&mut self, keypairs: &T) -> Result<(), SigningSessionError> {
+        self.check_consistency()?;
+        let positions = keypairs
+            .try_pubkeys()?
+            .iter()
+            .map(|pubkey| self.position(pubkey))
+            .collect::<Result<Vec<_>, _>>()?;
+        let signatures = keypairs.try_sign_message(&self.message.serialize())?;
+        for (index, signature) in positions.into_iter().zip(signatures) {
+            *self.signature_slot(index)? = Some(signature);
+        }
+        Ok(())
+    }
+
+    /// Add the signature of `signer`, verifying it first.
+    pub fn add_signature(
+        &mut self,
+        signer: &Address,
+        signature: Signature,
+    ) -> Result<(), SigningSessionError> {
+        self.check_consistency()?;
+        let index = self.position(signer)?;
+        self.try_add_signature_at(index, signature, &self.message.serialize())
+    }
+
+    /// Add the signatures collected by `other`, a session for the same
+    /// message.
+    ///
+    /// All of the signatures of `other` are verified before any is added, so
+    /// on error `self` is left unchanged. Signatures already present in
+    /// `self` are kept.
+    pub fn merge(&mut self, other: &Self) -> Result<(), SigningSessionError> {
+        self.check_consistency()?;
+        other.check_consistency()?;
+        if self.message != other.message {
+            return Err(SigningSessionError::MessageMismatch);
+        }
+        let message_data = self.message.serialize();
+        let new_signatures = other
+            .signers
+            .iter()
+            .zip(&other.signatures)
+            .enumerate()
+            .filter_map(|(index, (signer, signature))| {
+                signature
+                    .filter(|_| self.signatures[index].is_none())
+                    .map(|signature| (index, signer, signature))
+            })
+            .map(|(index, signer, signature)| {
+                if signature.verify(signer.as_ref(), &message_data) {
+                    Ok((index, signature))
+                } else {
+                    Err(SigningSessionError::InvalidSignature(*signer))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, signature) in new_signatures {
+            self.signatures[index] = Some(signature);
+        }
+        Ok(())
+    }
+
+    /// Check that the session is consistent with its message and that all of
+    /// its signatures are valid.
+    ///
+    /// Sessions read from untrusted sources should be verified before use.
+    pub fn verify(&self) -> Result<(), SigningSessionError> {
+        self.check_consistency()?;
+        let message_data = self.message.serialize();
+        for (signer, signature) in self.signers.iter().zip(&self.signatures) {
+            if let Some(signature) = signature {
+                if !signature.verify(signer.as_ref(), &message_data) {
+                    return Err(SigningSessionError::InvalidSignature(*signer));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A transaction carrying the signatures collected so far, with default
+    /// signatures for signers which haven't signed yet.
+    pub fn to_partial_transaction(&self) -> VersionedTransaction {
+        VersionedTransaction {
+            signatures: self
+                .signatures
+                .iter()
+                .map(|signature| signature.unwrap_or_default())
+                .collect(),
+            message: self.message.clone(),
+        }
+    }
+
+    /// The fully signed transaction.
+    pub fn try_into_transaction(self) -> Result<VersionedTransaction, SigningSessionError> {
+        if !self.is_complete() {
+            return Err(SigningSessionError::MissingSignatures(
+                self.missing_signers().copied().collect(),
+            ));
+        }
+        Ok(self.to_partial_transaction())
+    }
+
+    fn position(&self, signer: &Address) -> Result<usize, SigningSessionError> {
+        self.signers
+            .iter()
+            .position(|address| address == signer)
+            .ok_or(SigningSessionError::UnexpectedSigner(*signer))
+    }
+
+    fn try_add_signature_at(
+        &mut self,
+        index: usize,
+        signature: Signature,
+        message_data: &[u8],
+    ) -> Result<(), SigningSessionError> {
+        let signer = *self
+            .signers
+            .get(index)
+            .ok_or(SigningSessionError::InconsistentSession)?;
+        if !signature.verify(signer.as_ref(), message_data) {
+            return Err(SigningSessionError::InvalidSignature(signer));
+        }
+        *self.signature_slot(index)? = Some(signature);
+        Ok(())
+    }
+
+    fn signature_slot(
+        &mut self,
+        index: usize,
+    ) -> Result<&mut Option<Signature>, SigningSessionError> {
+        self.signatures
+            .get_mut(index)
+            .ok_or(SigningSessionError::InconsistentSession)
+    }
+
+    fn check_consistency(&self) -> Result<(), SigningSessionError> {
+        let num_required_signatures = usize::from(self.message.header().num_required_signatures);
+        if self
+            .message
+            .static_account_keys()
+            .get(..num_required_signatures)
+            != Some(self.signers.as_slice())
+            || self.signatures.len() != num_required_signatures
+        {
+            return Err(SigningSessionError::InconsistentSession);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_hash::Hash,
+        solana_instruction::{AccountMeta, Instruction},
+        solana_keypair::Keypair,
+        solana_message::{v0, Message},
+        solana_signer::Signer,
+    };
+
+    fn message(signers: &[&Keypair]) -> Message {
+        let instruction = Instruction::new_with_bytes(
+            Address::new_unique(),
+            &[1],
+            signers
+                .iter()
+                .map(|signer| AccountMeta::new(signer.pubkey(), true))
+                .collect(),
+        );
+        Message::new_with_blockhash(
+            &[instruction],
+            Some(&signers[0].pubkey()),
+            &Hash::new_unique(),
+        )
+    }
+
+    #[test]
+    fn test_sign_and_merge() {
+        let payer = Keypair::new();
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        let message = VersionedMessage::Legacy(message(&[&payer, &alice, &bob]));
+
+        let session = SigningSession::new(message.clone()).unwrap();
+        assert_eq!(session.signers(), &message.static_account_keys()[..3]);
+        assert_eq!(session.signers()[0], payer.pubkey());
+        assert_eq!(session.missing_signers().count(), 3);
+
+        // each party signs its own copy, passed around as bytes
+        let bytes = bincode::serialize(&session).unwrap();
+        let mut payer_session: SigningSession = bincode::deserialize(&bytes).unwrap();
+        payer_session.sign(&[&payer]).unwrap();
+        let mut alice_session: SigningSession = bincode::deserialize(&bytes).unwrap();
+        alice_session.sign(&[&alice]).unwrap();
+        let mut bob_session: SigningSession = bincode::deserialize(&bytes).unwrap();
+        bob_session.sign(&[&bob]).unwrap();
+
+        let mut session = payer_session;
+        session.merge(&alice_session).unwrap();
+        assert_eq!(
+            session.missing_signers().collect::<Vec<_>>(),
+            vec![&bob.pubkey()]
+        );
+        assert_eq!(
+            session.clone().try_into_transaction(),
+            Err(SigningSessionError::MissingSignatures(vec![bob.pubkey()]))
+        );
+
+        session.merge(&bob_session).unwrap();
+        assert!(session.is_complete());
+        assert_eq!(session.verify(), Ok(()));
+        let transaction = session.try_into_transaction().unwrap();
+        assert_eq!(
+            transaction,
+            VersionedTransaction::try_new(message, &[&payer, &alice, &bob]).unwrap()
+        );
+        assert!(transaction.verify_with_results().iter().all(|ok| *ok));
+    }
+
+    #[test]
+    fn test_merge_rejects_invalid_signatures() {
+        let payer = Keypair::new();
+        let alice = Keypair::new();
+        let message = VersionedMessage::Legacy(message(&[&payer, &alice]));
+        let mut session = SigningSession::new(message.clone()).unwrap();
+
+        let mut forged = session.clone();
+        forged.signatures[0] = Some(payer.sign_message(b"something else"));
+        forged.signatures[1] = Some(alice.sign_message(&message.serialize()));
+        assert_eq!(
+            session.merge(&forged),
+            Err(SigningSessionError::InvalidSignature(payer.pubkey()))
+        );
+        assert_eq!(
+            forged.verify(),
+            Err(SigningSessionError::InvalidSignature(payer.pubkey()))
+        );
+        // nothing was merged
+        assert_eq!(session.missing_signers().count(), 2);
+
+        let mut inconsistent = session.clone();
+        inconsistent.signers.swap(0, 1);
+        assert_eq!(
+            session.merge(&inconsistent),
+            Err(SigningSessionError::InconsistentSession)
+        );
+
+        let other =
+            SigningSession::new(VersionedMessage::Legacy(self::message(&[&payer]))).unwrap();
+        assert_eq!(
+            session.merge(&other),
+            Err(SigningSessionError::MessageMismatch)
+        );
+    }
+
+    #[test]
+    fn test_add_signature() {
+        let payer = Keypair::new();
+        let alice = Keypair::new();
+        let message = VersionedMessage::Legacy(message(&[&payer, &alice]));
+        let message_data = message.serialize();
+        let mut session = SigningSession::new(message).unwrap();
+
+        assert_eq!(
+            session.add_signature(&alice.pubkey(), payer.sign_message(&message_data)),
+            Err(SigningSessionError::InvalidSignature(alice.pubkey()))
+        );
+        let stranger = Keypair::new();
+        assert_eq!(
+            session.add_signature(&stranger.pubkey(), stranger.sign_message(&message_data)),
+            Err(SigningSessionError::UnexpectedSigner(stranger.pubkey()))
+        );
+        assert_eq!(
+            session.sign(&[&stranger]),
+            Err(SigningSessionError::UnexpectedSigner(stranger.pubkey()))
+        );
+
+        let signature = alice.sign_message(&message_data);
+        session.add_signature(&alice.pubkey(), signature).unwrap();
+        assert_eq!(session.signature(&alice.pubkey()), Some(&signature));
+        assert_eq!(session.signature(&payer.pubkey()), None);
+
+        // a session with missing signature slots, as may be deserialized, is
+        // rejected rather than indexed out of bounds
+        let mut inconsistent = session.clone();
+        inconsistent.signatures.truncate(1);
+        assert_eq!(
+            inconsistent.add_signature(&alice.pubkey(), signature),
+            Err(SigningSessionError::InconsistentSession)
+        );
+        assert_eq!(
+            inconsistent.sign(&[&payer]),
+            Err(SigningSessionError::InconsistentSession)
+        );
+    }
+
+    #[test]
+    fn test_from_transaction() {
+        let payer = Keypair::new();
+        let alice = Keypair::new();
+        let message = message(&[&payer, &alice]);
+        let recent_blockhash = message.recent_blockhash;
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction.partial_sign(&[&alice], recent_blockhash);
+
+        let mut session = SigningSession::from_transaction(&transaction).unwrap();
+        assert_eq!(
+            session.missing_signers().collect::<Vec<_>>(),
+            vec![&payer.pubkey()]
+        );
+        assert_eq!(
+            session.to_partial_transaction(),
+            VersionedTransaction::from(transaction.clone())
+        );
+
+        session.sign(&[&payer]).unwrap();
+        transaction.partial_sign(&[&payer], recent_blockhash);
+        assert_eq!(
+            session
+                .try_into_transaction()
+                .unwrap()
+                .into_legacy_transaction(),
+            Some(transaction.clone())
+        );
+
+        transaction.signatures[0] = Signature::from([1; 64]);
+        assert_eq!(
+            SigningSession::from_transaction(&transaction),
+            Err(SigningSessionError::InvalidSignature(payer.pubkey()))
+        );
+    }
+
+    #[test]
+    fn test_versioned_message() {
+        let payer = Keypair::new();
+        let alice = Keypair::new();
+        let instruction = Instruction::new_with_bytes(
+            Address::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(alice.pubkey(), true)],
+        );
+        let message = VersionedMessage::V0(
+            v0::Message::try_compile(&payer.pubkey(), &[instruction], &[], Hash::new_unique())
+                .unwrap(),
+        );
+
+        let mut session = SigningSession::new(message.clone()).unwrap();
+        let mut alice_session = session.clone();
+        alice_session.sign(&[&alice]).unwrap();
+        session.sign(&[&payer]).unwrap();
+        session.merge(&alice_session).unwrap();
+        assert_eq!(
+            session.try_into_transaction(),
+            Ok(VersionedTransaction::try_new(message, &[&payer, &alice]).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_invalid_message() {
+        let mut message = Message::new(&[], None);
+        message.header.num_required_signatures = 1;
+        assert_eq!(
+            SigningSession::new(VersionedMessage::Legacy(message)),
+            Err(SigningSessionError::InvalidMessage)
+        );
+    }
+}
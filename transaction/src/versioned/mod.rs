@@ -6,7 +6,10 @@ use solana_signer::{signers::Signers, SignerError};
 use wincode::{containers, len::ShortU16Len, SchemaRead, SchemaWrite};
 use {
     crate::Transaction,
-    solana_message::{inline_nonce::is_advance_nonce_instruction_data, VersionedMessage},
+    solana_message::{
+        inline_nonce::is_advance_nonce_instruction_data, transaction_size::TransactionSize,
+        VersionedMessage,
+    },
     solana_sanitize::SanitizeError,
     solana_sdk_ids::system_program,
     solana_signature::Signature,
@@ -207,6 +210,14 @@ impl VersionedTransaction {
             .collect()
     }
 
+    /// Compute the size of this transaction once serialized, broken down by
+    /// component, without serializing it.
+    pub fn transaction_size(&self) -> TransactionSize {
+        self.message
+            .transaction_size()
+            .with_num_signatures(self.signatures.len())
+    }
+
     /// Returns true if transaction begins with an advance nonce instruction.
     pub fn uses_durable_nonce(&self) -> bool {
         let message = &self.message;
@@ -231,7 +242,10 @@ mod tests {
         solana_hash::Hash,
         solana_instruction::{AccountMeta, Instruction},
         solana_keypair::Keypair,
-        solana_message::Message as LegacyMessage,
+        solana_message::{
+            transaction_size::TransactionSizeComponent, v0, AddressLookupTableAccount,
+            Message as LegacyMessage,
+        },
         solana_pubkey::Pubkey,
         solana_signer::Signer,
         solana_system_interface::instruction as system_instruction,
@@ -355,6 +369,55 @@ mod tests {
         assert!(!tx.uses_durable_nonce());
     }
 
+    #[test]
+    fn test_transaction_size() {
+        let payer = Keypair::new();
+        let accounts: Vec<AccountMeta> = (0..64)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect();
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[1; 64], accounts);
+        let address_lookup_table_account = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: instruction
+                .accounts
+                .iter()
+                .map(|meta| meta.pubkey)
+                .collect(),
+        };
+
+        let legacy_tx = VersionedTransaction::try_new(
+            VersionedMessage::Legacy(LegacyMessage::new(
+                std::slice::from_ref(&instruction),
+                Some(&payer.pubkey()),
+            )),
+            &[&payer],
+        )
+        .unwrap();
+        let size = legacy_tx.transaction_size();
+        assert_eq!(size.total(), bincode::serialize(&legacy_tx).unwrap().len());
+        assert_eq!(
+            size.check_fits_in_packet().unwrap_err().largest_component,
+            TransactionSizeComponent::AccountKeys
+        );
+
+        let v0_tx = VersionedTransaction::try_new(
+            VersionedMessage::V0(
+                v0::Message::try_compile(
+                    &payer.pubkey(),
+                    &[instruction],
+                    &[address_lookup_table_account],
+                    Hash::default(),
+                )
+                .unwrap(),
+            ),
+            &[&payer],
+        )
+        .unwrap();
+        let size = v0_tx.transaction_size();
+        assert_eq!(size.total(), bincode::serialize(&v0_tx).unwrap().len());
+        assert!(size.fits_in_packet());
+    }
+
     #[test]
     fn test_sanitize_signatures_inner() {
         assert_eq!(
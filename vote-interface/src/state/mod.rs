@@ -24,6 +24,12 @@ pub mod vote_state_v3;
 pub use vote_state_v3::VoteStateV3;
 pub mod vote_state_v4;
 pub use vote_state_v4::VoteStateV4;
+mod vote_processor;
+pub use vote_processor::compute_vote_latency;
+pub mod vote_state_canonical;
+pub use vote_state_canonical::{CanonicalVoteState, VoteStateConversionError, VoteStateVersion};
+pub mod vote_state_view;
+pub use vote_state_view::VoteStateView;
 mod vote_instruction_data;
 pub use vote_instruction_data::*;
 #[cfg(any(target_os = "solana", feature = "bincode"))]
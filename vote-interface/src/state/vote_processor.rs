@@ -0,0 +1,713 @@
+//! Application of votes to a [`VoteStateV4`].
+//!
+//! These are the rules the vote program applies when it processes a
+//! `TowerSync` or `VoteStateUpdate` instruction: the proposed tower is checked
+//! against the slot hashes of the fork the vote landed on, lockouts are
+//! checked against the current tower, and the votes rooted by the proposed
+//! tower are awarded credits according to their latency. The implementation
+//! is pure, so that recorded vote transactions can be replayed offline to
+//! reproduce the roots and credits of a vote account.
+//!
+//! Authorization of the vote is not checked here, the caller is expected to
+//! only replay votes signed by the authorized voter.
+
+use {
+    super::{
+        BlockTimestamp, LandedVote, Lockout, TowerSync, VoteStateUpdate, VoteStateV4,
+        MAX_EPOCH_CREDITS_HISTORY, MAX_LOCKOUT_HISTORY, VOTE_CREDITS_GRACE_SLOTS,
+        VOTE_CREDITS_MAXIMUM_PER_SLOT,
+    },
+    crate::error::VoteError,
+    solana_clock::{Epoch, Slot, UnixTimestamp},
+    solana_hash::Hash,
+    std::{cmp::Ordering, collections::VecDeque},
+};
+
+/// Number of slots between the slot that was voted on and the slot in which
+/// the vote landed, saturating at `u8::MAX`.
+pub fn compute_vote_latency(voted_for_slot: Slot, current_slot: Slot) -> u8 {
+    u8::try_from(current_slot.saturating_sub(voted_for_slot)).unwrap_or(u8::MAX)
+}
+
+impl VoteStateV4 {
+    /// Applies `tower_sync` to the vote state, as the vote program does when
+    /// the vote lands in `current_slot` of `epoch`.
+    ///
+    /// `slot_hashes` is the content of the `SlotHashes` sysvar of the bank
+    /// the vote landed in, ordered from newest to oldest slot. The vote state
+    /// is left unchanged if an error is returned.
+    pub fn process_tower_sync(
+        &mut self,
+        slot_hashes: &[(Slot, Hash)],
+        epoch: Epoch,
+        current_slot: Slot,
+        tower_sync: TowerSync,
+    ) -> Result<(), VoteError> {
+        let TowerSync {
+            mut lockouts,
+            mut root,
+            hash,
+            timestamp,
+            ..
+        } = tower_sync;
+        self.check_and_filter_proposed_vote_state(&mut lockouts, &mut root, &hash, slot_hashes)?;
+        self.process_new_vote_state(
+            lockouts.into_iter().map(LandedVote::from).collect(),
+            root,
+            timestamp,
+            epoch,
+            current_slot,
+        )
+    }
+
+    /// Applies `vote_state_update` to the vote state, as the vote program
+    /// does when the vote lands in `current_slot` of `epoch`.
+    ///
+    /// See [`process_tower_sync`](Self::process_tower_sync).
+    pub fn process_vote_state_update(
+        &mut self,
+        slot_hashes: &[(Slot, Hash)],
+        epoch: Epoch,
+        current_slot: Slot,
+        vote_state_update: VoteStateUpdate,
+    ) -> Result<(), VoteError> {
+        let VoteStateUpdate {
+            mut lockouts,
+            mut root,
+            hash,
+            timestamp,
+        } = vote_state_update;
+        self.check_and_filter_proposed_vote_state(&mut lockouts, &mut root, &hash, slot_hashes)?;
+        self.process_new_vote_state(
+            lockouts.into_iter().map(LandedVote::from).collect(),
+            root,
+            timestamp,
+            epoch,
+            current_slot,
+        )
+    }
+
+    /// Checks that the proposed tower is on the fork described by
+    /// `slot_hashes`, and removes the votes that are too old to be checked
+    /// and are not in the current tower.
+    ///
+    /// A proposed root that is older than the slot hashes history is replaced
+    /// by the newest vote of the current tower that is not newer than it, or
+    /// by the current root.
+    pub fn check_and_filter_proposed_vote_state(
+        &self,
+        proposed_lockouts: &mut VecDeque<Lockout>,
+        proposed_root: &mut Option<Slot>,
+        proposed_hash: &Hash,
+        slot_hashes: &[(Slot, Hash)],
+    ) -> Result<(), VoteError> {
+        let Some(last_proposed_slot) = proposed_lockouts.back().map(Lockout::slot) else {
+            return Err(VoteError::EmptySlots);
+        };
+
+        // the proposed tower must be newer than the current one
+        if let Some(last_vote_slot) = self.votes.back().map(LandedVote::slot) {
+            if last_proposed_slot <= last_vote_slot {
+                return Err(VoteError::VoteTooOld);
+            }
+        }
+
+        let Some(&(earliest_slot_hash_in_history, _)) = slot_hashes.last() else {
+            return Err(VoteError::SlotsMismatch);
+        };
+        if last_proposed_slot < earliest_slot_hash_in_history {
+            return Err(VoteError::VoteTooOld);
+        }
+
+        // a root older than the history can't be checked, fall back to the
+        // newest root this vote state can vouch for
+        if let Some(root) = *proposed_root {
+            if root < earliest_slot_hash_in_history {
+                *proposed_root = self
+                    .votes
+                    .iter()
+                    .rev()
+                    .map(LandedVote::slot)
+                    .find(|slot| *slot <= root)
+                    .or(self.root_slot);
+            }
+        }
+
+        // `proposed_lockouts` is ordered from oldest to newest slot, and
+        // `slot_hashes` from newest to oldest slot, so walk `slot_hashes`
+        // backwards. The root is checked first, if there is one.
+        let mut root_to_check = *proposed_root;
+        let mut proposed_lockouts_index = 0;
+        let mut slot_hashes_index = slot_hashes.len();
+        let mut proposed_lockouts_indices_to_filter = vec![];
+
+        while proposed_lockouts_index < proposed_lockouts.len() && slot_hashes_index > 0 {
+            let proposed_vote_slot = match root_to_check {
+                Some(root) => root,
+                None => proposed_lockouts[proposed_lockouts_index].slot(),
+            };
+            if root_to_check.is_none() {
+                if let Some(previous_lockout) = proposed_lockouts_index
+                    .checked_sub(1)
+                    .map(|index| &proposed_lockouts[index])
+                {
+                    if proposed_vote_slot <= previous_lockout.slot() {
+                        return Err(VoteError::SlotsNotOrdered);
+                    }
+                }
+            }
+            let ancestor_slot = slot_hashes[slot_hashes_index.saturating_sub(1)].0;
+
+            match proposed_vote_slot.cmp(&ancestor_slot) {
+                Ordering::Less => {
+                    if slot_hashes_index != slot_hashes.len() {
+                        // the slot is recent enough to be in the history but
+                        // isn't, so it is on another fork
+                        return Err(if root_to_check.is_some() {
+                            VoteError::RootOnDifferentFork
+                        } else {
+                            VoteError::SlotsMismatch
+                        });
+                    }
+                    // the slot is older than the history
+                    if proposed_vote_slot >= earliest_slot_hash_in_history {
+                        return Err(VoteError::AssertionFailed);
+                    }
+                    if root_to_check.is_some() {
+                        root_to_check = None;
+                    } else {
+                        if !self.contains_slot(proposed_vote_slot) {
+                            proposed_lockouts_indices_to_filter.push(proposed_lockouts_index);
+                        }
+                        proposed_lockouts_index = proposed_lockouts_index.saturating_add(1);
+                    }
+                }
+                Ordering::Greater => {
+                    slot_hashes_index = slot_hashes_index.saturating_sub(1);
+                }
+                Ordering::Equal => {
+                    if root_to_check.is_some() {
+                        root_to_check = None;
+                    } else {
+                        proposed_lockouts_index = proposed_lockouts_index.saturating_add(1);
+                        slot_hashes_index = slot_hashes_index.saturating_sub(1);
+                    }
+                }
+            }
+        }
+
+        if proposed_lockouts_index != proposed_lockouts.len() {
+            // the last proposed slot is not in the history
+            return Err(VoteError::SlotsMismatch);
+        }
+
+        let (slot, hash) = &slot_hashes[slot_hashes_index];
+        if *slot != last_proposed_slot {
+            return Err(VoteError::AssertionFailed);
+        }
+        if hash != proposed_hash {
+            return Err(VoteError::SlotHashMismatch);
+        }
+
+        let mut index = 0;
+        let mut indices_to_filter = proposed_lockouts_indices_to_filter.into_iter().peekable();
+        proposed_lockouts.retain(|_| {
+            let retain = indices_to_filter.next_if_eq(&index).is_none();
+            index = index.saturating_add(1);
+            retain
+        });
+
+        Ok(())
+    }
+
+    /// Replaces the tower of the vote state with `new_state` and `new_root`,
+    /// after checking that the transition respects the lockouts of the
+    /// current tower, and awards credits for the newly rooted votes.
+    ///
+    /// The latency of the votes already in the current tower is preserved,
+    /// the latency of new votes is computed from `current_slot`.
+    pub fn process_new_vote_state(
+        &mut self,
+        mut new_state: VecDeque<LandedVote>,
+        new_root: Option<Slot>,
+        timestamp: Option<UnixTimestamp>,
+        epoch: Epoch,
+        current_slot: Slot,
+    ) -> Result<(), VoteError> {
+        let Some(last_slot) = new_state.back().map(LandedVote::slot) else {
+            return Err(VoteError::EmptySlots);
+        };
+        if new_state.len() > MAX_LOCKOUT_HISTORY {
+            return Err(VoteError::TooManyVotes);
+        }
+
+        match (new_root, self.root_slot) {
+            (Some(new_root), Some(current_root)) if new_root < current_root => {
+                return Err(VoteError::RootRollBack);
+            }
+            (None, Some(_)) => return Err(VoteError::RootRollBack),
+            _ => (),
+        }
+
+        // the votes must be ordered by slot, with decreasing confirmation
+        // counts, and each vote must be within the lockout of the previous
+        let mut previous_vote: Option<&LandedVote> = None;
+        for vote in &new_state {
+            if vote.confirmation_count() == 0 {
+                return Err(VoteError::ZeroConfirmations);
+            }
+            if vote.confirmation_count() > MAX_LOCKOUT_HISTORY as u32 {
+                return Err(VoteError::ConfirmationTooLarge);
+            }
+            // towers without a root may report a root of 0
+            if let Some(new_root) = new_root {
+                if vote.slot() <= new_root && new_root != Slot::default() {
+                    return Err(VoteError::SlotSmallerThanRoot);
+                }
+            }
+            if let Some(previous_vote) = previous_vote {
+                if previous_vote.slot() >= vote.slot() {
+                    return Err(VoteError::SlotsNotOrdered);
+                }
+                if previous_vote.confirmation_count() <= vote.confirmation_count() {
+                    return Err(VoteError::ConfirmationsNotOrdered);
+                }
+                if vote.slot() > previous_vote.lockout.last_locked_out_slot() {
+                    return Err(VoteError::NewVoteStateLockoutMismatch);
+                }
+            }
+            previous_vote = Some(vote);
+        }
+
+        // the votes of the current tower up to the new root get rooted
+        let mut current_vote_state_index = 0;
+        let mut earned_credits = 0_u64;
+        if let Some(new_root) = new_root {
+            while current_vote_state_index < self.votes.len()
+                && self.votes[current_vote_state_index].slot() <= new_root
+            {
+                earned_credits = earned_credits
+                    .saturating_add(self.credits_for_vote_at_index(current_vote_state_index));
+                current_vote_state_index = current_vote_state_index.saturating_add(1);
+            }
+        }
+
+        // the remaining votes of the current tower that are missing from the
+        // new tower must have expired
+        let mut new_vote_state_index = 0;
+        while current_vote_state_index < self.votes.len() && new_vote_state_index < new_state.len()
+        {
+            let current_vote = &self.votes[current_vote_state_index];
+            let new_vote = &mut new_state[new_vote_state_index];
+            match current_vote.slot().cmp(&new_vote.slot()) {
+                Ordering::Less => {
+                    if current_vote.lockout.last_locked_out_slot() >= new_vote.slot() {
+                        return Err(VoteError::LockoutConflict);
+                    }
+                    current_vote_state_index = current_vote_state_index.saturating_add(1);
+                }
+                Ordering::Equal => {
+                    if new_vote.confirmation_count() < current_vote.confirmation_count() {
+                        return Err(VoteError::ConfirmationRollBack);
+                    }
+                    new_vote.latency = current_vote.latency;
+                    current_vote_state_index = current_vote_state_index.saturating_add(1);
+                    new_vote_state_index = new_vote_state_index.saturating_add(1);
+                }
+                Ordering::Greater => {
+                    new_vote_state_index = new_vote_state_index.saturating_add(1);
+                }
+            }
+        }
+
+        if let Some(timestamp) = timestamp {
+            self.process_timestamp(last_slot, timestamp)?;
+        }
+
+        for new_vote in new_state.iter_mut() {
+            if new_vote.latency == 0 {
+                new_vote.latency = compute_vote_latency(new_vote.slot(), current_slot);
+            }
+        }
+        if self.root_slot != new_root {
+            self.increment_credits(epoch, earned_credits);
+        }
+        self.root_slot = new_root;
+        self.votes = new_state;
+
+        Ok(())
+    }
+
+    /// Number of credits awarded to the vote at `index` of the tower once it
+    /// is rooted.
+    ///
+    /// Votes landing within [`VOTE_CREDITS_GRACE_SLOTS`] of the slot they
+    /// vote on earn [`VOTE_CREDITS_MAXIMUM_PER_SLOT`] credits, one credit
+    /// less for every additional slot of latency, with a minimum of one
+    /// credit. Votes without a recorded latency earn one credit.
+    pub fn credits_for_vote_at_index(&self, index: usize) -> u64 {
+        let latency = self
+            .votes
+            .get(index)
+            .map_or(0, |landed_vote| landed_vote.latency);
+        if latency == 0 {
+            return 1;
+        }
+        match latency.checked_sub(VOTE_CREDITS_GRACE_SLOTS) {
+            None | Some(0) => u64::from(VOTE_CREDITS_MAXIMUM_PER_SLOT),
+            Some(diff) => match VOTE_CREDITS_MAXIMUM_PER_SLOT.checked_sub(diff) {
+                None | Some(0) => 1,
+                Some(credits) => u64::from(credits),
+            },
+        }
+    }
+
+    /// Adds `credits` to the credits earned in `epoch`.
+    ///
+    /// An entry is only started for a new epoch if credits were earned in the
+    /// previous one, otherwise the previous entry is reused. At most
+    /// [`MAX_EPOCH_CREDITS_HISTORY`] entries are kept.
+    pub fn increment_credits(&mut self, epoch: Epoch, credits: u64) {
+        match self.epoch_credits.last_mut() {
+            None => self.epoch_credits.push((epoch, credits, 0)),
+            Some(last) if last.0 == epoch => last.1 = last.1.saturating_add(credits),
+            Some(last) => {
+                let (_, last_credits, last_prev_credits) = *last;
+                if last_credits != last_prev_credits {
+                    self.epoch_credits.push((
+                        epoch,
+                        last_credits.saturating_add(credits),
+                        last_credits,
+                    ));
+                } else {
+                    *last = (
+                        epoch,
+                        last_credits.saturating_add(credits),
+                        last_prev_credits,
+                    );
+                }
+                if self.epoch_credits.len() > MAX_EPOCH_CREDITS_HISTORY {
+                    self.epoch_credits.remove(0);
+                }
+            }
+        }
+    }
+
+    /// Records `timestamp` as the timestamp of `slot`, which must not be older
+    /// than the last recorded timestamp.
+    pub fn process_timestamp(
+        &mut self,
+        slot: Slot,
+        timestamp: UnixTimestamp,
+    ) -> Result<(), VoteError> {
+        let new_timestamp = BlockTimestamp { slot, timestamp };
+        if slot < self.last_timestamp.slot
+            || timestamp < self.last_timestamp.timestamp
+            || (slot == self.last_timestamp.slot
+                && new_timestamp != self.last_timestamp
+                && self.last_timestamp.slot != 0)
+        {
+            return Err(VoteError::TimestampTooOld);
+        }
+        self.last_timestamp = new_timestamp;
+        Ok(())
+    }
+
+    /// Whether the tower has a vote for `slot`.
+    pub fn contains_slot(&self, slot: Slot) -> bool {
+        self.votes
+            .binary_search_by(|vote| vote.slot().cmp(&slot))
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use super::*;
+
+    fn hash(slot: Slot) -> Hash {
+        Hash::new_from_array([slot as u8; 32])
+    }
+
+    /// Slot hashes of a bank at `current_slot` on a fork containing every slot.
+    fn slot_hashes(current_slot: Slot) -> Vec<(Slot, Hash)> {
+        (0..current_slot)
+            .rev()
+            .map(|slot| (slot, hash(slot)))
+            .collect()
+    }
+
+    /// A tower with `lockouts`, voting on the hash of its last slot.
+    fn tower(lockouts: Vec<(Slot, u32)>) -> TowerSync {
+        let tower_sync = TowerSync::from(lockouts);
+        TowerSync {
+            hash: hash(tower_sync.last_voted_slot().unwrap_or_default()),
+            ..tower_sync
+        }
+    }
+
+    /// Votes on `slot` with a tower of consecutive votes, landing in
+    /// `current_slot`.
+    fn vote(vote_state: &mut VoteStateV4, slot: Slot, current_slot: Slot) -> Result<(), VoteError> {
+        vote_state.process_tower_sync(
+            &slot_hashes(current_slot),
+            0,
+            current_slot,
+            TowerSync::new_from_slot(slot, hash(slot)),
+        )
+    }
+
+    #[test]
+    fn test_replay_consecutive_votes() {
+        let mut vote_state = VoteStateV4::default();
+        for slot in 0..31 {
+            vote(&mut vote_state, slot, slot + 1).unwrap();
+            assert_eq!(vote_state.votes.len(), slot as usize + 1);
+            assert_eq!(vote_state.root_slot, None);
+            assert_eq!(vote_state.credits(), 0);
+        }
+        assert!(vote_state.votes.iter().all(|vote| vote.latency == 1));
+        assert_eq!(vote_state.votes[0].confirmation_count(), 31);
+
+        for slot in 31..41 {
+            vote(&mut vote_state, slot, slot + 1).unwrap();
+        }
+        assert_eq!(vote_state.votes.len(), MAX_LOCKOUT_HISTORY);
+        assert_eq!(vote_state.root_slot, Some(9));
+        assert_eq!(
+            vote_state.credits(),
+            10 * u64::from(VOTE_CREDITS_MAXIMUM_PER_SLOT)
+        );
+        assert_eq!(vote_state.epoch_credits, vec![(0, 160, 0)]);
+    }
+
+    #[test]
+    fn test_replay_vote_state_update() {
+        let mut vote_state = VoteStateV4::default();
+        for slot in 0..40 {
+            let tower_sync = TowerSync::new_from_slot(slot, hash(slot));
+            let vote_state_update =
+                VoteStateUpdate::new(tower_sync.lockouts.clone(), tower_sync.root, hash(slot));
+            let mut expected = vote_state.clone();
+            expected
+                .process_tower_sync(&slot_hashes(slot + 3), 0, slot + 3, tower_sync)
+                .unwrap();
+            vote_state
+                .process_vote_state_update(&slot_hashes(slot + 3), 0, slot + 3, vote_state_update)
+                .unwrap();
+            assert_eq!(vote_state, expected);
+        }
+        assert_eq!(vote_state.root_slot, Some(8));
+        assert!(vote_state.votes.iter().all(|vote| vote.latency == 3));
+        // one slot over the grace period
+        assert_eq!(vote_state.credits(), 9 * 15);
+    }
+
+    #[test]
+    fn test_replay_skipped_slots() {
+        let mut vote_state = VoteStateV4::default();
+        let process = |vote_state: &mut VoteStateV4, lockouts, current_slot| {
+            vote_state.process_tower_sync(
+                &slot_hashes(current_slot),
+                0,
+                current_slot,
+                tower(lockouts),
+            )
+        };
+        process(&mut vote_state, vec![(1, 1)], 2).unwrap();
+        process(&mut vote_state, vec![(1, 2), (2, 1)], 3).unwrap();
+        // a vote for slot 5 expires the vote for 2, but not the one for 1
+        process(&mut vote_state, vec![(1, 2), (5, 1)], 6).unwrap();
+        assert_eq!(
+            vote_state.votes,
+            VecDeque::from(vec![
+                LandedVote {
+                    latency: 1,
+                    lockout: Lockout::new_with_confirmation_count(1, 2),
+                },
+                LandedVote {
+                    latency: 1,
+                    lockout: Lockout::new_with_confirmation_count(5, 1),
+                },
+            ])
+        );
+
+        // the vote for 5 doesn't expire until slot 8
+        assert_eq!(
+            process(&mut vote_state, vec![(1, 3), (6, 1)], 7),
+            Err(VoteError::LockoutConflict)
+        );
+        process(&mut vote_state, vec![(1, 3), (5, 2), (6, 1)], 7).unwrap();
+        assert_eq!(vote_state.votes[2].latency, 1);
+    }
+
+    #[test]
+    fn test_replay_errors() {
+        let mut vote_state = VoteStateV4::default();
+        vote(&mut vote_state, 5, 6).unwrap();
+        let expected = vote_state.clone();
+
+        let errors = [
+            (TowerSync::default(), VoteError::EmptySlots),
+            (TowerSync::new_from_slot(5, hash(5)), VoteError::VoteTooOld),
+            (
+                TowerSync::new_from_slot(9, hash(8)),
+                VoteError::SlotHashMismatch,
+            ),
+            (
+                TowerSync::new_from_slot(20, hash(20)),
+                VoteError::SlotsMismatch,
+            ),
+            (tower(vec![(8, 2), (7, 1)]), VoteError::SlotsNotOrdered),
+            (
+                tower(vec![(5, 1), (9, 2)]),
+                VoteError::ConfirmationsNotOrdered,
+            ),
+            (tower(vec![(5, 2), (9, 0)]), VoteError::ZeroConfirmations),
+            (
+                tower(vec![(5, 1), (9, 1)]),
+                VoteError::ConfirmationsNotOrdered,
+            ),
+        ];
+        for (tower_sync, error) in errors {
+            assert_eq!(
+                vote_state.process_tower_sync(&slot_hashes(10), 0, 10, tower_sync),
+                Err(error)
+            );
+            assert_eq!(vote_state, expected);
+        }
+        assert_eq!(
+            vote_state.process_tower_sync(&[], 0, 10, TowerSync::new_from_slot(9, hash(9))),
+            Err(VoteError::SlotsMismatch)
+        );
+
+        let mut tower_sync = tower(vec![(9, 1)]);
+        tower_sync.root = Some(3);
+        let slot_hashes_without_root = slot_hashes(10)
+            .into_iter()
+            .filter(|(slot, _)| *slot != 3)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vote_state.process_tower_sync(&slot_hashes_without_root, 0, 10, tower_sync),
+            Err(VoteError::RootOnDifferentFork)
+        );
+
+        // roots can't roll back
+        vote_state.root_slot = Some(3);
+        let tower_sync = tower(vec![(9, 1)]);
+        assert_eq!(
+            vote_state.process_tower_sync(&slot_hashes(10), 0, 10, tower_sync),
+            Err(VoteError::RootRollBack)
+        );
+    }
+
+    #[test]
+    fn test_replay_old_votes_filtered() {
+        let mut vote_state = VoteStateV4::default();
+        vote_state
+            .process_tower_sync(&slot_hashes(5), 0, 5, tower(vec![(4, 1)]))
+            .unwrap();
+        // slot hashes only remember slots 5 and above
+        let slot_hashes = slot_hashes(12)
+            .into_iter()
+            .filter(|(slot, _)| *slot >= 5)
+            .collect::<Vec<_>>();
+        vote_state
+            .process_tower_sync(
+                &slot_hashes,
+                0,
+                12,
+                tower(vec![(3, 4), (4, 3), (6, 2), (7, 1)]),
+            )
+            .unwrap();
+        // 3 is too old and was never voted on, 4 keeps its latency
+        assert_eq!(
+            vote_state
+                .votes
+                .iter()
+                .map(|vote| (vote.slot(), vote.confirmation_count(), vote.latency))
+                .collect::<Vec<_>>(),
+            vec![(4, 3, 1), (6, 2, 6), (7, 1, 5)]
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let mut vote_state = VoteStateV4::default();
+        let tower_sync = |slot, timestamp| TowerSync {
+            timestamp: Some(timestamp),
+            ..TowerSync::new_from_slot(slot, hash(slot))
+        };
+        vote_state
+            .process_tower_sync(&slot_hashes(2), 0, 2, tower_sync(1, 100))
+            .unwrap();
+        assert_eq!(
+            vote_state.last_timestamp,
+            BlockTimestamp {
+                slot: 1,
+                timestamp: 100
+            }
+        );
+        let expected = vote_state.clone();
+        assert_eq!(
+            vote_state.process_tower_sync(&slot_hashes(3), 0, 3, tower_sync(2, 99)),
+            Err(VoteError::TimestampTooOld)
+        );
+        assert_eq!(vote_state, expected);
+        vote_state
+            .process_tower_sync(&slot_hashes(3), 0, 3, tower_sync(2, 100))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_credits_for_vote_at_index() {
+        let mut vote_state = VoteStateV4::default();
+        let expected = [
+            (0, 1),
+            (1, 16),
+            (2, 16),
+            (3, 15),
+            (10, 8),
+            (17, 1),
+            (18, 1),
+            (255, 1),
+        ];
+        for (latency, _) in expected {
+            vote_state.votes.push_back(LandedVote {
+                latency,
+                lockout: Lockout::new(0),
+            });
+        }
+        for (index, (_, credits)) in expected.into_iter().enumerate() {
+            assert_eq!(vote_state.credits_for_vote_at_index(index), credits);
+        }
+        assert_eq!(vote_state.credits_for_vote_at_index(expected.len()), 1);
+    }
+
+    #[test]
+    fn test_increment_credits() {
+        let mut vote_state = VoteStateV4::default();
+        vote_state.increment_credits(0, 0);
+        vote_state.increment_credits(1, 0);
+        assert_eq!(vote_state.epoch_credits, vec![(1, 0, 0)]);
+        vote_state.increment_credits(1, 5);
+        vote_state.increment_credits(2, 3);
+        vote_state.increment_credits(2, 1);
+        assert_eq!(vote_state.epoch_credits, vec![(1, 5, 0), (2, 9, 5)]);
+        assert_eq!(vote_state.credits(), 9);
+
+        for epoch in 3..100 {
+            vote_state.increment_credits(epoch, 1);
+        }
+        assert_eq!(vote_state.epoch_credits.len(), MAX_EPOCH_CREDITS_HISTORY);
+        assert_eq!(vote_state.epoch_credits.last(), Some(&(99, 106, 105)));
+    }
+
+    #[test]
+    fn test_compute_vote_latency() {
+        assert_eq!(compute_vote_latency(10, 12), 2);
+        assert_eq!(compute_vote_latency(12, 10), 0);
+        assert_eq!(compute_vote_latency(0, 1000), u8::MAX);
+    }
+}
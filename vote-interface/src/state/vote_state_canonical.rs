@@ -0,0 +1,628 @@
+//! A vote state representation covering every vote state version.
+//!
+//! [`CanonicalVoteState`] holds the union of the fields of the
+//! [`VoteStateVersions`] layouts, so that a vote state can be read from any
+//! version and written back to any version. Converting to a version fails
+//! with a [`VoteStateConversionError`] instead of silently dropping fields
+//! the layout of that version can't represent.
+
+#[cfg(any(target_os = "solana", feature = "bincode"))]
+use solana_instruction_error::InstructionError;
+use {
+    super::{
+        vote_state_1_14_11::VoteState1_14_11, BlockTimestamp, CircBuf, LandedVote, VoteStateV3,
+        VoteStateV4, VoteStateVersions, BLS_PUBLIC_KEY_COMPRESSED_SIZE,
+    },
+    crate::authorized_voters::AuthorizedVoters,
+    core::fmt,
+    solana_clock::{Epoch, Slot},
+    solana_pubkey::Pubkey,
+    std::collections::VecDeque,
+};
+
+/// Basis points of a 100% commission.
+const MAX_COMMISSION_BPS: u16 = 10_000;
+
+/// Basis points per percentage point of the commission of older versions.
+const BPS_PER_PERCENT: u16 = 100;
+
+/// A layout of [`VoteStateVersions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoteStateVersion {
+    V1_14_11,
+    V3,
+    V4,
+}
+
+impl VoteStateVersion {
+    /// Size of the account data of a vote account with this layout.
+    pub fn size_of(self) -> usize {
+        match self {
+            Self::V1_14_11 => VoteState1_14_11::size_of(),
+            Self::V3 => VoteStateV3::size_of(),
+            Self::V4 => VoteStateV4::size_of(),
+        }
+    }
+}
+
+/// Reasons a vote state can't be converted to a given version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoteStateConversionError {
+    /// The vote state is [`VoteStateVersions::Uninitialized`].
+    Uninitialized,
+    /// The inflation rewards commission isn't a whole percentage.
+    InflationRewardsCommission(u16),
+    /// The block revenue commission isn't 100%.
+    BlockRevenueCommission(u16),
+    /// The inflation rewards collector isn't the vote account.
+    InflationRewardsCollector(Pubkey),
+    /// The block revenue collector isn't the node.
+    BlockRevenueCollector(Pubkey),
+    /// Delegator rewards are pending distribution.
+    PendingDelegatorRewards(u64),
+    /// A BLS public key is set.
+    BlsPubkey,
+    /// The vote for the slot has a recorded latency.
+    VoteLatency(Slot),
+    /// Prior voters are recorded, which V4 doesn't track.
+    PriorVoters,
+    /// The account data could not be read or written.
+    #[cfg(any(target_os = "solana", feature = "bincode"))]
+    AccountData(InstructionError),
+}
+
+impl core::error::Error for VoteStateConversionError {}
+
+impl fmt::Display for VoteStateConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Uninitialized => f.write_str("vote state is uninitialized"),
+            Self::InflationRewardsCommission(bps) => write!(
+                f,
+                "inflation rewards commission of {bps} basis points is not a whole percentage"
+            ),
+            Self::BlockRevenueCommission(bps) => write!(
+                f,
+                "block revenue commission of {bps} basis points can only be represented in V4"
+            ),
+            Self::InflationRewardsCollector(collector) => write!(
+                f,
+                "inflation rewards collector {collector} can only be represented in V4"
+            ),
+            Self::BlockRevenueCollector(collector) => write!(
+                f,
+                "block revenue collector {collector} can only be represented in V4"
+            ),
+            Self::PendingDelegatorRewards(lamports) => write!(
+                f,
+                "pending delegator rewards of {lamports} lamports can only be represented in V4"
+            ),
+            Self::BlsPubkey => f.write_str("BLS public key can only be represented in V4"),
+            Self::VoteLatency(slot) => write!(
+                f,
+                "latency of the vote for slot {slot} can't be represented in V1_14_11"
+            ),
+            Self::PriorVoters => f.write_str("prior voters can't be represented in V4"),
+            #[cfg(any(target_os = "solana", feature = "bincode"))]
+            Self::AccountData(err) => write!(f, "invalid vote account data: {err}"),
+        }
+    }
+}
+
+/// The fields of all the vote state versions.
+///
+/// Fields a version doesn't have take the value implied by that version: the
+/// collectors of older versions are the vote account and the node, and their
+/// block revenue commission is 100%. Prior voters are only tracked by
+/// versions older than V4, so conversion to V4 fails if any are recorded.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CanonicalVoteState {
+    pub node_pubkey: Pubkey,
+    pub authorized_withdrawer: Pubkey,
+    pub inflation_rewards_collector: Pubkey,
+    pub block_revenue_collector: Pubkey,
+    pub inflation_rewards_commission_bps: u16,
+    pub block_revenue_commission_bps: u16,
+    pub pending_delegator_rewards: u64,
+    pub bls_pubkey_compressed: Option<[u8; BLS_PUBLIC_KEY_COMPRESSED_SIZE]>,
+    pub votes: VecDeque<LandedVote>,
+    pub root_slot: Option<Slot>,
+    pub authorized_voters: AuthorizedVoters,
+    pub prior_voters: CircBuf<(Pubkey, Epoch, Epoch)>,
+    pub epoch_credits: Vec<(Epoch, u64, u64)>,
+    pub last_timestamp: BlockTimestamp,
+}
+
+impl CanonicalVoteState {
+    /// Reads the vote state of any version from the account data of the vote
+    /// account `vote_pubkey`.
+    #[cfg(any(target_os = "solana", feature = "bincode"))]
+    pub fn deserialize(
+        input: &[u8],
+        vote_pubkey: &Pubkey,
+    ) -> Result<Self, VoteStateConversionError> {
+        let versions =
+            VoteStateVersions::deserialize(input).map_err(VoteStateConversionError::AccountData)?;
+        Self::from_versions(versions, vote_pubkey)
+    }
+
+    /// Writes the vote state as the account data of the vote account
+    /// `vote_pubkey`, in the layout of `version`.
+    ///
+    /// The account data has the size of [`VoteStateVersion::size_of`].
+    #[cfg(feature = "bincode")]
+    pub fn serialize(
+        self,
+        version: VoteStateVersion,
+        vote_pubkey: &Pubkey,
+    ) -> Result<Vec<u8>, VoteStateConversionError> {
+        let versions = self.into_versions(version, vote_pubkey)?;
+        let mut output = vec![0; version.size_of()];
+        VoteStateV4::serialize(&versions, &mut output)
+            .map_err(VoteStateConversionError::AccountData)?;
+        Ok(output)
+    }
+
+    /// Converts a vote state of any version of the vote account
+    /// `vote_pubkey`.
+    pub fn from_versions(
+        versions: VoteStateVersions,
+        vote_pubkey: &Pubkey,
+    ) -> Result<Self, VoteStateConversionError> {
+        match versions {
+            VoteStateVersions::Uninitialized => Err(VoteStateConversionError::Uninitialized),
+            VoteStateVersions::V1_14_11(vote_state) => {
+                Ok(Self::from_v1_14_11(*vote_state, vote_pubkey))
+            }
+            VoteStateVersions::V3(vote_state) => Ok(Self::from_v3(*vote_state, vote_pubkey)),
+            VoteStateVersions::V4(vote_state) => Ok(Self::from(*vote_state)),
+        }
+    }
+
+    /// Converts a V1_14_11 vote state of the vote account `vote_pubkey`.
+    pub fn from_v1_14_11(vote_state: VoteState1_14_11, vote_pubkey: &Pubkey) -> Self {
+        let VoteState1_14_11 {
+            node_pubkey,
+            authorized_withdrawer,
+            commission,
+            votes,
+            root_slot,
+            authorized_voters,
+            prior_voters,
+            epoch_credits,
+            last_timestamp,
+        } = vote_state;
+        Self::from_v3(
+            VoteStateV3 {
+                node_pubkey,
+                authorized_withdrawer,
+                commission,
+                votes: votes.into_iter().map(LandedVote::from).collect(),
+                root_slot,
+                authorized_voters,
+                prior_voters,
+                epoch_credits,
+                last_timestamp,
+            },
+            vote_pubkey,
+        )
+    }
+
+    /// Converts a V3 vote state of the vote account `vote_pubkey`.
+    pub fn from_v3(vote_state: VoteStateV3, vote_pubkey: &Pubkey) -> Self {
+        let VoteStateV3 {
+            node_pubkey,
+            authorized_withdrawer,
+            commission,
+            votes,
+            root_slot,
+            authorized_voters,
+            prior_voters,
+            epoch_credits,
+            last_timestamp,
+        } = vote_state;
+        Self {
+            node_pubkey,
+            authorized_withdrawer,
+            inflation_rewards_collector: *vote_pubkey,
+            block_revenue_collector: node_pubkey,
+            inflation_rewards_commission_bps: u16::from(commission).saturating_mul(BPS_PER_PERCENT),
+            block_revenue_commission_bps: MAX_COMMISSION_BPS,
+            pending_delegator_rewards: 0,
+            bls_pubkey_compressed: None,
+            votes,
+            root_slot,
+            authorized_voters,
+            prior_voters,
+            epoch_credits,
+            last_timestamp,
+        }
+    }
+
+    /// Converts to the layout of `version`.
+    pub fn into_versions(
+        self,
+        version: VoteStateVersion,
+        vote_pubkey: &Pubkey,
+    ) -> Result<VoteStateVersions, VoteStateConversionError> {
+        Ok(match version {
+            VoteStateVersion::V1_14_11 => {
+                VoteStateVersions::V1_14_11(Box::new(self.into_v1_14_11(vote_pubkey)?))
+            }
+            VoteStateVersion::V3 => VoteStateVersions::new_v3(self.into_v3(vote_pubkey)?),
+            VoteStateVersion::V4 => VoteStateVersions::new_v4(self.into_v4()?),
+        })
+    }
+
+    /// Converts to a V1_14_11 vote state of the vote account `vote_pubkey`.
+    ///
+    /// Fails if the vote state can't be represented as V3, or if a vote has a
+    /// recorded latency.
+    pub fn into_v1_14_11(
+        self,
+        vote_pubkey: &Pubkey,
+    ) -> Result<VoteState1_14_11, VoteStateConversionError> {
+        if let Some(vote) = self.votes.iter().find(|vote| vote.latency != 0) {
+            return Err(VoteStateConversionError::VoteLatency(vote.slot()));
+        }
+        self.into_v3(vote_pubkey).map(VoteState1_14_11::from)
+    }
+
+    /// Converts to a V3 vote state of the vote account `vote_pubkey`.
+    ///
+    /// Fails if a field only V4 has differs from the value implied by V3, or
+    /// if the inflation rewards commission isn't a whole percentage.
+    pub fn into_v3(self, vote_pubkey: &Pubkey) -> Result<VoteStateV3, VoteStateConversionError> {
+        let Self {
+            node_pubkey,
+            authorized_withdrawer,
+            inflation_rewards_collector,
+            block_revenue_collector,
+            inflation_rewards_commission_bps,
+            block_revenue_commission_bps,
+            pending_delegator_rewards,
+            bls_pubkey_compressed,
+            votes,
+            root_slot,
+            authorized_voters,
+            prior_voters,
+            epoch_credits,
+            last_timestamp,
+        } = self;
+        if inflation_rewards_collector != *vote_pubkey {
+            return Err(VoteStateConversionError::InflationRewardsCollector(
+                inflation_rewards_collector,
+            ));
+        }
+        if block_revenue_collector != node_pubkey {
+            return Err(VoteStateConversionError::BlockRevenueCollector(
+                block_revenue_collector,
+            ));
+        }
+        if block_revenue_commission_bps != MAX_COMMISSION_BPS {
+            return Err(VoteStateConversionError::BlockRevenueCommission(
+                block_revenue_commission_bps,
+            ));
+        }
+        if pending_delegator_rewards != 0 {
+            return Err(VoteStateConversionError::PendingDelegatorRewards(
+                pending_delegator_rewards,
+            ));
+        }
+        if bls_pubkey_compressed.is_some() {
+            return Err(VoteStateConversionError::BlsPubkey);
+        }
+        let commission = (inflation_rewards_commission_bps % BPS_PER_PERCENT == 0)
+            .then(|| u8::try_from(inflation_rewards_commission_bps / BPS_PER_PERCENT).ok())
+            .flatten()
+            .ok_or(VoteStateConversionError::InflationRewardsCommission(
+                inflation_rewards_commission_bps,
+            ))?;
+        Ok(VoteStateV3 {
+            node_pubkey,
+            authorized_withdrawer,
+            commission,
+            votes,
+            root_slot,
+            authorized_voters,
+            prior_voters,
+            epoch_credits,
+            last_timestamp,
+        })
+    }
+
+    /// Converts to a V4 vote state.
+    ///
+    /// Fails if prior voters are recorded. The vote program drops them when it
+    /// upgrades a vote state to V4, which can be done by clearing
+    /// [`prior_voters`](Self::prior_voters) first.
+    pub fn into_v4(self) -> Result<VoteStateV4, VoteStateConversionError> {
+        if self.prior_voters.last().is_some() {
+            return Err(VoteStateConversionError::PriorVoters);
+        }
+        Ok(VoteStateV4 {
+            node_pubkey: self.node_pubkey,
+            authorized_withdrawer: self.authorized_withdrawer,
+            inflation_rewards_collector: self.inflation_rewards_collector,
+            block_revenue_collector: self.block_revenue_collector,
+            inflation_rewards_commission_bps: self.inflation_rewards_commission_bps,
+            block_revenue_commission_bps: self.block_revenue_commission_bps,
+            pending_delegator_rewards: self.pending_delegator_rewards,
+            bls_pubkey_compressed: self.bls_pubkey_compressed,
+            votes: self.votes,
+            root_slot: self.root_slot,
+            authorized_voters: self.authorized_voters,
+            epoch_credits: self.epoch_credits,
+            last_timestamp: self.last_timestamp,
+        })
+    }
+}
+
+impl From<VoteStateV4> for CanonicalVoteState {
+    fn from(vote_state: VoteStateV4) -> Self {
+        let VoteStateV4 {
+            node_pubkey,
+            authorized_withdrawer,
+            inflation_rewards_collector,
+            block_revenue_collector,
+            inflation_rewards_commission_bps,
+            block_revenue_commission_bps,
+            pending_delegator_rewards,
+            bls_pubkey_compressed,
+            votes,
+            root_slot,
+            authorized_voters,
+            epoch_credits,
+            last_timestamp,
+        } = vote_state;
+        Self {
+            node_pubkey,
+            authorized_withdrawer,
+            inflation_rewards_collector,
+            block_revenue_collector,
+            inflation_rewards_commission_bps,
+            block_revenue_commission_bps,
+            pending_delegator_rewards,
+            bls_pubkey_compressed,
+            votes,
+            root_slot,
+            authorized_voters,
+            prior_voters: CircBuf::default(),
+            epoch_credits,
+            last_timestamp,
+        }
+    }
+}
+
+impl TryFrom<CanonicalVoteState> for VoteStateV4 {
+    type Error = VoteStateConversionError;
+
+    fn try_from(vote_state: CanonicalVoteState) -> Result<Self, Self::Error> {
+        vote_state.into_v4()
+    }
+}
+
+impl VoteStateVersions {
+    /// The layout of the vote state, or `None` if it is uninitialized.
+    pub fn version(&self) -> Option<VoteStateVersion> {
+        match self {
+            Self::Uninitialized => None,
+            Self::V1_14_11(_) => Some(VoteStateVersion::V1_14_11),
+            Self::V3(_) => Some(VoteStateVersion::V3),
+            Self::V4(_) => Some(VoteStateVersion::V4),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {
+        super::*,
+        crate::state::Lockout,
+        arbitrary::{Arbitrary, Unstructured},
+    };
+
+    fn arbitrary_versions() -> impl Iterator<Item = VoteStateVersions> {
+        (0..1000).map(|_| {
+            let raw_data: Vec<u8> = (0..std::mem::size_of::<VoteStateV3>() * 4)
+                .map(|_| rand::random::<u8>())
+                .collect();
+            VoteStateVersions::arbitrary(&mut Unstructured::new(&raw_data)).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_roundtrip_same_version() {
+        let vote_pubkey = Pubkey::new_unique();
+        for versions in arbitrary_versions() {
+            let version = versions.version().unwrap();
+            let canonical =
+                CanonicalVoteState::from_versions(versions.clone(), &vote_pubkey).unwrap();
+            assert_eq!(
+                canonical.into_versions(version, &vote_pubkey).unwrap(),
+                versions
+            );
+        }
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let vote_pubkey = Pubkey::new_unique();
+        for versions in arbitrary_versions() {
+            let canonical =
+                CanonicalVoteState::from_versions(versions.clone(), &vote_pubkey).unwrap();
+
+            // upgrades match the vote program's once the prior voters, which
+            // it drops, are cleared
+            if canonical.prior_voters.last().is_some() {
+                assert_eq!(
+                    canonical.clone().into_v4(),
+                    Err(VoteStateConversionError::PriorVoters)
+                );
+            }
+            let mut upgraded = canonical.clone();
+            upgraded.prior_voters = CircBuf::default();
+            let v4 = upgraded.into_v4().unwrap();
+            assert_eq!(
+                versions.clone().try_convert_to_v4(&vote_pubkey),
+                Ok(v4.clone())
+            );
+            if versions.version() != Some(VoteStateVersion::V4) {
+                let v3 = canonical.clone().into_v3(&vote_pubkey).unwrap();
+                assert_eq!(versions.try_convert_to_v3(), Ok(v3));
+            }
+
+            // a V4 state that didn't change V4 fields goes back to V3
+            let mut canonical = CanonicalVoteState::from(v4);
+            canonical.inflation_rewards_collector = vote_pubkey;
+            canonical.block_revenue_collector = canonical.node_pubkey;
+            canonical.block_revenue_commission_bps = MAX_COMMISSION_BPS;
+            canonical.pending_delegator_rewards = 0;
+            canonical.bls_pubkey_compressed = None;
+            canonical.inflation_rewards_commission_bps = canonical.inflation_rewards_commission_bps
+                % (MAX_COMMISSION_BPS + 1)
+                / BPS_PER_PERCENT
+                * BPS_PER_PERCENT;
+            let v3 = canonical.clone().into_v3(&vote_pubkey).unwrap();
+            assert_eq!(CanonicalVoteState::from_v3(v3, &vote_pubkey), canonical);
+        }
+    }
+
+    #[test]
+    fn test_downgrade_errors() {
+        let vote_pubkey = Pubkey::new_unique();
+        let node_pubkey = Pubkey::new_unique();
+        let canonical = CanonicalVoteState::from_v3(
+            VoteStateV3 {
+                node_pubkey,
+                commission: 42,
+                ..VoteStateV3::default()
+            },
+            &vote_pubkey,
+        );
+        assert_eq!(canonical.inflation_rewards_commission_bps, 4_200);
+        assert_eq!(
+            canonical.clone().into_v3(&vote_pubkey).unwrap().commission,
+            42
+        );
+
+        let collector = Pubkey::new_unique();
+        let cases: [(fn(&mut CanonicalVoteState), _); 7] = [
+            (
+                |vote_state| vote_state.inflation_rewards_commission_bps = 4_250,
+                VoteStateConversionError::InflationRewardsCommission(4_250),
+            ),
+            (
+                |vote_state| vote_state.block_revenue_commission_bps = 5_000,
+                VoteStateConversionError::BlockRevenueCommission(5_000),
+            ),
+            (
+                |vote_state| vote_state.pending_delegator_rewards = 7,
+                VoteStateConversionError::PendingDelegatorRewards(7),
+            ),
+            (
+                |vote_state| vote_state.bls_pubkey_compressed = Some([1; 48]),
+                VoteStateConversionError::BlsPubkey,
+            ),
+            (
+                |vote_state| vote_state.inflation_rewards_collector = Pubkey::default(),
+                VoteStateConversionError::InflationRewardsCollector(Pubkey::default()),
+            ),
+            (
+                |vote_state| vote_state.block_revenue_collector = Pubkey::default(),
+                VoteStateConversionError::BlockRevenueCollector(Pubkey::default()),
+            ),
+            (
+                |vote_state| {
+                    vote_state.votes.push_back(LandedVote {
+                        latency: 1,
+                        lockout: Lockout::new(9),
+                    })
+                },
+                VoteStateConversionError::VoteLatency(9),
+            ),
+        ];
+        for (modify, error) in cases {
+            let mut vote_state = canonical.clone();
+            modify(&mut vote_state);
+            assert_eq!(
+                vote_state.clone().into_v1_14_11(&vote_pubkey),
+                Err(error.clone())
+            );
+            if error != VoteStateConversionError::VoteLatency(9) {
+                assert_eq!(vote_state.clone().into_v3(&vote_pubkey), Err(error));
+            }
+            assert_eq!(
+                CanonicalVoteState::from(vote_state.clone().into_v4().unwrap()),
+                vote_state
+            );
+        }
+
+        // V3 is checked against its own vote account
+        assert_eq!(
+            canonical.clone().into_v3(&collector),
+            Err(VoteStateConversionError::InflationRewardsCollector(
+                vote_pubkey
+            ))
+        );
+        assert_eq!(
+            CanonicalVoteState::from_versions(VoteStateVersions::Uninitialized, &vote_pubkey),
+            Err(VoteStateConversionError::Uninitialized)
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let vote_pubkey = Pubkey::new_unique();
+        let canonical = CanonicalVoteState::from_v3(
+            VoteStateV3 {
+                node_pubkey: Pubkey::new_unique(),
+                authorized_voters: AuthorizedVoters::new(1, Pubkey::new_unique()),
+                commission: 10,
+                votes: VecDeque::from(vec![LandedVote::from(Lockout::new(3))]),
+                ..VoteStateV3::get_max_sized_vote_state()
+            },
+            &vote_pubkey,
+        );
+        for version in [
+            VoteStateVersion::V1_14_11,
+            VoteStateVersion::V3,
+            VoteStateVersion::V4,
+        ] {
+            let data = canonical.clone().serialize(version, &vote_pubkey).unwrap();
+            assert_eq!(data.len(), version.size_of());
+            assert!(VoteStateVersions::is_correct_size_and_initialized(&data));
+            let versions = VoteStateVersions::deserialize(&data).unwrap();
+            assert_eq!(versions.version(), Some(version));
+
+            assert_eq!(
+                CanonicalVoteState::deserialize(&data, &vote_pubkey),
+                Ok(canonical.clone())
+            );
+        }
+
+        // prior voters survive V3 but can't be written as V4
+        let mut canonical = canonical;
+        canonical.prior_voters.append((Pubkey::new_unique(), 0, 1));
+        let data = canonical
+            .clone()
+            .serialize(VoteStateVersion::V3, &vote_pubkey)
+            .unwrap();
+        assert_eq!(
+            CanonicalVoteState::deserialize(&data, &vote_pubkey),
+            Ok(canonical.clone())
+        );
+        assert_eq!(
+            canonical.serialize(VoteStateVersion::V4, &vote_pubkey),
+            Err(VoteStateConversionError::PriorVoters)
+        );
+
+        assert_eq!(
+            CanonicalVoteState::deserialize(&[0; 4], &vote_pubkey),
+            Err(VoteStateConversionError::AccountData(
+                InstructionError::InvalidAccountData
+            ))
+        );
+    }
+}
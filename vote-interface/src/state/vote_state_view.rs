@@ -0,0 +1,443 @@
+//! A zero-copy view of vote account data.
+//!
+//! [`VoteStateView`] reads the fields of a vote state directly from the
+//! account data of any [`VoteStateVersions`](super::VoteStateVersions)
+//! layout. The data is checked once when the view is created, after which the
+//! accessors don't allocate.
+
+use {
+    super::{
+        BlockTimestamp, LandedVote, Lockout, VoteStateVersion, BLS_PUBLIC_KEY_COMPRESSED_SIZE,
+    },
+    solana_clock::{Epoch, Slot, UnixTimestamp},
+    solana_instruction_error::InstructionError,
+    solana_pubkey::Pubkey,
+};
+
+const PUBKEY_SIZE: usize = 32;
+const U64_SIZE: usize = 8;
+
+/// Size of a vote without latency: slot and confirmation count.
+const LOCKOUT_SIZE: usize = U64_SIZE + 4;
+/// Size of an authorized voter entry: epoch and pubkey.
+const AUTHORIZED_VOTER_SIZE: usize = U64_SIZE + PUBKEY_SIZE;
+/// Size of an epoch credits entry: epoch, credits and previous credits.
+const EPOCH_CREDITS_SIZE: usize = 3 * U64_SIZE;
+/// Size of the prior voters of versions older than V4.
+const PRIOR_VOTERS_SIZE: usize = 32 * (PUBKEY_SIZE + 2 * U64_SIZE) + U64_SIZE + 1;
+
+const NODE_PUBKEY_OFFSET: usize = 4;
+const AUTHORIZED_WITHDRAWER_OFFSET: usize = NODE_PUBKEY_OFFSET + PUBKEY_SIZE;
+/// Offset of the commission of versions older than V4.
+const COMMISSION_OFFSET: usize = AUTHORIZED_WITHDRAWER_OFFSET + PUBKEY_SIZE;
+/// Offset of the inflation rewards commission of V4, after the collectors.
+const INFLATION_REWARDS_COMMISSION_BPS_OFFSET: usize = COMMISSION_OFFSET + 2 * PUBKEY_SIZE;
+/// Offset of the optional BLS pubkey of V4, after the commissions and the
+/// pending delegator rewards.
+const BLS_PUBKEY_OFFSET: usize = INFLATION_REWARDS_COMMISSION_BPS_OFFSET + 2 + 2 + U64_SIZE;
+
+/// A borrowed view of the vote state in vote account data.
+///
+/// Supports the V1_14_11, V3 and V4 layouts. Fields that are converted for
+/// older layouts are converted as when they are deserialized into a
+/// [`VoteStateV4`](super::VoteStateV4).
+#[derive(Debug, Clone, Copy)]
+pub struct VoteStateView<'a> {
+    data: &'a [u8],
+    version: VoteStateVersion,
+    votes_offset: usize,
+    vote_count: usize,
+    root_slot_offset: usize,
+    authorized_voters_offset: usize,
+    authorized_voter_count: usize,
+    epoch_credits_offset: usize,
+    epoch_credit_count: usize,
+    last_timestamp_offset: usize,
+}
+
+impl<'a> VoteStateView<'a> {
+    /// Creates a view of the vote state in `data`.
+    ///
+    /// Fails with [`InstructionError::InvalidAccountData`] if `data` isn't a
+    /// V1_14_11, V3 or V4 vote state.
+    pub fn try_new(data: &'a [u8]) -> Result<Self, InstructionError> {
+        let mut reader = Reader { data, offset: 0 };
+        let version = match u32::from_le_bytes(reader.read()?) {
+            1 => VoteStateVersion::V1_14_11,
+            2 => VoteStateVersion::V3,
+            3 => VoteStateVersion::V4,
+            _ => return Err(InstructionError::InvalidAccountData),
+        };
+
+        if version == VoteStateVersion::V4 {
+            reader.skip(BLS_PUBKEY_OFFSET.saturating_sub(reader.offset))?;
+            match reader.read::<1>()? {
+                [0] => (),
+                [1] => reader.skip(BLS_PUBLIC_KEY_COMPRESSED_SIZE)?,
+                _ => return Err(InstructionError::InvalidAccountData),
+            }
+        } else {
+            reader.skip(
+                COMMISSION_OFFSET
+                    .saturating_add(1)
+                    .saturating_sub(reader.offset),
+            )?;
+        }
+
+        let (votes_offset, vote_count) = reader.read_vec(vote_size(version))?;
+        let root_slot_offset = reader.offset;
+        match reader.read::<1>()? {
+            [0] => (),
+            [1] => reader.skip(U64_SIZE)?,
+            _ => return Err(InstructionError::InvalidAccountData),
+        }
+        let (authorized_voters_offset, authorized_voter_count) =
+            reader.read_vec(AUTHORIZED_VOTER_SIZE)?;
+        if version != VoteStateVersion::V4 {
+            reader.skip(PRIOR_VOTERS_SIZE)?;
+        }
+        let (epoch_credits_offset, epoch_credit_count) = reader.read_vec(EPOCH_CREDITS_SIZE)?;
+        let last_timestamp_offset = reader.offset;
+        reader.skip(2 * U64_SIZE)?;
+
+        Ok(Self {
+            data,
+            version,
+            votes_offset,
+            vote_count,
+            root_slot_offset,
+            authorized_voters_offset,
+            authorized_voter_count,
+            epoch_credits_offset,
+            epoch_credit_count,
+            last_timestamp_offset,
+        })
+    }
+
+    /// The layout of the vote state.
+    pub fn version(&self) -> VoteStateVersion {
+        self.version
+    }
+
+    pub fn node_pubkey(&self) -> Pubkey {
+        Pubkey::new_from_array(self.read_at(NODE_PUBKEY_OFFSET))
+    }
+
+    pub fn authorized_withdrawer(&self) -> Pubkey {
+        Pubkey::new_from_array(self.read_at(AUTHORIZED_WITHDRAWER_OFFSET))
+    }
+
+    /// The inflation rewards commission in basis points.
+    pub fn inflation_rewards_commission_bps(&self) -> u16 {
+        match self.version {
+            VoteStateVersion::V4 => {
+                u16::from_le_bytes(self.read_at(INFLATION_REWARDS_COMMISSION_BPS_OFFSET))
+            }
+            _ => u16::from(self.data[COMMISSION_OFFSET]).saturating_mul(100),
+        }
+    }
+
+    /// The inflation rewards commission as a percentage, rounded down for V4.
+    pub fn commission(&self) -> u8 {
+        match self.version {
+            VoteStateVersion::V4 => {
+                u8::try_from(self.inflation_rewards_commission_bps() / 100).unwrap_or(u8::MAX)
+            }
+            _ => self.data[COMMISSION_OFFSET],
+        }
+    }
+
+    /// The authorized voter for `epoch`, which is the one set for the latest
+    /// epoch up to `epoch`.
+    pub fn authorized_voter(&self, epoch: Epoch) -> Option<Pubkey> {
+        let mut authorized_voter = None;
+        for index in 0..self.authorized_voter_count {
+            let offset = self
+                .authorized_voters_offset
+                .saturating_add(index.saturating_mul(AUTHORIZED_VOTER_SIZE));
+            let voter_epoch = u64::from_le_bytes(self.read_at(offset));
+            let is_later = match authorized_voter {
+                Some((latest_epoch, _)) => voter_epoch >= latest_epoch,
+                None => true,
+            };
+            if voter_epoch <= epoch && is_later {
+                authorized_voter = Some((voter_epoch, offset.saturating_add(U64_SIZE)));
+            }
+        }
+        authorized_voter.map(|(_, offset)| Pubkey::new_from_array(self.read_at(offset)))
+    }
+
+    pub fn root_slot(&self) -> Option<Slot> {
+        (self.data[self.root_slot_offset] == 1)
+            .then(|| u64::from_le_bytes(self.read_at(self.root_slot_offset.saturating_add(1))))
+    }
+
+    /// Number of votes in the tower.
+    pub fn vote_count(&self) -> usize {
+        self.vote_count
+    }
+
+    /// The vote at `index` of the tower, oldest first. Votes of V1_14_11 have
+    /// a latency of 0.
+    pub fn vote(&self, index: usize) -> Option<LandedVote> {
+        (index < self.vote_count).then(|| self.vote_at(index))
+    }
+
+    /// The votes of the tower, oldest first.
+    pub fn votes(&self) -> impl DoubleEndedIterator<Item = LandedVote> + ExactSizeIterator + 'a {
+        let view = *self;
+        (0..self.vote_count).map(move |index| view.vote_at(index))
+    }
+
+    /// The latest vote of the tower.
+    pub fn last_vote(&self) -> Option<LandedVote> {
+        self.vote_count
+            .checked_sub(1)
+            .map(|index| self.vote_at(index))
+    }
+
+    pub fn last_voted_slot(&self) -> Option<Slot> {
+        self.last_vote().map(|vote| vote.slot())
+    }
+
+    fn vote_at(&self, index: usize) -> LandedVote {
+        let mut offset = self
+            .votes_offset
+            .saturating_add(index.saturating_mul(vote_size(self.version)));
+        let latency = if self.version == VoteStateVersion::V1_14_11 {
+            0
+        } else {
+            offset = offset.saturating_add(1);
+            self.data[offset.saturating_sub(1)]
+        };
+        let slot = u64::from_le_bytes(self.read_at(offset));
+        let confirmation_count = u32::from_le_bytes(self.read_at(offset.saturating_add(U64_SIZE)));
+        LandedVote {
+            latency,
+            lockout: Lockout::new_with_confirmation_count(slot, confirmation_count),
+        }
+    }
+
+    /// Number of entries in the epoch credits history.
+    pub fn epoch_credit_count(&self) -> usize {
+        self.epoch_credit_count
+    }
+
+    /// The credits earned by the end of each epoch, oldest first, as
+    /// `(epoch, credits, prev_credits)`.
+    pub fn epoch_credits(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (Epoch, u64, u64)> + ExactSizeIterator + 'a {
+        let view = *self;
+        (0..self.epoch_credit_count).map(move |index| {
+            let offset = view
+                .epoch_credits_offset
+                .saturating_add(index.saturating_mul(EPOCH_CREDITS_SIZE));
+            (
+                u64::from_le_bytes(view.read_at(offset)),
+                u64::from_le_bytes(view.read_at(offset.saturating_add(U64_SIZE))),
+                u64::from_le_bytes(view.read_at(offset.saturating_add(2 * U64_SIZE))),
+            )
+        })
+    }
+
+    /// Number of credits owed to this account.
+    pub fn credits(&self) -> u64 {
+        self.epoch_credits()
+            .next_back()
+            .map_or(0, |(_, credits, _)| credits)
+    }
+
+    pub fn last_timestamp(&self) -> BlockTimestamp {
+        BlockTimestamp {
+            slot: u64::from_le_bytes(self.read_at(self.last_timestamp_offset)),
+            timestamp: UnixTimestamp::from_le_bytes(
+                self.read_at(self.last_timestamp_offset.saturating_add(U64_SIZE)),
+            ),
+        }
+    }
+
+    /// Reads `N` bytes at `offset`, which was checked by `try_new`.
+    fn read_at<const N: usize>(&self, offset: usize) -> [u8; N] {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.data[offset..offset.saturating_add(N)]);
+        bytes
+    }
+}
+
+fn vote_size(version: VoteStateVersion) -> usize {
+    match version {
+        VoteStateVersion::V1_14_11 => LOCKOUT_SIZE,
+        VoteStateVersion::V3 | VoteStateVersion::V4 => LOCKOUT_SIZE.saturating_add(1),
+    }
+}
+
+/// Bounds-checked reads of the data, for `VoteStateView::try_new`.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn skip(&mut self, len: usize) -> Result<(), InstructionError> {
+        self.offset = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(InstructionError::InvalidAccountData)?;
+        Ok(())
+    }
+
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], InstructionError> {
+        let start = self.offset;
+        self.skip(N)?;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.data[start..self.offset]);
+        Ok(bytes)
+    }
+
+    /// Reads the length of a sequence of `element_size` elements, skips the
+    /// elements, and returns their offset and number.
+    fn read_vec(&mut self, element_size: usize) -> Result<(usize, usize), InstructionError> {
+        let len = usize::try_from(u64::from_le_bytes(self.read()?))
+            .map_err(|_| InstructionError::InvalidAccountData)?;
+        let offset = self.offset;
+        self.skip(
+            len.checked_mul(element_size)
+                .ok_or(InstructionError::InvalidAccountData)?,
+        )?;
+        Ok((offset, len))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+    use {
+        super::*,
+        crate::state::{CanonicalVoteState, VoteStateV3, VoteStateV4, VoteStateVersions},
+        arbitrary::{Arbitrary, Unstructured},
+    };
+
+    fn assert_view_matches(data: &[u8], vote_pubkey: &Pubkey) {
+        let view = VoteStateView::try_new(data).unwrap();
+        let versions = VoteStateVersions::deserialize(data).unwrap();
+        assert_eq!(Some(view.version()), versions.version());
+        let vote_state = VoteStateV4::deserialize(data, vote_pubkey).unwrap();
+
+        assert_eq!(view.node_pubkey(), vote_state.node_pubkey);
+        assert_eq!(
+            view.authorized_withdrawer(),
+            vote_state.authorized_withdrawer
+        );
+        assert_eq!(
+            view.inflation_rewards_commission_bps(),
+            vote_state.inflation_rewards_commission_bps
+        );
+        if view.version() != VoteStateVersion::V4 {
+            let vote_state = CanonicalVoteState::from_versions(versions, vote_pubkey)
+                .unwrap()
+                .into_v3(vote_pubkey)
+                .unwrap();
+            assert_eq!(view.commission(), vote_state.commission);
+        }
+        assert_eq!(view.root_slot(), vote_state.root_slot);
+        assert_eq!(view.vote_count(), vote_state.votes.len());
+        assert!(view.votes().eq(vote_state.votes.iter().copied()));
+        assert_eq!(view.last_vote(), vote_state.votes.back().copied());
+        assert_eq!(
+            view.last_voted_slot(),
+            vote_state.votes.back().map(LandedVote::slot)
+        );
+        assert!(view
+            .epoch_credits()
+            .eq(vote_state.epoch_credits.iter().copied()));
+        assert_eq!(view.credits(), vote_state.credits());
+        assert_eq!(view.last_timestamp(), vote_state.last_timestamp);
+
+        let epochs = vote_state
+            .authorized_voters
+            .iter()
+            .flat_map(|(epoch, _)| [epoch.saturating_sub(1), *epoch, epoch.saturating_add(1)]);
+        for epoch in epochs.chain([0, u64::MAX]) {
+            assert_eq!(
+                view.authorized_voter(epoch),
+                vote_state.authorized_voters.get_authorized_voter(epoch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_view_arbitrary() {
+        let vote_pubkey = Pubkey::new_unique();
+        for _ in 0..1000 {
+            let raw_data: Vec<u8> = (0..std::mem::size_of::<VoteStateV3>() * 4)
+                .map(|_| rand::random::<u8>())
+                .collect();
+            let versions = VoteStateVersions::arbitrary(&mut Unstructured::new(&raw_data)).unwrap();
+            assert_view_matches(&bincode::serialize(&versions).unwrap(), &vote_pubkey);
+        }
+    }
+
+    #[test]
+    fn test_view_account_data() {
+        let vote_pubkey = Pubkey::new_unique();
+        let canonical = CanonicalVoteState::from(VoteStateV4 {
+            bls_pubkey_compressed: Some([7; BLS_PUBLIC_KEY_COMPRESSED_SIZE]),
+            ..VoteStateV4::get_max_sized_vote_state()
+        });
+        let data = canonical
+            .clone()
+            .serialize(VoteStateVersion::V4, &vote_pubkey)
+            .unwrap();
+        assert_view_matches(&data, &vote_pubkey);
+
+        let canonical =
+            CanonicalVoteState::from_v3(VoteStateV3::get_max_sized_vote_state(), &vote_pubkey);
+        for version in [VoteStateVersion::V3, VoteStateVersion::V4] {
+            let data = canonical.clone().serialize(version, &vote_pubkey).unwrap();
+            assert_view_matches(&data, &vote_pubkey);
+        }
+    }
+
+    #[test]
+    fn test_view_invalid_data() {
+        let data = bincode::serialize(&VoteStateVersions::new_v4(
+            VoteStateV4::get_max_sized_vote_state(),
+        ))
+        .unwrap();
+        for len in 0..data.len() {
+            assert_eq!(
+                VoteStateView::try_new(&data[..len]).unwrap_err(),
+                InstructionError::InvalidAccountData
+            );
+        }
+        assert!(VoteStateView::try_new(&data).is_ok());
+
+        for version in [0_u32, 4] {
+            let mut data = data.clone();
+            data[..4].copy_from_slice(&version.to_le_bytes());
+            assert_eq!(
+                VoteStateView::try_new(&data).unwrap_err(),
+                InstructionError::InvalidAccountData
+            );
+        }
+
+        // invalid option tag of the BLS pubkey
+        let mut data = data;
+        data[BLS_PUBKEY_OFFSET] = 2;
+        assert_eq!(
+            VoteStateView::try_new(&data).unwrap_err(),
+            InstructionError::InvalidAccountData
+        );
+
+        // vote count overflowing the data
+        let mut data =
+            bincode::serialize(&VoteStateVersions::new_v3(VoteStateV3::default())).unwrap();
+        data[COMMISSION_OFFSET + 1..COMMISSION_OFFSET + 9].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            VoteStateView::try_new(&data).unwrap_err(),
+            InstructionError::InvalidAccountData
+        );
+    }
+}